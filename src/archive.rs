@@ -0,0 +1,607 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    io::{self, Read, Write},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, TimeZone, Timelike};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+const COMPARE_CHUNK_LENGTH: usize = 64 * 1024;
+const ARCHIVE_SUFFIXES: [(&str, ArchiveFormat); 6] = [
+    (".zip", ArchiveFormat::Zip),
+    (".tar", ArchiveFormat::Tar),
+    (".tar.gz", ArchiveFormat::TarGz),
+    (".tgz", ArchiveFormat::TarGz),
+    (".tar.zst", ArchiveFormat::TarZst),
+    (".tzst", ArchiveFormat::TarZst),
+];
+
+// Called with the path, directory flag, modification time and content of a member
+type MemberVisitor<'a> =
+    dyn FnMut(&str, bool, Option<SystemTime>, &mut dyn Read) -> io::Result<()> + 'a;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+impl ArchiveFormat {
+    pub fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        split_archive_name(name).map(|(_, _, format)| format)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    path: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<DateTime<Local>>,
+}
+
+impl ArchiveEntry {
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn get_modified(&self) -> Option<DateTime<Local>> {
+        self.modified
+    }
+}
+
+// Where a virtual directory is inside an archive. The entries are read in the
+// background when the archive itself is entered and shared by every directory
+// below it.
+#[derive(Debug, Clone)]
+pub struct ArchiveLocation {
+    path: PathBuf,
+    member_path: String,
+    entries: Option<Arc<Vec<ArchiveEntry>>>,
+}
+
+impl ArchiveLocation {
+    pub fn build(path: &Path) -> Self {
+        Self {
+            path: PathBuf::from(path),
+            member_path: String::new(),
+            entries: None,
+        }
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_member_path(&self) -> &str {
+        &self.member_path
+    }
+
+    pub fn is_archive_root(&self) -> bool {
+        self.member_path.is_empty()
+    }
+
+    pub fn get_member(&self, name: &str) -> String {
+        join_member_path(&self.member_path, name)
+    }
+
+    pub fn get_child(&self, name: &str) -> Self {
+        Self {
+            path: self.path.clone(),
+            member_path: self.get_member(name),
+            entries: self.entries.clone(),
+        }
+    }
+
+    pub fn is_read(&self) -> bool {
+        self.entries.is_some()
+    }
+
+    pub fn set_entries(&mut self, entries: Arc<Vec<ArchiveEntry>>) {
+        self.entries = Some(entries);
+    }
+
+    // Lists the members directly below this location, nothing while the
+    // entries are not read yet. Archives do not always store their
+    // directories, so those are also derived from member paths.
+    pub fn list_members(&self) -> Vec<ArchiveEntry> {
+        let Some(entries) = &self.entries else {
+            return Vec::new();
+        };
+
+        let mut members: BTreeMap<String, ArchiveEntry> = BTreeMap::new();
+        for entry in entries.iter() {
+            let Some(relative) = strip_member_path(&entry.path, &self.member_path) else {
+                continue;
+            };
+            match relative.split_once('/') {
+                Some((directory, _)) => {
+                    members
+                        .entry(String::from(directory))
+                        .or_insert_with(|| ArchiveEntry {
+                            path: join_member_path(&self.member_path, directory),
+                            is_dir: true,
+                            size: 0,
+                            modified: None,
+                        });
+                }
+                None => {
+                    members.insert(String::from(relative), entry.clone());
+                }
+            }
+        }
+        members.into_values().collect()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExtractResult {
+    extracted: usize,
+    errors: Vec<String>,
+}
+
+impl ExtractResult {
+    pub fn get_extracted(&self) -> usize {
+        self.extracted
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+}
+
+// Splits a name such as `2024-01.tar.zst` into `2024-01` and `.tar.zst`
+pub fn split_archive_name(name: &str) -> Option<(&str, &str, ArchiveFormat)> {
+    ARCHIVE_SUFFIXES.iter().find_map(|(suffix, format)| {
+        let split = name
+            .len()
+            .checked_sub(suffix.len())
+            .filter(|split| *split > 0 && name.is_char_boundary(*split))?;
+        let (stem, name_suffix) = name.split_at(split);
+        name_suffix
+            .eq_ignore_ascii_case(suffix)
+            .then_some((stem, name_suffix, *format))
+    })
+}
+
+// Lists the accepted archive suffixes for messages, e.g. `.zip, .tar, ...`
+pub fn get_archive_suffixes() -> String {
+    ARCHIVE_SUFFIXES
+        .iter()
+        .map(|(suffix, _)| *suffix)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    ArchiveFormat::detect(path).is_some()
+}
+
+pub fn read_entries(path: &Path) -> io::Result<Vec<ArchiveEntry>> {
+    let format = ArchiveFormat::detect(path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Not an archive"))?;
+    let mut entries = Vec::new();
+    if format == ArchiveFormat::Zip {
+        let mut archive = ZipArchive::new(fs::File::open(path)?).map_err(io::Error::other)?;
+        for index in 0..archive.len() {
+            let member = archive.by_index_raw(index).map_err(io::Error::other)?;
+            let Some(member_path) = member.enclosed_name().and_then(|p| normalize(&p)) else {
+                continue;
+            };
+            entries.push(ArchiveEntry {
+                path: member_path,
+                is_dir: member.is_dir(),
+                size: member.size(),
+                modified: member.last_modified().and_then(read_zip_date),
+            });
+        }
+        return Ok(entries);
+    }
+
+    let mut archive = open_tar(path, format)?;
+    for member in archive.entries()? {
+        let member = member?;
+        let entry_type = member.header().entry_type();
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            continue;
+        }
+        let Some(member_path) = normalize(&member.path()?) else {
+            continue;
+        };
+        entries.push(ArchiveEntry {
+            path: member_path,
+            is_dir: entry_type.is_dir(),
+            size: member.size(),
+            modified: member
+                .header()
+                .mtime()
+                .ok()
+                .and_then(|seconds| Local.timestamp_opt(seconds as i64, 0).single()),
+        });
+    }
+    Ok(entries)
+}
+
+// Extracts one member, everything below a member directory, or the whole
+// archive when member_path is empty. The extracted paths are relative to the
+// parent of member_path and existing files are never overwritten.
+pub fn extract(archive_path: &Path, member_path: &str, destination: &Path) -> ExtractResult {
+    let parent = match member_path.rsplit_once('/') {
+        Some((parent, _)) => parent,
+        None => "",
+    };
+    extract_matching(archive_path, destination, parent, &|path| {
+        member_path.is_empty()
+            || path == member_path
+            || strip_member_path(path, member_path).is_some()
+    })
+}
+
+// Extracts the given members to their full paths below destination
+pub fn extract_members(
+    archive_path: &Path,
+    members: &[String],
+    destination: &Path,
+) -> ExtractResult {
+    let members: HashSet<&str> = members.iter().map(|member| member.as_str()).collect();
+    extract_matching(archive_path, destination, "", &|path| {
+        members.contains(path)
+    })
+}
+
+// Writes the files into a new archive under the given member paths. The
+// format follows the file name and an existing file is never replaced.
+pub fn create(archive_path: &Path, members: &[(String, PathBuf)]) -> io::Result<()> {
+    let format = ArchiveFormat::detect(archive_path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Not an archive"))?;
+    if let Some(parent) = archive_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = fs::File::create_new(archive_path)?;
+    let file = match format {
+        ArchiveFormat::Zip => {
+            let mut writer = ZipWriter::new(file);
+            for (member_path, source) in members {
+                let metadata = fs::metadata(source)?;
+                let mut options = SimpleFileOptions::default()
+                    .compression_method(CompressionMethod::Deflated)
+                    .large_file(metadata.len() >= u32::MAX as u64);
+                if let Some(modified) = metadata.modified().ok().and_then(write_zip_date) {
+                    options = options.last_modified_time(modified);
+                }
+                writer
+                    .start_file(member_path.as_str(), options)
+                    .map_err(io::Error::other)?;
+                io::copy(&mut fs::File::open(source)?, &mut writer)?;
+            }
+            writer.finish().map_err(io::Error::other)?
+        }
+        ArchiveFormat::Tar => write_tar(file, members)?,
+        ArchiveFormat::TarGz => {
+            write_tar(GzEncoder::new(file, Compression::default()), members)?.finish()?
+        }
+        ArchiveFormat::TarZst => write_tar(zstd::Encoder::new(file, 0)?, members)?.finish()?,
+    };
+    file.sync_all()
+}
+
+// Reads the archive back and compares every member with its source file
+pub fn verify(archive_path: &Path, members: &[(String, PathBuf)]) -> io::Result<()> {
+    let mut remaining: HashMap<&str, &Path> = members
+        .iter()
+        .map(|(member_path, source)| (member_path.as_str(), source.as_path()))
+        .collect();
+    for_each_member(archive_path, &mut |path, _, _, reader| {
+        let Some(source) = remaining.remove(path) else {
+            return Ok(());
+        };
+        if !has_same_content(reader, &mut fs::File::open(source)?)? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} differs from {}", path, source.display()),
+            ));
+        }
+        Ok(())
+    })?;
+    match remaining.keys().next() {
+        Some(missing) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is missing from the archive", missing),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn extract_matching(
+    archive_path: &Path,
+    destination: &Path,
+    parent: &str,
+    is_selected: &dyn Fn(&str) -> bool,
+) -> ExtractResult {
+    let mut result = ExtractResult::default();
+    let extracted = for_each_member(archive_path, &mut |path, is_dir, modified, reader| {
+        if !is_selected(path) {
+            return Ok(());
+        }
+        let relative = strip_member_path(path, parent).unwrap_or(path);
+        let target = destination.join(relative);
+        let written = if is_dir {
+            fs::create_dir_all(&target)
+        } else {
+            write_file(&target, modified, reader)
+        };
+        match written {
+            Ok(()) if !is_dir => result.extracted += 1,
+            Ok(()) => {}
+            Err(error) => result
+                .errors
+                .push(format!("{}: {}", target.display(), error)),
+        }
+        Ok(())
+    });
+    if let Err(error) = extracted {
+        result
+            .errors
+            .push(format!("{}: {}", archive_path.display(), error));
+    }
+    result
+}
+
+// Visits every regular file and directory in the archive
+fn for_each_member(archive_path: &Path, visit: &mut MemberVisitor) -> io::Result<()> {
+    let format = ArchiveFormat::detect(archive_path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Not an archive"))?;
+    if format == ArchiveFormat::Zip {
+        let mut archive =
+            ZipArchive::new(fs::File::open(archive_path)?).map_err(io::Error::other)?;
+        for index in 0..archive.len() {
+            let mut member = archive.by_index(index).map_err(io::Error::other)?;
+            let Some(path) = member.enclosed_name().and_then(|p| normalize(&p)) else {
+                continue;
+            };
+            let modified = member
+                .last_modified()
+                .and_then(read_zip_date)
+                .map(SystemTime::from);
+            let is_dir = member.is_dir();
+            visit(&path, is_dir, modified, &mut member)?;
+        }
+        return Ok(());
+    }
+
+    let mut archive = open_tar(archive_path, format)?;
+    for member in archive.entries()? {
+        let mut member = member?;
+        let entry_type = member.header().entry_type();
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            continue;
+        }
+        let Some(path) = normalize(&member.path()?) else {
+            continue;
+        };
+        let modified = member
+            .header()
+            .mtime()
+            .ok()
+            .map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds));
+        visit(&path, entry_type.is_dir(), modified, &mut member)?;
+    }
+    Ok(())
+}
+
+fn write_tar<W: Write>(writer: W, members: &[(String, PathBuf)]) -> io::Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    for (member_path, source) in members {
+        builder.append_path_with_name(source, member_path)?;
+    }
+    builder.into_inner()
+}
+
+fn has_same_content(first: &mut dyn Read, second: &mut dyn Read) -> io::Result<bool> {
+    let mut first_buffer = vec![0; COMPARE_CHUNK_LENGTH];
+    let mut second_buffer = vec![0; COMPARE_CHUNK_LENGTH];
+    loop {
+        let first_length = read_chunk(first, &mut first_buffer)?;
+        let second_length = read_chunk(second, &mut second_buffer)?;
+        if first_buffer[..first_length] != second_buffer[..second_length] {
+            return Ok(false);
+        }
+        if first_length == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+// Fills the buffer unless the reader ends first
+fn read_chunk(reader: &mut dyn Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut length = 0;
+    while length < buffer.len() {
+        match reader.read(&mut buffer[length..]) {
+            Ok(0) => break,
+            Ok(read) => length += read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(length)
+}
+
+fn open_tar(path: &Path, format: ArchiveFormat) -> io::Result<tar::Archive<Box<dyn Read>>> {
+    let file = fs::File::open(path)?;
+    let reader: Box<dyn Read> = match format {
+        ArchiveFormat::TarGz => Box::new(GzDecoder::new(file)),
+        ArchiveFormat::TarZst => Box::new(zstd::Decoder::new(file)?),
+        _ => Box::new(file),
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+// Zip stores the local time without an offset
+fn read_zip_date(date: zip::DateTime) -> Option<DateTime<Local>> {
+    let naive =
+        NaiveDate::from_ymd_opt(date.year().into(), date.month().into(), date.day().into())?
+            .and_hms_opt(
+                date.hour().into(),
+                date.minute().into(),
+                date.second().into(),
+            )?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+fn write_zip_date(modified: SystemTime) -> Option<zip::DateTime> {
+    let modified: DateTime<Local> = modified.into();
+    zip::DateTime::from_date_and_time(
+        modified.year().try_into().ok()?,
+        modified.month() as u8,
+        modified.day() as u8,
+        modified.hour() as u8,
+        modified.minute() as u8,
+        modified.second() as u8,
+    )
+    .ok()
+}
+
+fn write_file(
+    target: &Path,
+    modified: Option<SystemTime>,
+    reader: &mut dyn Read,
+) -> io::Result<()> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = fs::File::create_new(target)?;
+    io::copy(reader, &mut file)?;
+    if let Some(modified) = modified {
+        file.set_modified(modified)?;
+    }
+    Ok(())
+}
+
+// Member paths are kept as `a/b/c`. Paths that would escape the destination
+// are dropped, which also keeps them out of the listing.
+fn normalize(path: &Path) -> Option<String> {
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => components.push(name.to_str()?),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    if components.is_empty() {
+        return None;
+    }
+    Some(components.join("/"))
+}
+
+fn join_member_path(member_path: &str, name: &str) -> String {
+    if member_path.is_empty() {
+        return String::from(name);
+    }
+    format!("{}/{}", member_path, name)
+}
+
+fn strip_member_path<'a>(path: &'a str, member_path: &str) -> Option<&'a str> {
+    if member_path.is_empty() {
+        return Some(path);
+    }
+    path.strip_prefix(member_path)?.strip_prefix('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn member_paths_are_normalized() {
+        assert_eq!(
+            normalize(Path::new("photos/./2024/a.jpg")).as_deref(),
+            Some("photos/2024/a.jpg")
+        );
+        assert_eq!(
+            normalize(Path::new("./notes.txt")).as_deref(),
+            Some("notes.txt")
+        );
+        assert!(normalize(Path::new("../escape.txt")).is_none());
+        assert!(normalize(Path::new("photos/../../escape.txt")).is_none());
+        assert!(normalize(Path::new("/etc/passwd")).is_none());
+        assert!(normalize(Path::new(".")).is_none());
+    }
+
+    #[test]
+    fn archive_names_are_split_at_their_suffix() {
+        assert_eq!(
+            split_archive_name("2024-01.tar.zst"),
+            Some(("2024-01", ".tar.zst", ArchiveFormat::TarZst))
+        );
+        assert_eq!(
+            split_archive_name("backup.TAR.GZ"),
+            Some(("backup", ".TAR.GZ", ArchiveFormat::TarGz))
+        );
+        assert_eq!(
+            split_archive_name("photos.zip"),
+            Some(("photos", ".zip", ArchiveFormat::Zip))
+        );
+        assert_eq!(
+            split_archive_name("logs.tar"),
+            Some(("logs", ".tar", ArchiveFormat::Tar))
+        );
+        assert!(split_archive_name(".zip").is_none());
+        assert!(split_archive_name("notes.txt").is_none());
+        assert!(split_archive_name("résumé").is_none());
+        assert_eq!(
+            get_archive_suffixes(),
+            ".zip, .tar, .tar.gz, .tgz, .tar.zst, .tzst"
+        );
+    }
+
+    #[test]
+    fn member_paths_are_joined_and_stripped() {
+        assert_eq!(join_member_path("", "a.txt"), "a.txt");
+        assert_eq!(join_member_path("docs", "a.txt"), "docs/a.txt");
+        assert_eq!(strip_member_path("docs/a.txt", "docs"), Some("a.txt"));
+        assert_eq!(strip_member_path("docs/a.txt", ""), Some("docs/a.txt"));
+        assert_eq!(strip_member_path("documents/a.txt", "docs"), None);
+    }
+
+    #[test]
+    fn members_are_listed_once_entries_are_read() {
+        let entry = |path: &str| ArchiveEntry {
+            path: String::from(path),
+            is_dir: false,
+            size: 1,
+            modified: None,
+        };
+        let mut location = ArchiveLocation::build(Path::new("/tmp/photos.tar.gz"));
+        assert!(!location.is_read());
+        assert!(location.list_members().is_empty());
+
+        location.set_entries(Arc::new(vec![
+            entry("notes.txt"),
+            entry("2024/a.jpg"),
+            entry("2024/b.jpg"),
+        ]));
+        let members = location.list_members();
+        let names: Vec<&str> = members.iter().map(ArchiveEntry::get_path).collect();
+        assert_eq!(names, vec!["2024", "notes.txt"]);
+        let child = location.get_child("2024");
+        assert!(child.is_read());
+        assert_eq!(child.list_members().len(), 2);
+    }
+}
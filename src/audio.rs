@@ -0,0 +1,128 @@
+use std::{fs::File, path::Path};
+
+use symphonia::core::{
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::{MetadataOptions, MetadataRevision, StandardTagKey, Tag},
+    probe::Hint,
+};
+
+use crate::pattern::{TokenValue, Tokens};
+
+const AUDIO_EXTENSIONS: [&str; 7] = ["mp3", "flac", "m4a", "m4b", "ogg", "oga", "opus"];
+pub const TOKEN_NAMES: [&str; 7] = [
+    "artist",
+    "album",
+    "title",
+    "album_artist",
+    "year",
+    "track",
+    "disc",
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct AudioTags {
+    artist: Option<String>,
+    album_artist: Option<String>,
+    album: Option<String>,
+    title: Option<String>,
+    year: Option<u64>,
+    track: Option<u64>,
+    disc: Option<u64>,
+}
+
+impl AudioTags {
+    pub fn read(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        if !AUDIO_EXTENSIONS.contains(&extension.as_str()) {
+            return None;
+        }
+        let file = File::open(path).ok()?;
+        let stream = MediaSourceStream::new(Box::new(file), Default::default());
+        let mut hint = Hint::new();
+        hint.with_extension(&extension);
+
+        let mut probed = symphonia::default::get_probe()
+            .format(
+                &hint,
+                stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .ok()?;
+
+        // ID3v2 tags are read while probing, while the tags of the other
+        // formats are part of the container itself
+        let mut audio_tags = Self::default();
+        if let Some(metadata) = probed.metadata.get() {
+            if let Some(revision) = metadata.current() {
+                audio_tags.read_revision(revision);
+            }
+        }
+        if let Some(revision) = probed.format.metadata().current() {
+            audio_tags.read_revision(revision);
+        }
+        Some(audio_tags)
+    }
+
+    pub fn insert_tokens(&self, tokens: &mut Tokens) {
+        let text_tokens = [
+            ("artist", &self.artist),
+            ("album", &self.album),
+            ("title", &self.title),
+        ];
+        for (name, value) in text_tokens {
+            if let Some(value) = value {
+                tokens.insert(name, TokenValue::Text(value.clone()));
+            }
+        }
+        if let Some(album_artist) = self.album_artist.as_ref().or(self.artist.as_ref()) {
+            tokens.insert("album_artist", TokenValue::Text(album_artist.clone()));
+        }
+        // The release year of a song replaces the year of the file
+        let number_tokens = [
+            ("year", self.year),
+            ("track", self.track),
+            ("disc", self.disc),
+        ];
+        for (name, value) in number_tokens {
+            if let Some(value) = value {
+                tokens.insert(name, TokenValue::Number(value));
+            }
+        }
+    }
+
+    fn read_revision(&mut self, revision: &MetadataRevision) {
+        for tag in revision.tags() {
+            match tag.std_key {
+                Some(StandardTagKey::Artist) => self.artist = read_text(tag),
+                Some(StandardTagKey::AlbumArtist) => self.album_artist = read_text(tag),
+                Some(StandardTagKey::Album) => self.album = read_text(tag),
+                Some(StandardTagKey::TrackTitle) => self.title = read_text(tag),
+                Some(StandardTagKey::Date | StandardTagKey::ReleaseDate) => {
+                    self.year = self.year.or(read_number(tag))
+                }
+                Some(StandardTagKey::TrackNumber) => self.track = read_number(tag),
+                Some(StandardTagKey::DiscNumber) => self.disc = read_number(tag),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn read_text(tag: &Tag) -> Option<String> {
+    let text = tag.value.to_string();
+    let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    if text.is_empty() {
+        return None;
+    }
+    Some(String::from(text))
+}
+
+// Numbers are often stored as text such as `3/12` for track numbers or
+// `1999-05-01` for dates, so only the leading digits are used
+fn read_number(tag: &Tag) -> Option<u64> {
+    let text = read_text(tag)?;
+    let digits: String = text.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
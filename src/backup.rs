@@ -0,0 +1,502 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::{self, Metadata},
+    io::Error,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use chrono::Local;
+use iced::futures::channel::mpsc::{self as async_mpsc, UnboundedReceiver};
+
+use crate::{
+    filter::EntryFilter,
+    index, plan, scan,
+    storage::{self, SpaceShortage},
+    transfer::{self, ProgressThrottle, TransferProgress},
+};
+
+pub const DELETED_DIRECTORY: &str = ".deleted";
+// FAT, common on USB drives, keeps modification times in steps of two seconds
+const TIME_TOLERANCE: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackupCompare {
+    SizeAndTime,
+    Hash,
+}
+
+impl BackupCompare {
+    pub const ALL: [BackupCompare; 2] = [BackupCompare::SizeAndTime, BackupCompare::Hash];
+}
+
+impl fmt::Display for BackupCompare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            BackupCompare::SizeAndTime => "Compare size and date",
+            BackupCompare::Hash => "Compare contents",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackupActionKind {
+    New,
+    Changed,
+    Removed,
+}
+
+impl BackupActionKind {
+    pub const ALL: [BackupActionKind; 3] = [
+        BackupActionKind::New,
+        BackupActionKind::Changed,
+        BackupActionKind::Removed,
+    ];
+}
+
+impl fmt::Display for BackupActionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            BackupActionKind::New => "New",
+            BackupActionKind::Changed => "Changed",
+            BackupActionKind::Removed => "Removed",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+// One file to copy to the backup, or to set aside because it is gone from
+// the source. Paths are relative to both folders.
+#[derive(Debug, Clone)]
+pub struct BackupAction {
+    kind: BackupActionKind,
+    path: PathBuf,
+    size: u64,
+}
+
+impl BackupAction {
+    pub fn get_kind(&self) -> BackupActionKind {
+        self.kind
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BackupPlan {
+    source: PathBuf,
+    target: PathBuf,
+    actions: Vec<BackupAction>,
+    unchanged: usize,
+    kept: usize,
+    space_shortages: Vec<SpaceShortage>,
+    errors: Vec<String>,
+}
+
+impl BackupPlan {
+    pub fn get_source(&self) -> &Path {
+        &self.source
+    }
+
+    pub fn get_target(&self) -> &Path {
+        &self.target
+    }
+
+    pub fn get_actions(&self) -> &Vec<BackupAction> {
+        &self.actions
+    }
+
+    pub fn get_count(&self, kind: BackupActionKind) -> usize {
+        self.actions
+            .iter()
+            .filter(|action| action.kind == kind)
+            .count()
+    }
+
+    pub fn get_size(&self, kind: BackupActionKind) -> u64 {
+        self.actions
+            .iter()
+            .filter(|action| action.kind == kind)
+            .map(|action| action.size)
+            .sum()
+    }
+
+    pub fn get_unchanged(&self) -> usize {
+        self.unchanged
+    }
+
+    // Files only in the backup, left where they are
+    pub fn get_kept(&self) -> usize {
+        self.kept
+    }
+
+    pub fn get_space_shortages(&self) -> &Vec<SpaceShortage> {
+        &self.space_shortages
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BackupResult {
+    copied: usize,
+    removed: usize,
+    errors: Vec<String>,
+}
+
+impl BackupResult {
+    pub fn get_copied(&self) -> usize {
+        self.copied
+    }
+
+    pub fn get_removed(&self) -> usize {
+        self.removed
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum BackupEvent {
+    Progress(TransferProgress),
+    Finished(BackupResult),
+}
+
+// Compares source and target without changing anything. Files gone from the
+// source are only planned to be set aside when keep_removed is on. Hidden
+// files are backed up whether or not the browser shows them.
+pub fn plan_backup(
+    source: &Path,
+    target: &Path,
+    compare: BackupCompare,
+    keep_removed: bool,
+    ignore_patterns: &[String],
+) -> BackupPlan {
+    let mut backup_plan = BackupPlan {
+        source: PathBuf::from(source),
+        target: PathBuf::from(target),
+        actions: Vec::new(),
+        unchanged: 0,
+        kept: 0,
+        space_shortages: Vec::new(),
+        errors: Vec::new(),
+    };
+    let (resolved_source, resolved_target) = (resolve_path(source), resolve_path(target));
+    if resolved_target.starts_with(&resolved_source)
+        || resolved_source.starts_with(&resolved_target)
+    {
+        backup_plan.errors.push(format!(
+            "{} and {} cannot be inside each other",
+            source.display(),
+            target.display()
+        ));
+        return backup_plan;
+    }
+
+    let source_files = list_files(source, ignore_patterns, &mut backup_plan.errors);
+    let mut target_files = if target.exists() {
+        list_files(target, ignore_patterns, &mut backup_plan.errors)
+    } else {
+        HashMap::new()
+    };
+
+    let mut paths: Vec<&PathBuf> = source_files.keys().collect();
+    paths.sort();
+    for path in paths {
+        let source_metadata = &source_files[path];
+        let kind = match target_files.remove(path) {
+            None => BackupActionKind::New,
+            Some(target_metadata) => {
+                match is_changed(
+                    compare,
+                    &source.join(path),
+                    source_metadata,
+                    &target.join(path),
+                    &target_metadata,
+                ) {
+                    Ok(true) => BackupActionKind::Changed,
+                    Ok(false) => {
+                        backup_plan.unchanged += 1;
+                        continue;
+                    }
+                    Err(error) => {
+                        backup_plan
+                            .errors
+                            .push(format!("{}: {}", path.display(), error));
+                        continue;
+                    }
+                }
+            }
+        };
+        backup_plan.actions.push(BackupAction {
+            kind,
+            path: path.clone(),
+            size: source_metadata.len(),
+        });
+    }
+
+    let mut removed: Vec<(PathBuf, Metadata)> = target_files.into_iter().collect();
+    removed.sort_by(|a, b| a.0.cmp(&b.0));
+    for (path, metadata) in removed {
+        if !keep_removed {
+            backup_plan.kept += 1;
+            continue;
+        }
+        backup_plan.actions.push(BackupAction {
+            kind: BackupActionKind::Removed,
+            path,
+            size: metadata.len(),
+        });
+    }
+
+    let writes: Vec<(PathBuf, u64)> = backup_plan
+        .actions
+        .iter()
+        .filter(|action| action.kind != BackupActionKind::Removed)
+        .map(|action| (target.join(&action.path), action.size))
+        .collect();
+    let writes: Vec<(&Path, u64)> = writes
+        .iter()
+        .map(|(path, size)| (path.as_path(), *size))
+        .collect();
+    backup_plan.space_shortages = storage::find_space_shortages(&writes);
+    backup_plan
+}
+
+// Copies new and changed files first. Removed files are moved into a folder
+// named after the day inside .deleted, so nothing is lost to a bad backup.
+pub fn start_backup(backup_plan: BackupPlan, verify: bool) -> UnboundedReceiver<BackupEvent> {
+    let (sender, receiver) = async_mpsc::unbounded();
+    thread::spawn(move || {
+        let mut result = BackupResult::default();
+        let deleted_root = backup_plan
+            .target
+            .join(DELETED_DIRECTORY)
+            .join(Local::now().format("%Y-%m-%d").to_string());
+        let count = backup_plan.actions.len();
+        let mut throttle = ProgressThrottle::new();
+        for (done, action) in backup_plan.actions.iter().enumerate() {
+            let source = backup_plan.source.join(&action.path);
+            let target = backup_plan.target.join(&action.path);
+            let mut progress = |copied, size| {
+                if throttle.is_due() {
+                    let _ = sender.unbounded_send(BackupEvent::Progress(TransferProgress::build(
+                        done, count, &source, copied, size,
+                    )));
+                }
+            };
+            let applied = match action.kind {
+                BackupActionKind::New => create_parent(&target)
+                    .and_then(|_| transfer::copy_file(&source, &target, verify, &mut progress)),
+                BackupActionKind::Changed => {
+                    transfer::replace_file(&source, &target, verify, &mut progress)
+                }
+                BackupActionKind::Removed => set_aside(&target, &deleted_root.join(&action.path)),
+            };
+            match (applied, action.kind) {
+                (Ok(()), BackupActionKind::Removed) => result.removed += 1,
+                (Ok(()), _) => result.copied += 1,
+                (Err(error), _) => {
+                    result
+                        .errors
+                        .push(format!("{}: {}", action.path.display(), error))
+                }
+            }
+        }
+        let _ = sender.unbounded_send(BackupEvent::Finished(result));
+    });
+    receiver
+}
+
+// Files by their path relative to root. Earlier removed files in .deleted
+// and partial copies left by an interrupted backup are not part of it.
+fn list_files(
+    root: &Path,
+    ignore_patterns: &[String],
+    errors: &mut Vec<String>,
+) -> HashMap<PathBuf, Metadata> {
+    let mut files = HashMap::new();
+    let exclusions = [
+        format!("/{}/", DELETED_DIRECTORY),
+        String::from(transfer::PARTIAL_PATTERN),
+    ];
+    let entry_filter = match EntryFilter::build(true, ignore_patterns)
+        .and_then(|entry_filter| entry_filter.with_rooted_patterns(root, &exclusions))
+    {
+        Ok(entry_filter) => entry_filter,
+        Err(error) => {
+            errors.push(error.to_string());
+            return files;
+        }
+    };
+    scan::walk(root, &entry_filter, errors, |path, metadata| {
+        if metadata.is_file() {
+            if let Ok(relative) = path.strip_prefix(root) {
+                files.insert(PathBuf::from(relative), metadata.clone());
+            }
+        }
+        true
+    });
+    files
+}
+
+fn is_changed(
+    compare: BackupCompare,
+    source: &Path,
+    source_metadata: &Metadata,
+    target: &Path,
+    target_metadata: &Metadata,
+) -> Result<bool, Error> {
+    if source_metadata.len() != target_metadata.len() {
+        return Ok(true);
+    }
+    match compare {
+        BackupCompare::SizeAndTime => {
+            let (Ok(source_modified), Ok(target_modified)) =
+                (source_metadata.modified(), target_metadata.modified())
+            else {
+                return Ok(true);
+            };
+            let difference = source_modified
+                .duration_since(target_modified)
+                .unwrap_or_else(|error| error.duration());
+            Ok(difference > TIME_TOLERANCE)
+        }
+        BackupCompare::Hash => Ok(index::hash_file(source)? != index::hash_file(target)?),
+    }
+}
+
+// Resolves symlinks and relative parts. The target may not exist yet, so its
+// closest existing ancestor is resolved instead.
+fn resolve_path(path: &Path) -> PathBuf {
+    for ancestor in path.ancestors() {
+        if let Ok(resolved) = ancestor.canonicalize() {
+            return resolved.join(path.strip_prefix(ancestor).unwrap_or(path));
+        }
+    }
+    PathBuf::from(path)
+}
+
+fn create_parent(path: &Path) -> Result<(), Error> {
+    match path.parent() {
+        Some(parent) => fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+}
+
+fn set_aside(path: &Path, deleted_path: &Path) -> Result<(), Error> {
+    let (Some(directory), Some(file_name)) = (deleted_path.parent(), deleted_path.file_name())
+    else {
+        return Ok(());
+    };
+    fs::create_dir_all(directory)?;
+    let destination = plan::unique_destination(directory, file_name, &HashSet::new());
+    transfer::move_file(path, &destination, false, &mut |_, _| {})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use filetime::FileTime;
+
+    #[test]
+    fn is_changed_compares_size_time_or_contents() {
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-backup-changed-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let (source, target) = (directory.join("source"), directory.join("target"));
+        let check = |compare, contents: &str, seconds: i64| {
+            fs::write(&source, "contents").unwrap();
+            fs::write(&target, contents).unwrap();
+            filetime::set_file_mtime(&source, FileTime::from_unix_time(1_000_000, 0)).unwrap();
+            filetime::set_file_mtime(&target, FileTime::from_unix_time(1_000_000 + seconds, 0))
+                .unwrap();
+            let (source_metadata, target_metadata) = (
+                fs::metadata(&source).unwrap(),
+                fs::metadata(&target).unwrap(),
+            );
+            is_changed(
+                compare,
+                &source,
+                &source_metadata,
+                &target,
+                &target_metadata,
+            )
+            .unwrap()
+        };
+
+        assert!(check(BackupCompare::SizeAndTime, "longer contents", 0));
+        assert!(!check(BackupCompare::SizeAndTime, "contents", 0));
+        // Within the two seconds FAT rounds to, in either direction
+        assert!(!check(BackupCompare::SizeAndTime, "contents", 2));
+        assert!(!check(BackupCompare::SizeAndTime, "contents", -1));
+        assert!(check(BackupCompare::SizeAndTime, "contents", 3));
+        assert!(check(BackupCompare::SizeAndTime, "contents", -3));
+        assert!(!check(BackupCompare::Hash, "contents", 60));
+        assert!(check(BackupCompare::Hash, "CONTENTS", 0));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn plan_includes_hidden_files_and_skips_partial_and_set_aside_files() {
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-backup-plan-{}", std::process::id()));
+        let (source, target) = (directory.join("source"), directory.join("target"));
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(target.join(DELETED_DIRECTORY).join("2024-01-01")).unwrap();
+        fs::write(source.join(".profile"), "").unwrap();
+        fs::write(source.join("skip.tmp"), "").unwrap();
+        fs::write(target.join(".profile.0-0.part"), "").unwrap();
+        fs::write(
+            target.join(DELETED_DIRECTORY).join("2024-01-01/old.txt"),
+            "",
+        )
+        .unwrap();
+
+        let backup_plan = plan_backup(
+            &source,
+            &target,
+            BackupCompare::SizeAndTime,
+            true,
+            &[String::from("*.tmp")],
+        );
+        assert!(backup_plan.get_errors().is_empty());
+        let actions: Vec<(BackupActionKind, &Path)> = backup_plan
+            .get_actions()
+            .iter()
+            .map(|action| (action.get_kind(), action.get_path()))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![(BackupActionKind::New, Path::new(".profile"))]
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn plan_refuses_folders_inside_each_other() {
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-backup-nested-{}", std::process::id()));
+        let source = directory.join("source");
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(directory.join("other")).unwrap();
+        let target = directory.join("other/../source/backup");
+
+        let backup_plan = plan_backup(&source, &target, BackupCompare::SizeAndTime, false, &[]);
+        assert_eq!(backup_plan.get_errors().len(), 1);
+        assert!(backup_plan.get_actions().is_empty());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
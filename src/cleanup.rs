@@ -0,0 +1,285 @@
+use std::{
+    fs,
+    io::Error,
+    path::{Path, PathBuf},
+};
+
+use crate::protection::{ProtectedPath, Protection};
+
+// Files the operating system leaves behind, which do not make a folder worth keeping
+const JUNK_FILES: [&str; 3] = [".DS_Store", "Thumbs.db", "desktop.ini"];
+
+#[derive(Debug, Clone)]
+pub struct EmptyDirectory {
+    path: PathBuf,
+    junk: Vec<PathBuf>,
+}
+
+impl EmptyDirectory {
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_junk(&self) -> &Vec<PathBuf> {
+        &self.junk
+    }
+}
+
+// Directories are listed bottom-up, so removing them in order empties each
+// parent before it is reached
+#[derive(Debug, Clone)]
+pub struct CleanupPlan {
+    root: PathBuf,
+    directories: Vec<EmptyDirectory>,
+    protected: Vec<ProtectedPath>,
+    errors: Vec<String>,
+}
+
+impl CleanupPlan {
+    pub fn get_root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn get_directories(&self) -> &Vec<EmptyDirectory> {
+        &self.directories
+    }
+
+    pub fn get_protected(&self) -> &Vec<ProtectedPath> {
+        &self.protected
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CleanupResult {
+    removed: usize,
+    errors: Vec<String>,
+}
+
+impl CleanupResult {
+    pub fn get_removed(&self) -> usize {
+        self.removed
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+}
+
+fn is_junk_file(name: &str) -> bool {
+    JUNK_FILES.contains(&name)
+}
+
+// The root itself is never listed, even when everything below it is empty
+pub fn find_empty_directories(root: &Path, protection: &Protection) -> CleanupPlan {
+    let mut plan = CleanupPlan {
+        root: PathBuf::from(root),
+        directories: Vec::new(),
+        protected: Vec::new(),
+        errors: Vec::new(),
+    };
+    if let Some(protected) = protection.check_path(root) {
+        plan.protected.push(protected);
+        return plan;
+    }
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(error) => {
+            plan.errors.push(format!("{}: {}", root.display(), error));
+            return plan;
+        }
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            visit_directory(&entry.path(), protection, &mut plan);
+        }
+    }
+    plan
+}
+
+pub fn remove_empty_directories(plan: &CleanupPlan) -> CleanupResult {
+    let mut result = CleanupResult::default();
+    for directory in &plan.directories {
+        if directory.path == plan.root || !directory.path.starts_with(&plan.root) {
+            continue;
+        }
+        match remove_directory(&directory.path) {
+            Ok(()) => result.removed += 1,
+            Err(error) => result
+                .errors
+                .push(format!("{}: {}", directory.path.display(), error)),
+        }
+    }
+    result
+}
+
+// Returns whether the directory holds nothing but junk and empty directories.
+// Symlinks, protected directories and unreadable entries count as content.
+fn visit_directory(directory: &Path, protection: &Protection, plan: &mut CleanupPlan) -> bool {
+    if let Some(protected) = protection.check_directory(directory) {
+        plan.protected.push(protected);
+        return false;
+    }
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(error) => {
+            plan.errors
+                .push(format!("{}: {}", directory.display(), error));
+            return false;
+        }
+    };
+    let mut is_empty = true;
+    let mut junk = Vec::new();
+    for entry in entries {
+        let Ok(entry) = entry else {
+            is_empty = false;
+            continue;
+        };
+        let Ok(file_type) = entry.file_type() else {
+            is_empty = false;
+            continue;
+        };
+        let path = entry.path();
+        if file_type.is_dir() {
+            // Every subdirectory is visited so nested skeletons are found too
+            is_empty &= visit_directory(&path, protection, plan);
+        } else if file_type.is_file() && is_junk_file(&entry.file_name().to_string_lossy()) {
+            junk.push(path);
+        } else {
+            is_empty = false;
+        }
+    }
+    if is_empty {
+        plan.directories.push(EmptyDirectory {
+            path: PathBuf::from(directory),
+            junk,
+        });
+    }
+    is_empty
+}
+
+// The contents are checked again, so anything added since the preview keeps
+// the directory in place
+fn remove_directory(directory: &Path) -> Result<(), Error> {
+    let mut junk = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_file() && is_junk_file(&entry.file_name().to_string_lossy()) {
+            junk.push(entry.path());
+        } else {
+            return Err(Error::new(
+                std::io::ErrorKind::DirectoryNotEmpty,
+                "Not empty anymore, kept",
+            ));
+        }
+    }
+    for path in junk {
+        fs::remove_file(path)?;
+    }
+    fs::remove_dir(directory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_paths(plan: &CleanupPlan) -> Vec<PathBuf> {
+        plan.get_directories()
+            .iter()
+            .map(|directory| PathBuf::from(directory.get_path().strip_prefix(&plan.root).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn nested_empty_directories_are_removed_bottom_up() {
+        let root =
+            std::env::temp_dir().join(format!("fileorder-cleanup-nested-{}", std::process::id()));
+        fs::create_dir_all(root.join("a/b/c")).unwrap();
+        fs::create_dir_all(root.join("kept/empty")).unwrap();
+        fs::write(root.join("a/b/.DS_Store"), "").unwrap();
+        fs::write(root.join("kept/notes.txt"), "").unwrap();
+
+        let plan = find_empty_directories(&root, &Protection::build(&[]));
+        assert!(plan.get_errors().is_empty(), "{:?}", plan.get_errors());
+        let mut paths = get_paths(&plan);
+        let position = |path: &str| paths.iter().position(|p| p == Path::new(path)).unwrap();
+        assert!(position("a/b/c") < position("a/b"));
+        assert!(position("a/b") < position("a"));
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("a"),
+                PathBuf::from("a/b"),
+                PathBuf::from("a/b/c"),
+                PathBuf::from("kept/empty"),
+            ]
+        );
+        let junk = plan
+            .get_directories()
+            .iter()
+            .find(|directory| directory.get_path() == root.join("a/b"))
+            .map(EmptyDirectory::get_junk);
+        assert_eq!(junk, Some(&vec![root.join("a/b/.DS_Store")]));
+
+        let result = remove_empty_directories(&plan);
+        assert!(result.get_errors().is_empty(), "{:?}", result.get_errors());
+        assert_eq!(result.get_removed(), 4);
+        assert!(!root.join("a").exists());
+        assert!(!root.join("kept/empty").exists());
+        assert!(root.join("kept/notes.txt").is_file());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn root_is_never_removed() {
+        let root =
+            std::env::temp_dir().join(format!("fileorder-cleanup-root-{}", std::process::id()));
+        fs::create_dir_all(root.join("empty")).unwrap();
+        fs::write(root.join("Thumbs.db"), "").unwrap();
+
+        let plan = find_empty_directories(&root, &Protection::build(&[]));
+        assert_eq!(get_paths(&plan), vec![PathBuf::from("empty")]);
+        assert_eq!(remove_empty_directories(&plan).get_removed(), 1);
+        assert!(root.is_dir());
+        assert!(root.join("Thumbs.db").is_file());
+
+        // A plan listing the root still leaves it in place
+        let plan = CleanupPlan {
+            root: root.clone(),
+            directories: vec![EmptyDirectory {
+                path: root.clone(),
+                junk: Vec::new(),
+            }],
+            protected: Vec::new(),
+            errors: Vec::new(),
+        };
+        assert_eq!(remove_empty_directories(&plan).get_removed(), 0);
+        assert!(root.is_dir());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn directories_that_gained_a_file_are_kept() {
+        let root =
+            std::env::temp_dir().join(format!("fileorder-cleanup-gained-{}", std::process::id()));
+        fs::create_dir_all(root.join("outer/inner")).unwrap();
+
+        let plan = find_empty_directories(&root, &Protection::build(&[]));
+        assert_eq!(plan.get_directories().len(), 2);
+        fs::write(root.join("outer/inner/new.txt"), "").unwrap();
+
+        let result = remove_empty_directories(&plan);
+        assert_eq!(result.get_removed(), 0);
+        assert_eq!(result.get_errors().len(), 2, "{:?}", result.get_errors());
+        assert!(root.join("outer/inner/new.txt").is_file());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
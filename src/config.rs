@@ -0,0 +1,284 @@
+use std::{
+    fs,
+    io::Error,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    filter::EntryFilter,
+    import,
+    protection::{self, Protection},
+    retention::Policy,
+    rules::{self, Rule},
+    util,
+};
+
+const CONFIG_DIRECTORY: &str = "fileorder";
+const CONFIG_FILE: &str = "config.toml";
+const MAX_RECENT_LOCATIONS: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    bookmarks: Vec<PathBuf>,
+    recent_locations: Vec<PathBuf>,
+    show_hidden: bool,
+    show_thumbnails: bool,
+    verify_copies: bool,
+    import_root: PathBuf,
+    import_pattern: String,
+    ignore_patterns: Vec<String>,
+    protected_paths: Vec<PathBuf>,
+    rules: Vec<Rule>,
+    policies: Vec<Policy>,
+    #[serde(skip)]
+    read_only: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bookmarks: Vec::new(),
+            recent_locations: Vec::new(),
+            show_hidden: false,
+            show_thumbnails: false,
+            verify_copies: true,
+            import_root: dirs::home_dir().unwrap_or_default(),
+            import_pattern: String::from(import::DEFAULT_IMPORT_PATTERN),
+            ignore_patterns: Vec::new(),
+            protected_paths: protection::default_protected_paths(),
+            rules: rules::default_rules(),
+            policies: Vec::new(),
+            read_only: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Stands in for a config.toml that could not be read. It is never saved,
+    // so the defaults do not replace the settings in the broken file.
+    pub fn read_only() -> Self {
+        Self {
+            read_only: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn load() -> Result<Self, Error> {
+        let Some(path) = get_config_path() else {
+            return Ok(Self::new());
+        };
+        match fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content).map_err(Error::other),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let Some(path) = get_config_path() else {
+            return Err(Error::other("Could not find configuration directory"));
+        };
+        self.save_to(&path)
+    }
+
+    fn save_to(&self, path: &Path) -> Result<(), Error> {
+        if self.read_only {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = toml::to_string_pretty(self).map_err(Error::other)?;
+        util::write_file_atomically(path, content.as_bytes())
+    }
+
+    pub fn get_bookmarks(&self) -> &Vec<PathBuf> {
+        &self.bookmarks
+    }
+
+    // The bookmark and location changes return whether anything changed, so
+    // the file is only written when there is something new to save
+    pub fn add_bookmark(&mut self, path: &Path) -> bool {
+        if self.bookmarks.iter().any(|bookmark| bookmark == path) {
+            return false;
+        }
+        self.bookmarks.push(PathBuf::from(path));
+        true
+    }
+
+    pub fn remove_bookmark(&mut self, index: usize) -> bool {
+        if index >= self.bookmarks.len() {
+            return false;
+        }
+        self.bookmarks.remove(index);
+        true
+    }
+
+    pub fn move_bookmark(&mut self, from: usize, to: usize) -> bool {
+        if from == to || from >= self.bookmarks.len() || to >= self.bookmarks.len() {
+            return false;
+        }
+        self.bookmarks.swap(from, to);
+        true
+    }
+
+    pub fn get_recent_locations(&self) -> &Vec<PathBuf> {
+        &self.recent_locations
+    }
+
+    pub fn add_recent_location(&mut self, path: &Path) -> bool {
+        if self
+            .recent_locations
+            .first()
+            .is_some_and(|first| first == path)
+        {
+            return false;
+        }
+        self.recent_locations.retain(|location| location != path);
+        self.recent_locations.insert(0, PathBuf::from(path));
+        self.recent_locations.truncate(MAX_RECENT_LOCATIONS);
+        true
+    }
+
+    pub fn get_show_hidden(&self) -> bool {
+        self.show_hidden
+    }
+
+    pub fn set_show_hidden(&mut self, show_hidden: bool) {
+        self.show_hidden = show_hidden;
+    }
+
+    pub fn get_show_thumbnails(&self) -> bool {
+        self.show_thumbnails
+    }
+
+    pub fn set_show_thumbnails(&mut self, show_thumbnails: bool) {
+        self.show_thumbnails = show_thumbnails;
+    }
+
+    // Moves to another drive are copies, read back before the original is removed
+    pub fn get_verify_copies(&self) -> bool {
+        self.verify_copies
+    }
+
+    pub fn set_verify_copies(&mut self, verify_copies: bool) {
+        self.verify_copies = verify_copies;
+    }
+
+    pub fn get_import_root(&self) -> &Path {
+        &self.import_root
+    }
+
+    pub fn set_import_root(&mut self, import_root: PathBuf) {
+        self.import_root = import_root;
+    }
+
+    pub fn get_import_pattern(&self) -> &str {
+        &self.import_pattern
+    }
+
+    pub fn set_import_pattern(&mut self, import_pattern: String) {
+        self.import_pattern = import_pattern;
+    }
+
+    pub fn build_entry_filter(&self) -> Result<EntryFilter, Error> {
+        EntryFilter::build(self.show_hidden, &self.ignore_patterns)
+    }
+
+    pub fn get_ignore_patterns(&self) -> &Vec<String> {
+        &self.ignore_patterns
+    }
+
+    pub fn build_protection(&self) -> Protection {
+        Protection::build(&self.protected_paths)
+    }
+
+    pub fn get_rules(&self) -> &Vec<Rule> {
+        &self.rules
+    }
+
+    pub fn get_policies(&self) -> &Vec<Policy> {
+        &self.policies
+    }
+}
+
+fn get_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|directory| directory.join(CONFIG_DIRECTORY).join(CONFIG_FILE))
+}
+
+pub fn get_user_directories() -> Vec<(String, PathBuf)> {
+    let user_directories = [
+        ("Home", dirs::home_dir()),
+        ("Desktop", dirs::desktop_dir()),
+        ("Documents", dirs::document_dir()),
+        ("Downloads", dirs::download_dir()),
+        ("Music", dirs::audio_dir()),
+        ("Pictures", dirs::picture_dir()),
+        ("Videos", dirs::video_dir()),
+    ];
+    user_directories
+        .into_iter()
+        .filter_map(|(name, path)| path.map(|path| (String::from(name), path)))
+        .filter(|(_, path)| path.is_dir())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_location_is_unchanged_when_already_first() {
+        let mut config = Config::new();
+        assert!(config.add_recent_location(Path::new("/home")));
+        assert!(config.add_recent_location(Path::new("/tmp")));
+        assert!(!config.add_recent_location(Path::new("/tmp")));
+        assert!(config.add_recent_location(Path::new("/home")));
+        assert_eq!(
+            config.recent_locations,
+            vec![PathBuf::from("/home"), PathBuf::from("/tmp")]
+        );
+    }
+
+    #[test]
+    fn bookmark_changes_report_whether_anything_changed() {
+        let mut config = Config::new();
+        assert!(config.add_bookmark(Path::new("/home")));
+        assert!(!config.add_bookmark(Path::new("/home")));
+        assert!(config.add_bookmark(Path::new("/tmp")));
+        assert!(!config.move_bookmark(0, 0));
+        assert!(!config.move_bookmark(0, 2));
+        assert!(config.move_bookmark(0, 1));
+        assert!(!config.remove_bookmark(2));
+        assert!(config.remove_bookmark(0));
+        assert_eq!(config.bookmarks, vec![PathBuf::from("/home")]);
+    }
+
+    #[test]
+    fn read_only_config_is_never_saved() {
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-config-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join(CONFIG_FILE);
+        fs::write(&path, "not = [valid").unwrap();
+
+        assert!(Config::read_only().save_to(&path).is_ok());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "not = [valid");
+
+        let mut config = Config::new();
+        config.add_bookmark(Path::new("/home"));
+        config.save_to(&path).unwrap();
+        let saved: Config = toml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved.bookmarks, vec![PathBuf::from("/home")]);
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
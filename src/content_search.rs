@@ -0,0 +1,475 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use iced::futures::channel::mpsc::{self as async_mpsc, UnboundedReceiver};
+use regex::{Regex, RegexBuilder};
+
+use crate::{filter::EntryFilter, scan};
+
+const BATCH_INTERVAL: Duration = Duration::from_millis(100);
+const BINARY_CHECK_LENGTH: usize = 8 * 1024;
+const READ_BUFFER_LENGTH: usize = 64 * 1024;
+// Files are streamed, so the limit is only about time. Longer lines are
+// matched on their start and the rest is skipped.
+pub const MAX_FILE_SIZE: u64 = 1024 * 1024 * 1024;
+const MAX_READ_LINE_LENGTH: u64 = 1024 * 1024;
+const MAX_MATCHES_PER_FILE: usize = 100;
+const MAX_MATCHED_FILES: usize = 5000;
+const MAX_LINE_LENGTH: usize = 300;
+pub const CONTEXT_LINE_CHOICES: [usize; 5] = [0, 1, 2, 3, 5];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseMode {
+    Smart,
+    Sensitive,
+    Insensitive,
+}
+
+impl CaseMode {
+    pub const ALL: [CaseMode; 3] = [CaseMode::Smart, CaseMode::Sensitive, CaseMode::Insensitive];
+}
+
+impl fmt::Display for CaseMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            CaseMode::Smart => "Smart case",
+            CaseMode::Sensitive => "Match case",
+            CaseMode::Insensitive => "Ignore case",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ContentQuery {
+    pattern: String,
+    use_regex: bool,
+    case_mode: CaseMode,
+    context_lines: usize,
+}
+
+impl ContentQuery {
+    pub fn new() -> Self {
+        Self {
+            pattern: String::new(),
+            use_regex: false,
+            case_mode: CaseMode::Smart,
+            context_lines: 2,
+        }
+    }
+
+    pub fn get_pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn set_pattern(&mut self, pattern: String) {
+        self.pattern = pattern;
+    }
+
+    pub fn get_use_regex(&self) -> bool {
+        self.use_regex
+    }
+
+    pub fn set_use_regex(&mut self, use_regex: bool) {
+        self.use_regex = use_regex;
+    }
+
+    pub fn get_case_mode(&self) -> CaseMode {
+        self.case_mode
+    }
+
+    pub fn set_case_mode(&mut self, case_mode: CaseMode) {
+        self.case_mode = case_mode;
+    }
+
+    pub fn get_context_lines(&self) -> usize {
+        self.context_lines
+    }
+
+    pub fn set_context_lines(&mut self, context_lines: usize) {
+        self.context_lines = context_lines;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ContentMatcher {
+    regex: Regex,
+    context_lines: usize,
+}
+
+impl ContentMatcher {
+    // Smart case ignores case unless the pattern has an uppercase letter
+    pub fn build(query: &ContentQuery) -> Result<Self, String> {
+        if query.pattern.is_empty() {
+            return Err(String::from("Enter text to search for"));
+        }
+        let pattern = if query.use_regex {
+            query.pattern.clone()
+        } else {
+            regex::escape(&query.pattern)
+        };
+        let case_insensitive = match query.case_mode {
+            CaseMode::Smart => !query.pattern.chars().any(char::is_uppercase),
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(case_insensitive)
+            .build()
+            .map_err(|error| error.to_string())?;
+        Ok(Self {
+            regex,
+            context_lines: query.context_lines,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchLine {
+    number: usize,
+    text: String,
+    is_match: bool,
+}
+
+impl MatchLine {
+    pub fn get_number(&self) -> usize {
+        self.number
+    }
+
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_match(&self) -> bool {
+        self.is_match
+    }
+}
+
+// Matching lines of one file with their context, in line order
+#[derive(Debug, Clone)]
+pub struct ContentMatch {
+    path: PathBuf,
+    lines: Vec<MatchLine>,
+    match_count: usize,
+}
+
+impl ContentMatch {
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_lines(&self) -> &Vec<MatchLine> {
+        &self.lines
+    }
+
+    pub fn get_match_count(&self) -> usize {
+        self.match_count
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ContentEvent {
+    Found(Vec<ContentMatch>),
+    Finished {
+        searched: usize,
+        skipped: Vec<PathBuf>,
+        errors: Vec<String>,
+    },
+}
+
+enum WorkerResult {
+    Searched(Option<ContentMatch>),
+    Skipped(PathBuf),
+    Error(String),
+}
+
+// Walks the tree on one thread and searches the files on a pool of workers.
+// Results are sent in batches until the search ends or is cancelled.
+pub fn start(
+    root: PathBuf,
+    matcher: ContentMatcher,
+    entry_filter: EntryFilter,
+    cancelled: Arc<AtomicBool>,
+) -> UnboundedReceiver<ContentEvent> {
+    let (sender, receiver) = async_mpsc::unbounded();
+    thread::spawn(move || {
+        let workers = thread::available_parallelism()
+            .map(|workers| workers.get())
+            .unwrap_or(4);
+        let (path_sender, path_receiver) = mpsc::sync_channel::<PathBuf>(256);
+        let path_receiver = Arc::new(Mutex::new(path_receiver));
+        let (result_sender, result_receiver) = mpsc::channel::<WorkerResult>();
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                let path_receiver = path_receiver.clone();
+                let result_sender = result_sender.clone();
+                let matcher = &matcher;
+                let cancelled = &cancelled;
+                scope.spawn(move || loop {
+                    let Ok(path) = path_receiver.lock().map(|receiver| receiver.recv()) else {
+                        break;
+                    };
+                    let Ok(path) = path else {
+                        break;
+                    };
+                    if cancelled.load(Ordering::Relaxed) {
+                        continue;
+                    }
+                    let result = match search_file(&path, matcher, cancelled) {
+                        Ok(content_match) => WorkerResult::Searched(content_match),
+                        Err(error) => WorkerResult::Error(format!("{}: {}", path.display(), error)),
+                    };
+                    if result_sender.send(result).is_err() {
+                        break;
+                    }
+                });
+            }
+            let walk_results = result_sender.clone();
+            let cancelled = &cancelled;
+            let entry_filter = &entry_filter;
+            let root = &root;
+            scope.spawn(move || {
+                let mut errors = Vec::new();
+                scan::walk(root, entry_filter, &mut errors, |path, metadata| {
+                    if cancelled.load(Ordering::Relaxed) {
+                        return false;
+                    }
+                    if !metadata.is_file() {
+                        return true;
+                    }
+                    if metadata.len() > MAX_FILE_SIZE {
+                        return walk_results
+                            .send(WorkerResult::Skipped(PathBuf::from(path)))
+                            .is_ok();
+                    }
+                    path_sender.send(PathBuf::from(path)).is_ok()
+                });
+                for error in errors {
+                    let _ = walk_results.send(WorkerResult::Error(error));
+                }
+            });
+            drop(result_sender);
+
+            collect_results(result_receiver, &sender, cancelled);
+        });
+    });
+    receiver
+}
+
+fn collect_results(
+    result_receiver: mpsc::Receiver<WorkerResult>,
+    sender: &async_mpsc::UnboundedSender<ContentEvent>,
+    cancelled: &AtomicBool,
+) {
+    let mut batch = Vec::new();
+    let mut skipped = Vec::new();
+    let mut errors = Vec::new();
+    let mut searched = 0;
+    let mut matched = 0;
+    loop {
+        match result_receiver.recv_timeout(BATCH_INTERVAL) {
+            Ok(WorkerResult::Searched(content_match)) => {
+                searched += 1;
+                if let Some(content_match) = content_match {
+                    matched += 1;
+                    batch.push(content_match);
+                    if matched >= MAX_MATCHED_FILES && !cancelled.swap(true, Ordering::Relaxed) {
+                        errors.push(format!("Stopped after {} files", MAX_MATCHED_FILES));
+                    }
+                }
+                continue;
+            }
+            Ok(WorkerResult::Skipped(path)) => {
+                skipped.push(path);
+                continue;
+            }
+            Ok(WorkerResult::Error(error)) => {
+                errors.push(error);
+                continue;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+        if !batch.is_empty()
+            && sender
+                .unbounded_send(ContentEvent::Found(std::mem::take(&mut batch)))
+                .is_err()
+        {
+            // Nobody is listening anymore
+            cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+    if !batch.is_empty() {
+        let _ = sender.unbounded_send(ContentEvent::Found(batch));
+    }
+    let _ = sender.unbounded_send(ContentEvent::Finished {
+        searched,
+        skipped,
+        errors,
+    });
+}
+
+// Files with a NUL byte near the start are binary and skipped. The file is
+// read line by line, keeping only the lines that may be shown as context of
+// the next match.
+fn search_file(
+    path: &Path,
+    matcher: &ContentMatcher,
+    cancelled: &AtomicBool,
+) -> std::io::Result<Option<ContentMatch>> {
+    let mut reader = BufReader::with_capacity(READ_BUFFER_LENGTH, File::open(path)?);
+    let start = reader.fill_buf()?;
+    if start[..start.len().min(BINARY_CHECK_LENGTH)].contains(&0) {
+        return Ok(None);
+    }
+
+    let mut match_count = 0;
+    let mut shown: Vec<MatchLine> = Vec::new();
+    let mut before: VecDeque<MatchLine> = VecDeque::with_capacity(matcher.context_lines);
+    let mut after = 0;
+    let mut buffer = Vec::new();
+    let mut number = 0;
+    while read_line(&mut reader, &mut buffer)? {
+        if cancelled.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        number += 1;
+        let content = String::from_utf8_lossy(&buffer);
+        let content = content.trim_end_matches(['\n', '\r']);
+        let is_match = matcher.regex.is_match(content);
+        if is_match {
+            match_count += 1;
+        }
+        let line = |is_match| MatchLine {
+            number,
+            text: content.chars().take(MAX_LINE_LENGTH).collect(),
+            is_match,
+        };
+        if is_match && match_count <= MAX_MATCHES_PER_FILE {
+            shown.extend(before.drain(..));
+            shown.push(line(true));
+            after = matcher.context_lines;
+        } else if after > 0 {
+            // A line shown as context of an earlier match can be a match too
+            shown.push(line(is_match));
+            after -= 1;
+        } else if matcher.context_lines > 0 {
+            if before.len() == matcher.context_lines {
+                before.pop_front();
+            }
+            before.push_back(line(false));
+        }
+    }
+    if match_count == 0 {
+        return Ok(None);
+    }
+    Ok(Some(ContentMatch {
+        path: PathBuf::from(path),
+        lines: shown,
+        match_count,
+    }))
+}
+
+// Reads the next line into buffer, cut off at MAX_READ_LINE_LENGTH. Returns
+// false at the end of the file.
+fn read_line(reader: &mut impl BufRead, buffer: &mut Vec<u8>) -> std::io::Result<bool> {
+    buffer.clear();
+    let read = reader
+        .by_ref()
+        .take(MAX_READ_LINE_LENGTH)
+        .read_until(b'\n', buffer)?;
+    if read == 0 {
+        return Ok(false);
+    }
+    if buffer.last() != Some(&b'\n') && read as u64 == MAX_READ_LINE_LENGTH {
+        reader.skip_until(b'\n')?;
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn search(
+        name: &str,
+        content: &[u8],
+        pattern: &str,
+        context_lines: usize,
+    ) -> Option<ContentMatch> {
+        let path =
+            std::env::temp_dir().join(format!("fileorder-content-{}-{}", name, std::process::id()));
+        fs::write(&path, content).unwrap();
+        let mut query = ContentQuery::new();
+        query.set_pattern(String::from(pattern));
+        query.set_context_lines(context_lines);
+        let matcher = ContentMatcher::build(&query).unwrap();
+        let result = search_file(&path, &matcher, &AtomicBool::new(false)).unwrap();
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    fn shown(content_match: &ContentMatch) -> Vec<(usize, bool)> {
+        content_match
+            .get_lines()
+            .iter()
+            .map(|line| (line.get_number(), line.is_match()))
+            .collect()
+    }
+
+    #[test]
+    fn matches_are_shown_with_their_context() {
+        let content = b"one\ntwo\nneedle\nfour\nfive\nsix\nseven\nneedle\r\nnine\n";
+        let content_match = search("context", content, "needle", 1).unwrap();
+        assert_eq!(content_match.get_match_count(), 2);
+        assert_eq!(
+            shown(&content_match),
+            vec![
+                (2, false),
+                (3, true),
+                (4, false),
+                (7, false),
+                (8, true),
+                (9, false)
+            ]
+        );
+        assert_eq!(content_match.get_lines()[4].get_text(), "needle");
+    }
+
+    #[test]
+    fn overlapping_context_is_shown_once() {
+        let content = b"a\nneedle\nb\nneedle\nc\n";
+        let content_match = search("overlap", content, "needle", 2).unwrap();
+        assert_eq!(
+            shown(&content_match),
+            vec![(1, false), (2, true), (3, false), (4, true), (5, false)]
+        );
+    }
+
+    #[test]
+    fn binary_files_and_files_without_matches_are_skipped() {
+        assert!(search("binary", b"needle\0\x01\x02", "needle", 0).is_none());
+        assert!(search("none", b"hay\nstack\n", "needle", 0).is_none());
+    }
+
+    #[test]
+    fn long_lines_are_cut_off_without_losing_line_numbers() {
+        let mut content = vec![b'x'; MAX_READ_LINE_LENGTH as usize + 10];
+        content.extend_from_slice(b" needle\nneedle\n");
+        let content_match = search("long", &content, "needle", 0).unwrap();
+        assert_eq!(shown(&content_match), vec![(2, true)]);
+    }
+}
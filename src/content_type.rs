@@ -0,0 +1,152 @@
+use std::{
+    fs::File,
+    io::{Error, Read},
+    path::{Path, PathBuf},
+};
+
+use infer::MatcherType;
+
+use crate::{filter::EntryFilter, scan};
+
+const SNIFF_LENGTH: u64 = 8192;
+
+// Extensions that are used interchangeably for the same content
+const EXTENSION_ALIASES: [&[&str]; 11] = [
+    &["jpg", "jpeg", "jpe", "jfif"],
+    &[
+        "tif", "tiff", "dng", "cr2", "nef", "nrw", "arw", "orf", "pef",
+    ],
+    &["heif", "heic", "avif"],
+    &["mp4", "m4v", "m4a", "m4b", "mov"],
+    &["ogg", "oga", "ogv", "opus"],
+    &["gz", "tgz"],
+    &[
+        "zip", "jar", "apk", "docx", "xlsx", "pptx", "odt", "ods", "odp", "epub", "cbz",
+    ],
+    &["exe", "dll", "msi"],
+    &["html", "htm", "xhtml"],
+    &["xml", "svg", "plist"],
+    &["sh", "bash", "zsh"],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContentType {
+    mime: &'static str,
+    extension: &'static str,
+    kind: &'static str,
+}
+
+impl ContentType {
+    pub fn detect(path: &Path) -> Result<Option<Self>, Error> {
+        let mut buffer = Vec::new();
+        File::open(path)?
+            .take(SNIFF_LENGTH)
+            .read_to_end(&mut buffer)?;
+        Ok(Self::detect_from_bytes(&buffer))
+    }
+
+    pub fn detect_from_bytes(buffer: &[u8]) -> Option<Self> {
+        if let Some(found) = infer::get(buffer) {
+            return Some(Self {
+                mime: found.mime_type(),
+                extension: found.extension(),
+                kind: get_kind_name(found.matcher_type()),
+            });
+        }
+        if !buffer.is_empty() && is_text(buffer) {
+            return Some(Self {
+                mime: "text/plain",
+                extension: "txt",
+                kind: "text",
+            });
+        }
+        None
+    }
+
+    pub fn get_mime(&self) -> &'static str {
+        self.mime
+    }
+
+    pub fn get_kind(&self) -> &'static str {
+        self.kind
+    }
+
+    // Plain text has no signature and files without an extension make no
+    // claim about their content, so both are always accepted
+    pub fn matches_extension(&self, path: &Path) -> bool {
+        if self.kind == "text" {
+            return true;
+        }
+        let Some(extension) = path.extension().and_then(|e| e.to_str()) else {
+            return true;
+        };
+        let extension = extension.to_lowercase();
+        if extension == self.extension {
+            return true;
+        }
+        EXTENSION_ALIASES.iter().any(|aliases| {
+            aliases.contains(&extension.as_str()) && aliases.contains(&self.extension)
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeMismatch {
+    path: PathBuf,
+    content_type: ContentType,
+}
+
+impl TypeMismatch {
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_content_type(&self) -> &ContentType {
+        &self.content_type
+    }
+}
+
+pub fn find_type_mismatches(root: &Path, entry_filter: &EntryFilter) -> Vec<TypeMismatch> {
+    let scan_result = scan::scan(root, entry_filter, None);
+    scan_result
+        .get_files()
+        .iter()
+        .filter_map(|file| {
+            let content_type = ContentType::detect(file.get_path()).ok().flatten()?;
+            if content_type.matches_extension(file.get_path()) {
+                return None;
+            }
+            Some(TypeMismatch {
+                path: PathBuf::from(file.get_path()),
+                content_type,
+            })
+        })
+        .collect()
+}
+
+fn get_kind_name(matcher_type: MatcherType) -> &'static str {
+    match matcher_type {
+        MatcherType::App => "executable",
+        MatcherType::Archive => "archive",
+        MatcherType::Audio => "audio",
+        MatcherType::Book => "book",
+        MatcherType::Doc => "document",
+        MatcherType::Font => "font",
+        MatcherType::Image => "image",
+        MatcherType::Text => "text",
+        MatcherType::Video => "video",
+        MatcherType::Custom => "other",
+    }
+}
+
+// The buffer may end in the middle of a multi-byte character, so an
+// incomplete sequence at the very end is still accepted as text
+fn is_text(buffer: &[u8]) -> bool {
+    if buffer.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(buffer) {
+        Ok(_) => true,
+        Err(error) => error.error_len().is_none(),
+    }
+}
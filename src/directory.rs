@@ -1,12 +1,15 @@
-use chrono::{DateTime, Local};
-
+use crate::archive::{self, ArchiveEntry, ArchiveLocation};
 use crate::file::{File, FileMetadata};
+use crate::filter::{DirectoryFilter, EntryFilter};
 use crate::util;
 use std::ffi::{OsStr, OsString};
 use std::fs::{self, FileType, Metadata, ReadDir};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[derive(Debug)]
 struct ParsedFile {
+    path: PathBuf,
     file_type: FileType,
     file_name: OsString,
     metadata: Metadata,
@@ -19,6 +22,7 @@ pub struct Directory {
     directories: Vec<Directory>,
     files: Vec<File>,
     metadata: FileMetadata,
+    archive: Option<ArchiveLocation>,
 }
 
 impl Directory {
@@ -29,6 +33,7 @@ impl Directory {
             directories: Vec::new(),
             files: Vec::new(),
             metadata: FileMetadata::new(),
+            archive: None,
         }
     }
 
@@ -45,6 +50,7 @@ impl Directory {
             directories,
             files,
             metadata,
+            archive: None,
         }
     }
 
@@ -58,23 +64,31 @@ impl Directory {
         &mut self,
         original_path: &OsStr,
         directories_read: &mut usize,
+        entry_filter: &EntryFilter,
     ) -> std::io::Result<()> {
         let current_dir = self;
+        if current_dir.archive.is_some() {
+            current_dir.insert_archive_members(directories_read, entry_filter);
+            return Ok(());
+        }
         let path = OsString::from(original_path);
-        current_dir.insert_files_and_directories(path.as_os_str(), directories_read)?;
+        current_dir.insert_files_and_directories(
+            path.as_os_str(),
+            directories_read,
+            entry_filter,
+        )?;
         Ok(())
     }
 
-    pub fn find_directory_by_id(&mut self, id_stack: &Vec<usize>) -> &mut Directory {
+    pub fn find_directory_by_id(&mut self, id_stack: &[usize]) -> &mut Directory {
         let mut current_dir = self;
-        let mut iter = id_stack.iter();
 
-        while let Some(iterator) = iter.next() {
-            let index = util::find_directory_index_by_id(current_dir, *iterator);
+        for id in id_stack {
+            let index = util::find_directory_index_by_id(current_dir, *id);
             if let Some(i) = index {
                 current_dir = &mut current_dir.get_mut_directories()[i];
             }
-            if let None = index {
+            if index.is_none() {
                 break;
             }
         }
@@ -84,24 +98,28 @@ impl Directory {
 
     pub fn insert_new_sub_directory(
         &mut self,
-        id_stack: &Vec<usize>,
+        id_stack: &[usize],
         current_path: &mut OsString,
         directories_read: &mut usize,
         selected_directory_id: usize,
+        entry_filter: &EntryFilter,
     ) -> std::io::Result<()> {
         let mut current_dir = self;
-        for i in 0..id_stack.len() {
-            let result = util::find_directory_index_by_id(&mut current_dir, selected_directory_id);
+        for id in id_stack {
+            let result = util::find_directory_index_by_id(current_dir, selected_directory_id);
             if let Some(index) = result {
                 current_path.push("/");
                 current_path.push(current_dir.get_directories()[index].get_name());
 
-                current_dir.get_mut_directories()[index]
-                    .write_directory_content(current_path.as_os_str(), directories_read)?;
+                current_dir.get_mut_directories()[index].write_directory_content(
+                    current_path.as_os_str(),
+                    directories_read,
+                    entry_filter,
+                )?;
                 break;
             }
 
-            let result: Option<usize> = util::find_directory_index_by_id(current_dir, id_stack[i]);
+            let result: Option<usize> = util::find_directory_index_by_id(current_dir, *id);
 
             if let Some(selected) = result {
                 current_dir = &mut current_dir.get_mut_directories()[selected];
@@ -110,6 +128,43 @@ impl Directory {
         Ok(())
     }
 
+    pub fn find_directory_id_by_name(&self, name: &OsStr) -> Option<usize> {
+        self.directories
+            .iter()
+            .find(|directory| directory.get_name() == name)
+            .map(|directory| directory.get_directory_id())
+    }
+
+    // A typed path may lead through a directory that is hidden or ignored in
+    // the listing. It is added from the filesystem so the path still opens.
+    pub fn insert_unlisted_directory(
+        &mut self,
+        name: &OsStr,
+        path: &Path,
+        directories_read: &mut usize,
+    ) -> Option<usize> {
+        if self.archive.is_some() {
+            return None;
+        }
+        let metadata = fs::metadata(path).ok()?;
+        let archive =
+            (metadata.is_file() && archive::is_archive(path)).then(|| ArchiveLocation::build(path));
+        if !metadata.is_dir() && archive.is_none() {
+            return None;
+        }
+        let id = *directories_read;
+        self.directories.push(Directory {
+            id,
+            name: OsString::from(name),
+            directories: Vec::new(),
+            files: Vec::new(),
+            metadata: FileMetadata::read(&metadata),
+            archive,
+        });
+        *directories_read += 1;
+        Some(id)
+    }
+
     pub fn get_directory_id(&self) -> usize {
         self.id
     }
@@ -146,18 +201,81 @@ impl Directory {
         &self.metadata
     }
 
+    pub fn get_archive(&self) -> &Option<ArchiveLocation> {
+        &self.archive
+    }
+
+    // An archive that was entered but whose entries are still being read
+    pub fn is_unread_archive(&self) -> bool {
+        self.archive
+            .as_ref()
+            .is_some_and(|location| !location.is_read())
+    }
+
+    pub fn insert_archive_entries(
+        &mut self,
+        entries: Arc<Vec<ArchiveEntry>>,
+        directories_read: &mut usize,
+        entry_filter: &EntryFilter,
+    ) {
+        if let Some(location) = &mut self.archive {
+            location.set_entries(entries);
+        }
+        self.insert_archive_members(directories_read, entry_filter);
+    }
+
+    fn insert_archive_members(&mut self, directories_read: &mut usize, entry_filter: &EntryFilter) {
+        let Some(location) = self.archive.clone() else {
+            return;
+        };
+        let members = location.list_members();
+        let mut directories: Vec<Directory> = Vec::new();
+        let mut files: Vec<File> = Vec::new();
+        for member in members {
+            let name = member.get_path().rsplit('/').next().unwrap_or_default();
+            if !entry_filter.get_show_hidden() && name.starts_with('.') {
+                continue;
+            }
+            let metadata =
+                FileMetadata::build(None, member.get_modified(), None, member.get_size());
+            if member.is_dir() {
+                directories.push(Directory {
+                    id: *directories_read,
+                    name: OsString::from(name),
+                    directories: Vec::new(),
+                    files: Vec::new(),
+                    metadata,
+                    archive: Some(location.get_child(name)),
+                });
+                *directories_read += 1;
+            } else {
+                files.push(File::build(OsStr::new(name), metadata));
+            }
+        }
+        self.insert_files(files);
+        self.insert_directories(directories);
+    }
+
     fn insert_files_and_directories(
         &mut self,
         path: &OsStr,
         directories_read: &mut usize,
+        entry_filter: &EntryFilter,
     ) -> std::io::Result<()> {
         match fs::read_dir(path) {
             Ok(entries) => {
                 let mut directories: Vec<Directory> = Vec::new();
                 let mut files: Vec<File> = Vec::new();
+                let directory_filter = entry_filter.for_directory(Path::new(path));
 
                 // Insert directories and files to current_dir
-                self.read_entries(entries, &mut directories, &mut files, directories_read);
+                self.read_entries(
+                    entries,
+                    &mut directories,
+                    &mut files,
+                    directories_read,
+                    &directory_filter,
+                );
                 self.insert_files(files);
                 self.insert_directories(directories);
                 Ok(())
@@ -186,28 +304,33 @@ impl Directory {
         directories: &mut Vec<Directory>,
         files: &mut Vec<File>,
         directories_read: &mut usize,
+        directory_filter: &DirectoryFilter,
     ) {
-        let list_of_files: Vec<_> = self.get_entries(entries);
+        let list_of_files: Vec<_> = self.get_entries(entries, directory_filter);
         for file in list_of_files {
-            if file.file_type.is_dir() {
+            // Archives are entered like directories and listed when opened
+            let archive = (file.file_type.is_file() && archive::is_archive(&file.path))
+                .then(|| ArchiveLocation::build(&file.path));
+            if file.file_type.is_dir() || archive.is_some() {
                 directories.push(Directory {
                     id: *directories_read,
                     name: file.file_name,
                     directories: Vec::new(),
                     files: Vec::new(),
-                    metadata: self.read_metadata_from_file(&file.metadata),
+                    metadata: FileMetadata::read(&file.metadata),
+                    archive,
                 });
                 *directories_read += 1;
             } else if file.file_type.is_file() {
                 files.push(File::build(
                     file.file_name.as_os_str(),
-                    self.read_metadata_from_file(&file.metadata),
+                    FileMetadata::read(&file.metadata),
                 ));
             }
         }
     }
 
-    fn get_entries(&self, entries: ReadDir) -> Vec<ParsedFile> {
+    fn get_entries(&self, entries: ReadDir, directory_filter: &DirectoryFilter) -> Vec<ParsedFile> {
         entries
             .filter_map(|entry| match entry {
                 Ok(entry) => {
@@ -227,7 +350,12 @@ impl Directory {
 
                     if let Ok(mt) = metadata {
                         if let Ok(ft) = file_type {
+                            let path = entry.path();
+                            if directory_filter.is_ignored(&path, ft.is_dir()) {
+                                return None;
+                            }
                             return Some(ParsedFile {
+                                path,
                                 file_name,
                                 file_type: ft,
                                 metadata: mt,
@@ -238,32 +366,9 @@ impl Directory {
                 }
                 Err(error) => {
                     eprintln!("Error occured when reading entries: {}", error);
-                    return None;
+                    None
                 }
             })
             .collect()
     }
-
-    fn read_metadata_from_file(&self, metadata: &Metadata) -> FileMetadata {
-        let mut file_metadata_created: Option<DateTime<Local>> = None;
-        let mut file_metadata_modified: Option<DateTime<Local>> = None;
-        let mut file_metadata_accessed: Option<DateTime<Local>> = None;
-
-        if let Ok(created) = metadata.created() {
-            file_metadata_created = Some(created.into());
-        }
-        if let Ok(modified) = metadata.modified() {
-            file_metadata_modified = Some(modified.into());
-        }
-
-        if let Ok(accessed) = metadata.accessed() {
-            file_metadata_accessed = Some(accessed.into());
-        }
-
-        FileMetadata::build(
-            file_metadata_created,
-            file_metadata_modified,
-            file_metadata_accessed,
-        )
-    }
 }
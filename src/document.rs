@@ -0,0 +1,426 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
+use lopdf::Document;
+use quick_xml::{escape, events::Event, Reader, XmlVersion};
+use zip::ZipArchive;
+
+use crate::pattern::{TokenValue, Tokens};
+
+const OOXML_EXTENSIONS: [&str; 6] = ["docx", "docm", "xlsx", "xlsm", "pptx", "pptm"];
+const ODF_EXTENSIONS: [&str; 3] = ["odt", "ods", "odp"];
+pub const TOKEN_NAMES: [&str; 5] = [
+    "doc_title",
+    "doc_author",
+    "doc_subject",
+    "doc_created",
+    "doc_modified",
+];
+const MAX_PROPERTIES_LENGTH: u64 = 1024 * 1024;
+// Larger PDFs are not parsed as a whole, only searched for XMP near their
+// start and end, where writers put the metadata stream
+const MAX_PDF_LENGTH: u64 = 32 * 1024 * 1024;
+const XMP_WINDOW_LENGTH: u64 = 1024 * 1024;
+const XMP_START: &[u8] = b"<x:xmpmeta";
+const XMP_END: &[u8] = b"</x:xmpmeta>";
+
+// Local names of the XML elements and XMP attributes holding the properties.
+// ODF keeps the author in `initial-creator` and the last editor in `creator`.
+const XML_PROPERTIES: [&str; 10] = [
+    "title",
+    "creator",
+    "initial-creator",
+    "subject",
+    "created",
+    "creation-date",
+    "CreateDate",
+    "modified",
+    "date",
+    "ModifyDate",
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct DocumentMetadata {
+    title: Option<String>,
+    author: Option<String>,
+    subject: Option<String>,
+    created: Option<DateTime<Local>>,
+    modified: Option<DateTime<Local>>,
+}
+
+impl DocumentMetadata {
+    pub fn read(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        if extension == "pdf" {
+            read_pdf(path)
+        } else if OOXML_EXTENSIONS.contains(&extension.as_str()) {
+            read_zip_properties(path, "docProps/core.xml")
+        } else if ODF_EXTENSIONS.contains(&extension.as_str()) {
+            read_zip_properties(path, "meta.xml")
+        } else {
+            None
+        }
+    }
+
+    pub fn get_properties(&self) -> Vec<(&'static str, String)> {
+        let mut properties = Vec::new();
+        let text_properties = [
+            ("Title", &self.title),
+            ("Author", &self.author),
+            ("Subject", &self.subject),
+        ];
+        for (name, value) in text_properties {
+            if let Some(value) = value {
+                properties.push((name, value.clone()));
+            }
+        }
+        let date_properties = [("Created", self.created), ("Modified", self.modified)];
+        for (name, value) in date_properties {
+            if let Some(value) = value {
+                properties.push((name, value.format("%Y-%m-%d %H:%M").to_string()));
+            }
+        }
+        properties
+    }
+
+    pub fn insert_tokens(&self, tokens: &mut Tokens) {
+        let text_tokens = [
+            ("doc_title", &self.title),
+            ("doc_author", &self.author),
+            ("doc_subject", &self.subject),
+        ];
+        for (name, value) in text_tokens {
+            if let Some(value) = value {
+                tokens.insert(name, TokenValue::Text(value.clone()));
+            }
+        }
+        if let Some(created) = self.created {
+            tokens.insert("doc_created", TokenValue::Date(created));
+        }
+        if let Some(modified) = self.modified {
+            tokens.insert("doc_modified", TokenValue::Date(modified));
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.get_properties().is_empty()
+    }
+
+    // Values already read take precedence over the given ones
+    fn merge(&mut self, other: Self) {
+        self.title = self.title.take().or(other.title);
+        self.author = self.author.take().or(other.author);
+        self.subject = self.subject.take().or(other.subject);
+        self.created = self.created.or(other.created);
+        self.modified = self.modified.or(other.modified);
+    }
+}
+
+// The Info dictionary is preferred, and XMP fills in what it lacks. The XMP
+// packet is meant to be stored uncompressed so it is found from the raw bytes.
+fn read_pdf(path: &Path) -> Option<DocumentMetadata> {
+    let mut file = fs::File::open(path).ok()?;
+    let length = file.metadata().ok()?.len();
+    if length > MAX_PDF_LENGTH {
+        return read_pdf_xmp(&mut file, length);
+    }
+    let mut buffer = Vec::with_capacity(length as usize);
+    file.take(MAX_PDF_LENGTH).read_to_end(&mut buffer).ok()?;
+    let mut document_metadata = DocumentMetadata::default();
+    if let Ok(info) = Document::load_metadata_mem(&buffer) {
+        document_metadata = DocumentMetadata {
+            title: info.title.and_then(clean_text),
+            author: info.author.and_then(clean_text),
+            subject: info.subject.and_then(clean_text),
+            created: info.creation_date.as_deref().and_then(parse_pdf_date),
+            modified: info.modification_date.as_deref().and_then(parse_pdf_date),
+        };
+    }
+    if let Some(xmp) = find_xmp_packet(&buffer) {
+        document_metadata.merge(read_xml_properties(&String::from_utf8_lossy(xmp)));
+    }
+    if document_metadata.is_empty() {
+        return None;
+    }
+    Some(document_metadata)
+}
+
+// The end is searched first, as incremental updates append the current packet
+fn read_pdf_xmp(file: &mut fs::File, length: u64) -> Option<DocumentMetadata> {
+    for start in [length.saturating_sub(XMP_WINDOW_LENGTH), 0] {
+        let mut buffer = Vec::new();
+        file.seek(SeekFrom::Start(start)).ok()?;
+        (&mut *file)
+            .take(XMP_WINDOW_LENGTH)
+            .read_to_end(&mut buffer)
+            .ok()?;
+        if let Some(xmp) = find_xmp_packet(&buffer) {
+            let document_metadata = read_xml_properties(&String::from_utf8_lossy(xmp));
+            if !document_metadata.is_empty() {
+                return Some(document_metadata);
+            }
+        }
+    }
+    None
+}
+
+fn read_zip_properties(path: &Path, properties_name: &str) -> Option<DocumentMetadata> {
+    let file = fs::File::open(path).ok()?;
+    let mut archive = ZipArchive::new(file).ok()?;
+    let properties = archive.by_name(properties_name).ok()?;
+    if properties.size() > MAX_PROPERTIES_LENGTH {
+        return None;
+    }
+    let mut xml = String::new();
+    properties
+        .take(MAX_PROPERTIES_LENGTH)
+        .read_to_string(&mut xml)
+        .ok()?;
+    let document_metadata = read_xml_properties(&xml);
+    if document_metadata.is_empty() {
+        return None;
+    }
+    Some(document_metadata)
+}
+
+fn find_xmp_packet(buffer: &[u8]) -> Option<&[u8]> {
+    // Incremental updates append a new packet, so the last one is current
+    let start = buffer
+        .windows(XMP_START.len())
+        .rposition(|window| window == XMP_START)?;
+    let length = buffer[start..]
+        .windows(XMP_END.len())
+        .position(|window| window == XMP_END)?;
+    Some(&buffer[start..start + length + XMP_END.len()])
+}
+
+// Collects the text of the known elements by local name. Text split by nested
+// elements, such as the `rdf:li` items of an XMP list, is joined with `; `.
+fn read_xml_properties(xml: &str) -> DocumentMetadata {
+    let mut reader = Reader::from_str(xml);
+    let mut values: HashMap<String, String> = HashMap::new();
+    let mut current: Option<(String, usize, Vec<String>)> = None;
+    let mut depth = 0;
+    let mut new_piece = true;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element)) => {
+                depth += 1;
+                new_piece = true;
+                read_xml_attributes(&element, &mut values);
+                let name = String::from(element.local_name().as_ref());
+                if current.is_none() && XML_PROPERTIES.contains(&name.as_str()) {
+                    current = Some((name, depth, Vec::new()));
+                }
+            }
+            Ok(Event::Empty(element)) => {
+                new_piece = true;
+                read_xml_attributes(&element, &mut values);
+            }
+            Ok(Event::End(_)) => {
+                new_piece = true;
+                if let Some((name, start_depth, pieces)) = current.take() {
+                    if start_depth == depth {
+                        let pieces: Vec<String> = pieces
+                            .iter()
+                            .filter_map(|piece| clean_text(piece.clone()))
+                            .collect();
+                        if !pieces.is_empty() {
+                            values.entry(name).or_insert(pieces.join("; "));
+                        }
+                    } else {
+                        current = Some((name, start_depth, pieces));
+                    }
+                }
+                depth -= 1;
+            }
+            Ok(Event::Text(content)) => {
+                if let Some((_, _, pieces)) = current.as_mut() {
+                    push_text(pieces, &content.xml10_content(), new_piece);
+                    new_piece = false;
+                }
+            }
+            Ok(Event::GeneralRef(reference)) => {
+                if let Some((_, _, pieces)) = current.as_mut() {
+                    let resolved = match reference.resolve_char_ref() {
+                        Ok(Some(character)) => Some(character.to_string()),
+                        _ => escape::resolve_predefined_entity(&reference).map(String::from),
+                    };
+                    if let Some(resolved) = resolved {
+                        push_text(pieces, &resolved, new_piece);
+                        new_piece = false;
+                    }
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    let text = |names: &[&str]| names.iter().find_map(|name| values.get(*name).cloned());
+    let date = |names: &[&str]| text(names).as_deref().and_then(parse_iso_date);
+    DocumentMetadata {
+        title: text(&["title"]),
+        author: text(&["initial-creator", "creator"]),
+        subject: text(&["subject"]),
+        created: date(&["created", "creation-date", "CreateDate"]),
+        modified: date(&["modified", "date", "ModifyDate"]),
+    }
+}
+
+// XMP may store simple properties as attributes of `rdf:Description`
+fn read_xml_attributes(
+    element: &quick_xml::events::BytesStart,
+    values: &mut HashMap<String, String>,
+) {
+    for attribute in element.attributes().filter_map(|attribute| attribute.ok()) {
+        let name = attribute.key.local_name();
+        if !XML_PROPERTIES.contains(&name.as_ref()) {
+            continue;
+        }
+        if let Some(value) = attribute
+            .normalized_value(XmlVersion::Implicit1_0)
+            .ok()
+            .and_then(|value| clean_text(value.to_string()))
+        {
+            values.entry(String::from(name.as_ref())).or_insert(value);
+        }
+    }
+}
+
+fn push_text(pieces: &mut Vec<String>, text: &str, new_piece: bool) {
+    match pieces.last_mut() {
+        Some(last) if !new_piece => last.push_str(text),
+        _ => pieces.push(String::from(text)),
+    }
+}
+
+fn clean_text(text: String) -> Option<String> {
+    let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    if text.is_empty() {
+        return None;
+    }
+    Some(String::from(text))
+}
+
+// PDF dates look like `D:20240131093000+02'00'`, where everything after the
+// year is optional and a missing offset means local time
+fn parse_pdf_date(date: &str) -> Option<DateTime<Local>> {
+    let date = date.trim().trim_start_matches("D:");
+    let digits: String = date.chars().take_while(|c| c.is_ascii_digit()).collect();
+    let field = |start: usize, default: u32| -> Option<u32> {
+        match digits.get(start..start + 2) {
+            Some(value) => value.parse().ok(),
+            None => Some(default),
+        }
+    };
+    let year: i32 = digits.get(0..4)?.parse().ok()?;
+    let naive = NaiveDate::from_ymd_opt(year, field(4, 1)?, field(6, 1)?)?.and_hms_opt(
+        field(8, 0)?,
+        field(10, 0)?,
+        field(12, 0)?,
+    )?;
+
+    let offset: String = date[digits.len()..]
+        .chars()
+        .filter(|c| *c != '\'')
+        .collect();
+    let offset_seconds = match offset.chars().next() {
+        Some('Z') => Some(0),
+        Some(sign @ ('+' | '-')) => {
+            let hours: i32 = offset.get(1..3)?.parse().ok()?;
+            let minutes: i32 = offset.get(3..5).and_then(|m| m.parse().ok()).unwrap_or(0);
+            let seconds = hours * 3600 + minutes * 60;
+            Some(if sign == '-' { -seconds } else { seconds })
+        }
+        _ => None,
+    };
+    match offset_seconds {
+        Some(seconds) => FixedOffset::east_opt(seconds)?
+            .from_local_datetime(&naive)
+            .single()
+            .map(|date| date.with_timezone(&Local)),
+        None => Local.from_local_datetime(&naive).earliest(),
+    }
+}
+
+// Office and XMP dates are ISO 8601, with or without the time and offset
+fn parse_iso_date(date: &str) -> Option<DateTime<Local>> {
+    let date = date.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Some(date.with_timezone(&Local));
+    }
+    if let Ok(date) = DateTime::parse_from_str(date, "%Y-%m-%dT%H:%M%:z") {
+        return Some(date.with_timezone(&Local));
+    }
+    let naive = [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .ok()?
+            .and_hms_opt(0, 0, 0)
+    })?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(date: &str) -> Option<String> {
+        parse_pdf_date(date).map(|date| date.to_utc().format("%Y-%m-%d %H:%M:%S").to_string())
+    }
+
+    #[test]
+    fn pdf_dates_with_offsets_are_converted() {
+        assert_eq!(
+            utc("D:20240309143015+01'00'").as_deref(),
+            Some("2024-03-09 13:30:15")
+        );
+        assert_eq!(
+            utc("D:20240309143015-05'30").as_deref(),
+            Some("2024-03-09 20:00:15")
+        );
+        assert_eq!(
+            utc("D:20240309143015Z").as_deref(),
+            Some("2024-03-09 14:30:15")
+        );
+    }
+
+    #[test]
+    fn pdf_dates_may_leave_out_fields() {
+        let date = parse_pdf_date("D:2024").unwrap();
+        assert_eq!(date.naive_local().to_string(), "2024-01-01 00:00:00");
+        let date = parse_pdf_date("20240309").unwrap();
+        assert_eq!(date.naive_local().to_string(), "2024-03-09 00:00:00");
+    }
+
+    #[test]
+    fn invalid_pdf_dates_are_rejected() {
+        assert!(parse_pdf_date("").is_none());
+        assert!(parse_pdf_date("D:").is_none());
+        assert!(parse_pdf_date("D:20241345").is_none());
+        assert!(parse_pdf_date("yesterday").is_none());
+    }
+
+    #[test]
+    fn last_xmp_packet_is_found() {
+        let buffer = b"%PDF <x:xmpmeta>old</x:xmpmeta> ... <x:xmpmeta>new</x:xmpmeta> %%EOF";
+        assert_eq!(
+            find_xmp_packet(buffer),
+            Some(&b"<x:xmpmeta>new</x:xmpmeta>"[..])
+        );
+        assert!(find_xmp_packet(b"%PDF no metadata").is_none());
+    }
+}
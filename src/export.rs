@@ -0,0 +1,270 @@
+use std::{
+    fmt, fs,
+    io::Error,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    content_type::ContentType, directory::Directory, file::FileMetadata, filter::EntryFilter,
+    index, scan, util,
+};
+
+const EXPORT_PREFIX: &str = "fileorder";
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+const HTML_STYLE: &str = "body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; width: 100%; font-size: 14px; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; vertical-align: top; }
+th { background: #eee; position: sticky; top: 0; }
+tr:nth-child(even) { background: #f8f8f8; }
+td.number { text-align: right; font-variant-numeric: tabular-nums; }";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Html,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [ExportFormat::Csv, ExportFormat::Json, ExportFormat::Html];
+
+    fn get_extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Html => "html",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Json => "JSON",
+            ExportFormat::Html => "HTML",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+// One file or directory of an exported listing
+#[derive(Debug, Clone, Serialize)]
+struct ListingEntry {
+    path: String,
+    kind: &'static str,
+    size: u64,
+    size_text: String,
+    created: Option<String>,
+    modified: Option<String>,
+    accessed: Option<String>,
+    content_type: Option<&'static str>,
+    hash: Option<String>,
+}
+
+impl ListingEntry {
+    fn build(
+        root: &Path,
+        path: &Path,
+        is_dir: bool,
+        metadata: &FileMetadata,
+        content_type: Option<&ContentType>,
+        hashed: bool,
+    ) -> Self {
+        Self {
+            path: path
+                .strip_prefix(root)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .to_string(),
+            kind: if is_dir { "directory" } else { "file" },
+            size: metadata.get_size(),
+            size_text: util::format_size(metadata.get_size()),
+            created: metadata.get_created().map(format_date),
+            modified: metadata.get_modified().map(format_date),
+            accessed: metadata.get_accessed().map(format_date),
+            content_type: content_type.map(ContentType::get_mime),
+            hash: (hashed && !is_dir)
+                .then(|| index::hash_file(path).ok())
+                .flatten(),
+        }
+    }
+}
+
+// A new file in the downloads folder, named after what is exported and when
+pub fn get_export_path(name: &str, format: ExportFormat) -> Result<PathBuf, String> {
+    let directory = dirs::download_dir()
+        .or_else(dirs::home_dir)
+        .ok_or_else(|| String::from("Could not find downloads directory"))?;
+    Ok(directory.join(format!(
+        "{}-{}-{}.{}",
+        EXPORT_PREFIX,
+        name,
+        Local::now().format("%Y%m%d-%H%M%S"),
+        format.get_extension()
+    )))
+}
+
+// CSV and HTML get one row per record with the field names as columns
+pub fn write_records<T: Serialize>(
+    path: &Path,
+    format: ExportFormat,
+    title: &str,
+    records: &[T],
+) -> Result<(), Error> {
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_path(path)?;
+            for record in records {
+                writer.serialize(record)?;
+            }
+            writer.flush()
+        }
+        ExportFormat::Json => write_json(path, &records),
+        ExportFormat::Html => write_html(path, title, records),
+    }
+}
+
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), Error> {
+    let content = serde_json::to_string_pretty(value).map_err(Error::other)?;
+    fs::write(path, content)
+}
+
+// The listing comes from the directory as shown in the browser, or from the
+// disk when every subdirectory is included. Unreadable entries are left out
+// and files that cannot be read have no hash.
+pub fn export_listing(
+    root: &Path,
+    directory: &Directory,
+    recursive: bool,
+    entry_filter: &EntryFilter,
+    hashed: bool,
+    format: ExportFormat,
+) -> Result<PathBuf, String> {
+    let mut entries = Vec::new();
+    if recursive {
+        let mut errors = Vec::new();
+        scan::walk(root, entry_filter, &mut errors, |path, metadata| {
+            let content_type = if metadata.is_file() {
+                ContentType::detect(path).ok().flatten()
+            } else {
+                None
+            };
+            entries.push(ListingEntry::build(
+                root,
+                path,
+                metadata.is_dir(),
+                &FileMetadata::read(metadata),
+                content_type.as_ref(),
+                hashed,
+            ));
+            true
+        });
+    } else {
+        for subdirectory in directory.get_directories() {
+            entries.push(ListingEntry::build(
+                root,
+                &root.join(subdirectory.get_name()),
+                true,
+                subdirectory.get_metadata(),
+                None,
+                hashed,
+            ));
+        }
+        for file in directory.get_files() {
+            let path = root.join(file.get_name());
+            entries.push(ListingEntry::build(
+                root,
+                &path,
+                false,
+                file.get_metadata(),
+                ContentType::detect(&path).ok().flatten().as_ref(),
+                hashed,
+            ));
+        }
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let path = get_export_path("listing", format)?;
+    let title = format!("Files in {}", root.display());
+    write_records(&path, format, &title, &entries)
+        .map(|_| path)
+        .map_err(|error| error.to_string())
+}
+
+// A single page without outside resources, so it can be mailed or archived as is
+fn write_html<T: Serialize>(path: &Path, title: &str, records: &[T]) -> Result<(), Error> {
+    let values = records
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<Value>, _>>()
+        .map_err(Error::other)?;
+    let columns: Vec<String> = values
+        .first()
+        .and_then(Value::as_object)
+        .map(|object| object.keys().cloned().collect())
+        .unwrap_or_default();
+
+    let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n", escape_html(title)));
+    html.push_str(&format!(
+        "<style>\n{}\n</style>\n</head>\n<body>\n",
+        HTML_STYLE
+    ));
+    html.push_str(&format!("<h1>{}</h1>\n", escape_html(title)));
+    html.push_str(&format!(
+        "<p>Exported {}, {} rows</p>\n<table>\n<thead>\n<tr>",
+        Local::now().format(DATE_FORMAT),
+        values.len()
+    ));
+    for column in &columns {
+        html.push_str(&format!(
+            "<th>{}</th>",
+            escape_html(&column.replace('_', " "))
+        ));
+    }
+    html.push_str("</tr>\n</thead>\n<tbody>\n");
+    for value in &values {
+        html.push_str("<tr>");
+        for column in &columns {
+            match value.get(column) {
+                Some(Value::Number(number)) => {
+                    html.push_str(&format!("<td class=\"number\">{}</td>", number))
+                }
+                Some(Value::String(text)) => {
+                    html.push_str(&format!("<td>{}</td>", escape_html(text)))
+                }
+                Some(Value::Null) | None => html.push_str("<td></td>"),
+                Some(other) => {
+                    html.push_str(&format!("<td>{}</td>", escape_html(&other.to_string())))
+                }
+            }
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+    fs::write(path, html)
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+fn format_date(date: DateTime<Local>) -> String {
+    date.format(DATE_FORMAT).to_string()
+}
@@ -1,4 +1,7 @@
-use std::ffi::{OsStr, OsString};
+use std::{
+    ffi::{OsStr, OsString},
+    fs::Metadata,
+};
 
 use chrono::{DateTime, Local};
 
@@ -7,6 +10,7 @@ pub struct FileMetadata {
     created: Option<DateTime<Local>>,
     modified: Option<DateTime<Local>>,
     accessed: Option<DateTime<Local>>,
+    size: u64,
 }
 
 impl FileMetadata {
@@ -15,6 +19,7 @@ impl FileMetadata {
             created: None,
             modified: None,
             accessed: None,
+            size: 0,
         }
     }
 
@@ -22,13 +27,39 @@ impl FileMetadata {
         created: Option<DateTime<Local>>,
         modified: Option<DateTime<Local>>,
         accessed: Option<DateTime<Local>>,
+        size: u64,
     ) -> Self {
         Self {
             created,
             modified,
             accessed,
+            size,
         }
     }
+    pub fn read(metadata: &Metadata) -> Self {
+        let mut file_metadata_created: Option<DateTime<Local>> = None;
+        let mut file_metadata_modified: Option<DateTime<Local>> = None;
+        let mut file_metadata_accessed: Option<DateTime<Local>> = None;
+
+        if let Ok(created) = metadata.created() {
+            file_metadata_created = Some(created.into());
+        }
+        if let Ok(modified) = metadata.modified() {
+            file_metadata_modified = Some(modified.into());
+        }
+
+        if let Ok(accessed) = metadata.accessed() {
+            file_metadata_accessed = Some(accessed.into());
+        }
+
+        Self::build(
+            file_metadata_created,
+            file_metadata_modified,
+            file_metadata_accessed,
+            metadata.len(),
+        )
+    }
+
     pub fn get_created(&self) -> Option<DateTime<Local>> {
         self.created
     }
@@ -40,6 +71,10 @@ impl FileMetadata {
     pub fn get_accessed(&self) -> Option<DateTime<Local>> {
         self.accessed
     }
+
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -56,7 +91,7 @@ impl File {
         }
     }
     pub fn get_name(&self) -> &OsStr {
-        &self.name.as_os_str()
+        self.name.as_os_str()
     }
 
     pub fn get_metadata(&self) -> &FileMetadata {
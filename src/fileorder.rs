@@ -1,7 +1,56 @@
-use iced::Element;
-use std::{ffi::OsString, io::Error};
+use chrono::{DateTime, Local};
+use iced::{
+    advanced::widget::{self, operation::focusable},
+    keyboard::{self, key, Key, Modifiers},
+    widget::{
+        image, pane_grid,
+        scrollable::{self, AbsoluteOffset, Viewport},
+        text_input,
+    },
+    Element, Subscription, Task,
+};
+use std::{
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+    fs,
+    io::Error,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
-use crate::{directory::Directory, file::FileMetadata, layouts, util};
+use crate::{
+    archive::{self, ArchiveEntry, ExtractResult},
+    backup::{self, BackupCompare, BackupEvent, BackupPlan, BackupResult},
+    cleanup::{self, CleanupPlan, CleanupResult},
+    config::{self, Config},
+    content_search::{self, CaseMode, ContentEvent, ContentMatch, ContentMatcher, ContentQuery},
+    content_type::{self, ContentType, TypeMismatch},
+    directory::Directory,
+    document::DocumentMetadata,
+    export::{self, ExportFormat},
+    file::{File, FileMetadata},
+    filter::EntryFilter,
+    history::{History, HistoryEntry},
+    import::{self, ImportEvent, ImportPlan, ImportResult},
+    index::{self, DuplicateReport, IndexUpdate},
+    journal::{self, JournalEntry, UndoResult},
+    layouts,
+    plan::{self, ApplyEvent, Plan, PlanResult},
+    preview::{Preview, PreviewMode},
+    protection::Protection,
+    retention::{self, RetentionReport, RetentionResult},
+    rules::Rule,
+    search::{self, SearchCriteria, SearchEvent, SearchField, SearchQuery, SearchResult},
+    snapshot::{self, SnapshotChoice, SnapshotDiff, SnapshotFilter, SnapshotInfo, SnapshotResult},
+    storage::{self, SpaceInfo},
+    thumbnail::{self, ThumbnailSize},
+    transfer::TransferProgress,
+    usage::{self, UsageNode, UsageReport, UsageSort},
+    util,
+};
 
 #[derive(Debug)]
 pub struct App {
@@ -10,11 +59,129 @@ pub struct App {
     layout: layouts::Layout,
     id_stack: Vec<usize>,
     current_path: OsString,
+    address: String,
+    history: History,
+    scroll_offset: AbsoluteOffset,
+    selected: Option<OsString>,
+    selected_metadata: Option<(PathBuf, DocumentMetadata)>,
+    selected_thumbnail: Option<(PathBuf, image::Handle)>,
+    thumbnails: HashMap<PathBuf, LoadedThumbnail>,
+    loading_thumbnails: bool,
+    content_types: HashMap<PathBuf, DetectedType>,
+    detecting_types: bool,
+    reading_archive: Option<PathBuf>,
+    // What is left of a typed path that leads into an archive being read
+    pending_components: Vec<OsString>,
+    preview: Option<Preview>,
+    preview_mode: PreviewMode,
+    panes: pane_grid::State<layouts::BrowserPane>,
+    filter_text: String,
+    search_query: SearchQuery,
+    search_root: PathBuf,
+    search_results: Vec<SearchResult>,
+    search_errors: Vec<String>,
+    searching: bool,
+    search_id: usize,
+    search_cancelled: Arc<AtomicBool>,
+    index_update: Option<IndexUpdate>,
+    updating_index: bool,
+    duplicate_report: Option<DuplicateReport>,
+    snapshots: Vec<SnapshotInfo>,
+    snapshot_errors: Vec<String>,
+    hash_snapshot: bool,
+    saving_snapshot: bool,
+    snapshot_result: Option<SnapshotResult>,
+    old_snapshot: Option<SnapshotChoice>,
+    new_snapshot: Option<SnapshotChoice>,
+    comparing_snapshots: bool,
+    snapshot_diff: Option<SnapshotDiff>,
+    export_result: Option<Result<PathBuf, String>>,
+    export_recursive: bool,
+    export_hashes: bool,
+    exporting: bool,
+    usage: Option<UsageReport>,
+    usage_path: PathBuf,
+    measuring_usage: bool,
+    usage_sort: UsageSort,
+    usage_descending: bool,
+    content_query: ContentQuery,
+    content_root: PathBuf,
+    content_results: Vec<ContentMatch>,
+    content_errors: Vec<String>,
+    content_skipped: Vec<PathBuf>,
+    content_searched: usize,
+    content_searching: bool,
+    content_search_id: usize,
+    content_cancelled: Arc<AtomicBool>,
+    bulk_destination: String,
     directories_read: usize,
     external_storage_directories: Vec<Directory>,
+    device_space: HashMap<usize, SpaceInfo>,
+    current_space: Option<SpaceInfo>,
+    camera_volumes: HashMap<usize, PathBuf>,
+    import_volume: PathBuf,
+    import_root: String,
+    import_pattern: String,
+    delete_after_import: bool,
+    import_plan: Option<ImportPlan>,
+    planning_import: bool,
+    importing: bool,
+    import_progress: Option<TransferProgress>,
+    import_result: Option<ImportResult>,
+    backup_source: PathBuf,
+    backup_devices: Vec<String>,
+    backup_target: String,
+    backup_compare: BackupCompare,
+    keep_removed_in_backup: bool,
+    backup_plan: Option<BackupPlan>,
+    planning_backup: bool,
+    backing_up: bool,
+    backup_progress: Option<TransferProgress>,
+    backup_result: Option<BackupResult>,
+    config: Config,
+    entry_filter: EntryFilter,
+    protection: Protection,
+    plan: Option<Plan>,
+    plan_result: Option<PlanResult>,
+    applying_plan: bool,
+    plan_progress: Option<TransferProgress>,
+    type_report: Option<Vec<TypeMismatch>>,
+    extract_destination: String,
+    extract_result: Option<ExtractResult>,
+    extracting: bool,
+    undo_preview: Option<Result<Option<JournalEntry>, String>>,
+    undo_result: Option<UndoResult>,
+    retention_report: Option<RetentionReport>,
+    retention_result: Option<RetentionResult>,
+    previewing_retention: bool,
+    applying_retention: bool,
+    cleanup_plan: Option<CleanupPlan>,
+    cleanup_result: Option<CleanupResult>,
+    removing_empty_directories: bool,
+    user_directories: Vec<(String, PathBuf)>,
     error: Option<Error>,
 }
 
+// Failed thumbnails are remembered too, so they are not retried until the
+// file changes
+#[derive(Debug)]
+struct LoadedThumbnail {
+    modified: Option<DateTime<Local>>,
+    handle: Option<image::Handle>,
+}
+
+type ThumbnailBytes = (PathBuf, Option<DateTime<Local>>, Option<Vec<u8>>);
+
+// Content types are sniffed in the background after a directory is listed,
+// and like thumbnails kept until the file changes
+#[derive(Debug)]
+struct DetectedType {
+    modified: Option<DateTime<Local>>,
+    content_type: Option<ContentType>,
+}
+
+type ContentTypes = Vec<(PathBuf, Option<DateTime<Local>>, Option<ContentType>)>;
+
 #[derive(Debug)]
 pub enum OperatingSystem {
     MacOs,
@@ -33,18 +200,138 @@ fn detect_operating_system() -> OperatingSystem {
 }
 
 const ROOTPATH: &str = "";
+pub const ADDRESS_BAR_ID: &str = "address_bar";
+pub const DIRECTORY_LIST_ID: &str = "directory_list";
 
 impl Default for App {
     fn default() -> Self {
+        let (config, mut error) = match Config::load() {
+            Ok(config) => (config, None),
+            Err(error) => (
+                Config::read_only(),
+                Some(Error::new(
+                    error.kind(),
+                    format!(
+                        "Could not read the settings, changes will not be saved: {}",
+                        error
+                    ),
+                )),
+            ),
+        };
+        let protection = config.build_protection();
+        let entry_filter = match config.build_entry_filter() {
+            Ok(entry_filter) => entry_filter,
+            Err(filter_error) => {
+                error = Some(filter_error);
+                EntryFilter::new()
+            }
+        };
         Self {
             operating_system: detect_operating_system(),
             root: Directory::new(),
             layout: layouts::Layout::Home,
             id_stack: Vec::new(),
             current_path: OsString::from(ROOTPATH),
+            address: util::display_path(OsStr::new(ROOTPATH)),
+            history: History::new(),
+            scroll_offset: AbsoluteOffset::default(),
+            selected: None,
+            selected_metadata: None,
+            selected_thumbnail: None,
+            thumbnails: HashMap::new(),
+            loading_thumbnails: false,
+            content_types: HashMap::new(),
+            detecting_types: false,
+            reading_archive: None,
+            pending_components: Vec::new(),
+            preview: None,
+            preview_mode: PreviewMode::Text,
+            panes: layouts::create_browser_panes(),
+            filter_text: String::new(),
+            search_query: SearchQuery::new(),
+            search_root: PathBuf::new(),
+            search_results: Vec::new(),
+            search_errors: Vec::new(),
+            searching: false,
+            search_id: 0,
+            search_cancelled: Arc::new(AtomicBool::new(false)),
+            index_update: None,
+            updating_index: false,
+            duplicate_report: None,
+            snapshots: Vec::new(),
+            snapshot_errors: Vec::new(),
+            hash_snapshot: false,
+            saving_snapshot: false,
+            snapshot_result: None,
+            old_snapshot: None,
+            new_snapshot: None,
+            comparing_snapshots: false,
+            snapshot_diff: None,
+            export_result: None,
+            export_recursive: false,
+            export_hashes: false,
+            exporting: false,
+            usage: None,
+            usage_path: PathBuf::new(),
+            measuring_usage: false,
+            usage_sort: UsageSort::Size,
+            usage_descending: true,
+            content_query: ContentQuery::new(),
+            content_root: PathBuf::new(),
+            content_results: Vec::new(),
+            content_errors: Vec::new(),
+            content_skipped: Vec::new(),
+            content_searched: 0,
+            content_searching: false,
+            content_search_id: 0,
+            content_cancelled: Arc::new(AtomicBool::new(false)),
+            bulk_destination: String::new(),
             directories_read: 0,
             external_storage_directories: Vec::new(),
-            error: None,
+            device_space: HashMap::new(),
+            current_space: None,
+            camera_volumes: HashMap::new(),
+            import_volume: PathBuf::new(),
+            import_root: String::new(),
+            import_pattern: String::new(),
+            delete_after_import: false,
+            import_plan: None,
+            planning_import: false,
+            importing: false,
+            import_progress: None,
+            import_result: None,
+            backup_source: PathBuf::new(),
+            backup_devices: Vec::new(),
+            backup_target: String::new(),
+            backup_compare: BackupCompare::SizeAndTime,
+            keep_removed_in_backup: false,
+            backup_plan: None,
+            planning_backup: false,
+            backing_up: false,
+            backup_progress: None,
+            backup_result: None,
+            config,
+            protection,
+            entry_filter,
+            plan: None,
+            plan_result: None,
+            applying_plan: false,
+            plan_progress: None,
+            type_report: None,
+            extract_destination: String::new(),
+            extract_result: None,
+            extracting: false,
+            undo_preview: None,
+            undo_result: None,
+            retention_report: None,
+            retention_result: None,
+            previewing_retention: false,
+            applying_retention: false,
+            cleanup_plan: None,
+            cleanup_result: None,
+            removing_empty_directories: false,
+            user_directories: Vec::new(),
+            error,
         }
     }
 }
@@ -56,20 +343,162 @@ pub enum Message {
     In(usize),
     Out,
     InExternal(usize),
+    NavigateTo(OsString),
+    AddressChanged(String),
+    AddressSubmitted,
+    TabPressed,
+    AddressCompleted(widget::Id),
+    Back,
+    Forward,
+    Scrolled(Viewport),
+    Select(OsString),
+    SelectedMetadataRead(PathBuf, Option<DocumentMetadata>),
+    SelectedThumbnailLoaded(PathBuf, Option<Vec<u8>>),
+    ToggleThumbnails,
+    ThumbnailsLoaded(Vec<ThumbnailBytes>),
+    ContentTypesDetected(ContentTypes),
+    ArchiveEntriesRead(PathBuf, Result<Arc<Vec<ArchiveEntry>>, String>),
+    PreviewRead(Option<Preview>),
+    PreviewModeChanged(PreviewMode),
+    PaneResized(pane_grid::ResizeEvent),
+    FilterChanged(String),
+    SearchLayout,
+    SearchQueryChanged(SearchField, String),
+    ToggleSearchRegex(bool),
+    ToggleSearchIndex(bool),
+    UpdateIndex,
+    IndexUpdated(IndexUpdate),
+    StartSearch,
+    StopSearch,
+    SearchEvent(usize, SearchEvent),
+    OpenSearchResult(PathBuf),
+    FindDuplicates,
+    DuplicatesFound(DuplicateReport),
+    SnapshotsLayout,
+    ToggleSnapshotHash(bool),
+    SaveSnapshot,
+    SnapshotSaved(SnapshotResult),
+    OldSnapshotSelected(SnapshotChoice),
+    NewSnapshotSelected(SnapshotChoice),
+    CompareSnapshots,
+    SnapshotsCompared(SnapshotDiff),
+    ExportSnapshotDiff(ExportFormat),
+    ToggleExportRecursive(bool),
+    ToggleExportHashes(bool),
+    ExportListing(ExportFormat),
+    ExportPlan(ExportFormat),
+    Exported(Result<PathBuf, String>),
+    UsageLayout,
+    MeasureUsage,
+    UsageMeasured(UsageReport),
+    UsageNavigate(PathBuf),
+    SortUsage(UsageSort),
+    BrowseUsage,
+    ContentSearchLayout,
+    ContentPatternChanged(String),
+    ToggleContentRegex(bool),
+    CaseModeSelected(CaseMode),
+    ContextLinesSelected(usize),
+    StartContentSearch,
+    StopContentSearch,
+    ContentSearchEvent(usize, ContentEvent),
+    OrganizeMatchedFiles,
+    BulkDestinationChanged(String),
+    MoveMatchedFiles,
+    AddBookmark,
+    RemoveBookmark(usize),
+    MoveBookmark(usize, usize),
+    ToggleHidden,
+    CreatePlan,
+    PlanCreated(Plan),
+    ToggleVerifyCopies(bool),
+    ApplyPlan,
+    PlanApplyEvent(ApplyEvent),
+    ClosePlan,
+    CreateTypeReport,
+    TypeReportCreated(Vec<TypeMismatch>),
+    BackToBrowser,
+    DeviceSpaceRead(Vec<(usize, SpaceInfo)>),
+    CurrentSpaceRead(PathBuf, Option<SpaceInfo>),
+    CameraVolumesFound(Vec<(usize, PathBuf)>),
+    ImportLayout(PathBuf),
+    ImportRootChanged(String),
+    ImportPatternChanged(String),
+    PlanImport,
+    ImportPlanned(ImportPlan),
+    ToggleDeleteAfterImport(bool),
+    StartImport,
+    ImportEvent(ImportEvent),
+    BackupLayout,
+    BackupDeviceSelected(String),
+    BackupTargetChanged(String),
+    BackupCompareSelected(BackupCompare),
+    ToggleKeepRemovedInBackup(bool),
+    PlanBackup,
+    BackupPlanned(BackupPlan),
+    StartBackup,
+    BackupEvent(BackupEvent),
+    ExtractDestinationChanged(String),
+    Extract(Option<OsString>),
+    Extracted(ExtractResult),
+    Undo,
+    UndoPreviewRead(Result<Option<JournalEntry>, String>),
+    ConfirmUndo,
+    CloseUndo,
+    Undone(UndoResult),
+    RetentionLayout,
+    PreviewPolicy(usize),
+    PolicyPreviewed(RetentionReport),
+    ApplyRetention,
+    RetentionApplied(RetentionResult),
+    FindEmptyDirectories(PathBuf),
+    EmptyDirectoriesFound(CleanupPlan),
+    RemoveEmptyDirectories,
+    EmptyDirectoriesRemoved(CleanupResult),
+}
+
+fn handle_key_press(key: Key, modifiers: Modifiers) -> Option<Message> {
+    match key {
+        Key::Named(key::Named::Tab) => Some(Message::TabPressed),
+        Key::Named(key::Named::ArrowLeft) if modifiers.alt() => Some(Message::Back),
+        Key::Named(key::Named::ArrowRight) if modifiers.alt() => Some(Message::Forward),
+        _ => None,
+    }
 }
 
 impl App {
-    pub fn view(&self) -> Element<Message> {
+    pub fn view(&self) -> Element<'_, Message> {
         if let OperatingSystem::None = self.operating_system {
             return layouts::error_layout(self, "Could not detect operating system");
         }
         match self.layout {
             layouts::Layout::Home => layouts::home_layout(),
             layouts::Layout::Templates => layouts::file_browser(self),
+            layouts::Layout::Plan => layouts::plan_layout(self),
+            layouts::Layout::Undo => layouts::undo_layout(self),
+            layouts::Layout::TypeReport => layouts::type_report_layout(self),
+            layouts::Layout::Retention => layouts::retention_layout(self),
+            layouts::Layout::Cleanup => layouts::cleanup_layout(self),
+            layouts::Layout::Search => layouts::search_layout(self),
+            layouts::Layout::ContentSearch => layouts::content_search_layout(self),
+            layouts::Layout::Duplicates => layouts::duplicates_layout(self),
+            layouts::Layout::Snapshots => layouts::snapshots_layout(self),
+            layouts::Layout::Usage => layouts::usage_layout(self),
+            layouts::Layout::Import => layouts::import_layout(self),
+            layouts::Layout::Backup => layouts::backup_layout(self),
+        }
+    }
+
+    pub fn subscription(&self) -> Subscription<Message> {
+        match self.layout {
+            layouts::Layout::Templates => keyboard::on_key_press(handle_key_press),
+            _ => Subscription::none(),
         }
     }
 
-    pub fn update(&mut self, message: Message) {
+    pub fn update(&mut self, message: Message) -> Task<Message> {
+        let previous_path = self.current_path.clone();
+        self.history.save_state(self.scroll_offset, &self.selected);
         match message {
             Message::HomeLayout => {
                 self.error = None;
@@ -79,64 +508,239 @@ impl App {
             Message::TemplateLayout => {
                 self.error = None;
                 self.external_storage_directories.clear();
+                self.device_space.clear();
+                self.camera_volumes.clear();
                 let external_storage_paths =
                     util::get_external_storage_paths(&self.operating_system);
+                let mut read_device_space = Task::none();
+                let mut find_camera_volumes = Task::none();
                 if let Ok(storage_paths) = external_storage_paths {
                     self.initialize_external_devices(&storage_paths);
+                    let camera_paths = storage_paths.clone();
+                    find_camera_volumes = Task::perform(
+                        async move {
+                            camera_paths
+                                .iter()
+                                .map(PathBuf::from)
+                                .enumerate()
+                                .filter(|(_, path)| import::is_camera_volume(path))
+                                .collect()
+                        },
+                        Message::CameraVolumesFound,
+                    );
+                    read_device_space = Task::perform(
+                        async move {
+                            storage_paths
+                                .iter()
+                                .enumerate()
+                                .filter_map(|(i, path)| {
+                                    storage::get_space_info(Path::new(path))
+                                        .ok()
+                                        .map(|space| (i, space))
+                                })
+                                .collect()
+                        },
+                        Message::DeviceSpaceRead,
+                    );
                 } else if let Err(error) = external_storage_paths {
                     self.error = Some(error);
                 }
 
+                self.user_directories = config::get_user_directories();
                 self.current_path = OsString::from(ROOTPATH);
                 self.root.clear_directories();
 
                 let mut index = 0;
                 let mut initial_path = OsString::new();
                 initial_path.push("/");
-                if let Err(error) = self
-                    .root
-                    .write_directory_content(initial_path.as_os_str(), &mut index)
-                {
+                if let Err(error) = self.root.write_directory_content(
+                    initial_path.as_os_str(),
+                    &mut index,
+                    &self.entry_filter,
+                ) {
                     self.error = Some(error);
                 }
                 self.directories_read = index;
+                self.id_stack.clear();
+                self.address = util::display_path(&self.current_path);
+                self.history.clear();
+                self.history.push(&self.current_path);
+                self.selected = None;
                 self.layout = layouts::Layout::Templates;
+                return Task::batch([
+                    read_device_space,
+                    find_camera_volumes,
+                    self.detect_content_types(),
+                    self.read_current_space(),
+                ]);
+            }
+            Message::DeviceSpaceRead(device_space) => {
+                self.device_space = device_space.into_iter().collect();
+            }
+            Message::CameraVolumesFound(camera_volumes) => {
+                self.camera_volumes = camera_volumes.into_iter().collect();
+            }
+            Message::ImportLayout(volume) => {
+                self.error = None;
+                self.layout = layouts::Layout::Import;
+                self.import_volume = volume;
+                self.import_root = self.config.get_import_root().display().to_string();
+                self.import_pattern = String::from(self.config.get_import_pattern());
+                self.import_result = None;
+                return self.plan_import();
+            }
+            Message::ImportRootChanged(import_root) => {
+                self.import_root = import_root;
+            }
+            Message::ImportPatternChanged(import_pattern) => {
+                self.import_pattern = import_pattern;
+            }
+            Message::PlanImport => {
+                self.config
+                    .set_import_root(PathBuf::from(self.import_root.trim()));
+                self.config
+                    .set_import_pattern(String::from(self.import_pattern.trim()));
+                self.save_config();
+                self.import_result = None;
+                return self.plan_import();
+            }
+            Message::ImportPlanned(import_plan) => {
+                self.planning_import = false;
+                self.import_plan = Some(import_plan);
+            }
+            Message::ToggleDeleteAfterImport(delete_after_import) => {
+                self.delete_after_import = delete_after_import;
+            }
+            Message::StartImport => {
+                if self.importing || self.import_result.is_some() {
+                    return Task::none();
+                }
+                if let Some(import_plan) = self.import_plan.clone() {
+                    self.importing = true;
+                    self.import_progress = None;
+                    let receiver = import::start_import(import_plan, self.delete_after_import);
+                    return Task::run(receiver, Message::ImportEvent);
+                }
+            }
+            Message::ImportEvent(event) => match event {
+                ImportEvent::Progress(progress) => self.import_progress = Some(progress),
+                ImportEvent::Finished(import_result) => {
+                    self.importing = false;
+                    self.import_progress = None;
+                    self.import_result = Some(import_result);
+                }
+            },
+            Message::BackupLayout => {
+                self.error = None;
+                self.layout = layouts::Layout::Backup;
+                self.backup_source = util::current_path_buf(&self.current_path);
+                self.backup_devices = util::get_external_storage_paths(&self.operating_system)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|path| path.to_string_lossy().to_string())
+                    .collect();
+                self.backup_target.clear();
+                self.clear_backup_plan();
+            }
+            Message::BackupDeviceSelected(device) => {
+                // Each source gets its own folder on the device
+                let mut target = PathBuf::from(device);
+                if let Some(name) = self.backup_source.file_name() {
+                    target.push(name);
+                }
+                self.backup_target = target.display().to_string();
+                self.clear_backup_plan();
+            }
+            Message::BackupTargetChanged(backup_target) => {
+                self.backup_target = backup_target;
+                self.clear_backup_plan();
+            }
+            Message::BackupCompareSelected(backup_compare) => {
+                self.backup_compare = backup_compare;
+                self.clear_backup_plan();
+            }
+            Message::ToggleKeepRemovedInBackup(keep_removed_in_backup) => {
+                self.keep_removed_in_backup = keep_removed_in_backup;
+                self.clear_backup_plan();
+            }
+            Message::PlanBackup => {
+                let target = PathBuf::from(self.backup_target.trim());
+                if self.planning_backup || self.backing_up || target.as_os_str().is_empty() {
+                    return Task::none();
+                }
+                self.clear_backup_plan();
+                self.planning_backup = true;
+                let source = self.backup_source.clone();
+                let compare = self.backup_compare;
+                let keep_removed = self.keep_removed_in_backup;
+                let ignore_patterns = self.config.get_ignore_patterns().clone();
+                return Task::perform(
+                    async move {
+                        backup::plan_backup(
+                            &source,
+                            &target,
+                            compare,
+                            keep_removed,
+                            &ignore_patterns,
+                        )
+                    },
+                    Message::BackupPlanned,
+                );
+            }
+            Message::BackupPlanned(backup_plan) => {
+                self.planning_backup = false;
+                self.backup_plan = Some(backup_plan);
+            }
+            Message::StartBackup => {
+                if self.backing_up || self.backup_result.is_some() {
+                    return Task::none();
+                }
+                if let Some(backup_plan) = self.backup_plan.clone() {
+                    self.backing_up = true;
+                    self.backup_progress = None;
+                    let receiver =
+                        backup::start_backup(backup_plan, self.config.get_verify_copies());
+                    return Task::run(receiver, Message::BackupEvent);
+                }
+            }
+            Message::BackupEvent(event) => match event {
+                BackupEvent::Progress(progress) => self.backup_progress = Some(progress),
+                BackupEvent::Finished(backup_result) => {
+                    self.backing_up = false;
+                    self.backup_progress = None;
+                    self.backup_result = Some(backup_result);
+                }
+            },
+            Message::CurrentSpaceRead(path, space) => {
+                // Only the last visited path is shown
+                if path == util::current_path_buf(&self.current_path) {
+                    self.current_space = space;
+                }
             }
             Message::In(selected_directory_id) => {
                 self.error = None;
-                self.id_stack.push(selected_directory_id);
-                let temp_directories_read = self.directories_read;
-                if let Err(error) = self.root.insert_new_sub_directory(
-                    &mut self.id_stack,
-                    &mut self.current_path,
-                    &mut self.directories_read,
-                    selected_directory_id,
-                ) {
+                if let Err(error) = self.enter_directory(selected_directory_id) {
                     self.error = Some(error);
-                    self.directories_read = temp_directories_read;
-                    self.current_path = util::remove_directory_from_path(
-                        &self.current_path,
-                        &self.operating_system,
-                    );
-                    self.id_stack.pop();
                 }
-                println!("current_path: {:?}", self.current_path);
+                self.address = util::display_path(&self.current_path);
+                return self.visit_current_path(&previous_path);
             }
             Message::Out => {
                 self.error = None;
-                if let Some(_) = self.id_stack.last() {
+                if self.id_stack.last().is_some() {
                     let directory = self.root.find_directory_by_id(&self.id_stack);
                     let directory_ids = directory.get_directory_ids();
                     self.directories_read -= directory_ids.len();
                     directory.get_mut_directories().clear();
                     directory.get_mut_files().clear();
                     self.current_path = util::remove_directory_from_path(
-                        &self.current_path.as_os_str(),
+                        self.current_path.as_os_str(),
                         &self.operating_system,
                     );
                     self.id_stack.pop();
                 }
-                println!("current_path: {:?}", self.current_path);
+                self.address = util::display_path(&self.current_path);
+                return self.visit_current_path(&previous_path);
             }
             Message::InExternal(selected_directory_id) => {
                 self.error = None;
@@ -149,9 +753,719 @@ impl App {
                     }
                     _ => {}
                 }
-                println!("current_path: {:?}", self.current_path);
+                self.address = util::display_path(&self.current_path);
+                return self.visit_current_path(&previous_path);
+            }
+            Message::NavigateTo(path) => {
+                self.error = None;
+                self.navigate(&path);
+                return self.visit_current_path(&previous_path);
+            }
+            Message::AddressChanged(address) => {
+                self.address = address;
+            }
+            Message::AddressSubmitted => {
+                self.error = None;
+                let path = OsString::from(self.address.trim());
+                self.navigate(&path);
+                return self.visit_current_path(&previous_path);
+            }
+            Message::TabPressed => {
+                // Tab only completes while typing in the address bar
+                return widget::operate(focusable::find_focused()).map(Message::AddressCompleted);
+            }
+            Message::AddressCompleted(focused) => {
+                if focused != widget::Id::from(text_input::Id::new(ADDRESS_BAR_ID)) {
+                    return Task::none();
+                }
+                if let Some(completed) = util::complete_path(&self.address) {
+                    self.address = completed;
+                    return text_input::move_cursor_to_end(text_input::Id::new(ADDRESS_BAR_ID));
+                }
+            }
+            Message::Back => {
+                if let Some(entry) = self.history.back().cloned() {
+                    return self.restore_history_entry(entry);
+                }
+            }
+            Message::Forward => {
+                if let Some(entry) = self.history.forward().cloned() {
+                    return self.restore_history_entry(entry);
+                }
+            }
+            Message::Scrolled(viewport) => {
+                self.scroll_offset = viewport.absolute_offset();
+            }
+            Message::Select(file_name) => {
+                self.selected = Some(file_name);
+                if let Some(path) = self.get_selected_path() {
+                    let metadata_path = path.clone();
+                    let read_metadata = Task::perform(
+                        async move {
+                            let document_metadata = DocumentMetadata::read(&metadata_path);
+                            (metadata_path, document_metadata)
+                        },
+                        |(path, document_metadata)| {
+                            Message::SelectedMetadataRead(path, document_metadata)
+                        },
+                    );
+                    let preview_path = path.clone();
+                    let read_preview = Task::perform(
+                        async move { Preview::read(&preview_path).ok() },
+                        Message::PreviewRead,
+                    );
+                    if !thumbnail::is_thumbnailable(&path) {
+                        return Task::batch([read_metadata, read_preview]);
+                    }
+                    let load_thumbnail = Task::perform(
+                        async move {
+                            let bytes = thumbnail::load(&path, ThumbnailSize::Large).ok();
+                            (path, bytes)
+                        },
+                        |(path, bytes)| Message::SelectedThumbnailLoaded(path, bytes),
+                    );
+                    return Task::batch([read_metadata, read_preview, load_thumbnail]);
+                }
+            }
+            Message::SelectedMetadataRead(path, document_metadata) => {
+                self.selected_metadata = document_metadata.map(|metadata| (path, metadata));
+            }
+            Message::SelectedThumbnailLoaded(path, bytes) => {
+                self.selected_thumbnail =
+                    bytes.map(|bytes| (path, image::Handle::from_bytes(bytes)));
+            }
+            Message::PreviewRead(preview) => {
+                self.preview = preview;
+            }
+            Message::PreviewModeChanged(preview_mode) => {
+                self.preview_mode = preview_mode;
+            }
+            Message::PaneResized(pane_grid::ResizeEvent { split, ratio }) => {
+                self.panes.resize(split, ratio);
+            }
+            Message::FilterChanged(filter_text) => {
+                self.filter_text = filter_text;
+            }
+            Message::SearchLayout => {
+                self.error = None;
+                self.layout = layouts::Layout::Search;
+            }
+            Message::SearchQueryChanged(field, value) => {
+                self.search_query.set(field, value);
+            }
+            Message::ToggleSearchRegex(use_regex) => {
+                self.search_query.set_use_regex(use_regex);
+            }
+            Message::ToggleSearchIndex(use_index) => {
+                self.search_query.set_use_index(use_index);
+            }
+            Message::UpdateIndex => {
+                if self.updating_index {
+                    return Task::none();
+                }
+                self.updating_index = true;
+                self.index_update = None;
+                let root = util::current_path_buf(&self.current_path);
+                let entry_filter = self.entry_filter.clone();
+                return Task::perform(
+                    async move { index::update_index(&root, &entry_filter) },
+                    Message::IndexUpdated,
+                );
+            }
+            Message::IndexUpdated(index_update) => {
+                self.updating_index = false;
+                self.index_update = Some(index_update);
+            }
+            Message::StartSearch => {
+                self.search_cancelled.store(true, Ordering::Relaxed);
+                self.search_results.clear();
+                self.search_errors.clear();
+                let criteria = match SearchCriteria::build(&self.search_query) {
+                    Ok(criteria) => criteria,
+                    Err(error) => {
+                        self.searching = false;
+                        self.search_errors.push(error);
+                        return Task::none();
+                    }
+                };
+                // Events of an earlier search may still arrive, so each one is tagged
+                self.search_id += 1;
+                self.search_cancelled = Arc::new(AtomicBool::new(false));
+                self.search_root = util::current_path_buf(&self.current_path);
+                self.searching = true;
+                let search_id = self.search_id;
+                let receiver = search::start(
+                    self.search_root.clone(),
+                    criteria,
+                    self.entry_filter.clone(),
+                    self.search_cancelled.clone(),
+                );
+                return Task::run(receiver, move |event| {
+                    Message::SearchEvent(search_id, event)
+                });
+            }
+            Message::StopSearch => {
+                self.search_cancelled.store(true, Ordering::Relaxed);
+            }
+            Message::SearchEvent(search_id, event) => {
+                if search_id != self.search_id {
+                    return Task::none();
+                }
+                match event {
+                    SearchEvent::Found(results) => self.search_results.extend(results),
+                    SearchEvent::Finished(errors) => {
+                        self.searching = false;
+                        self.search_errors.extend(errors);
+                    }
+                }
+            }
+            Message::ContentSearchLayout => {
+                self.error = None;
+                self.layout = layouts::Layout::ContentSearch;
+            }
+            Message::ContentPatternChanged(pattern) => {
+                self.content_query.set_pattern(pattern);
+            }
+            Message::ToggleContentRegex(use_regex) => {
+                self.content_query.set_use_regex(use_regex);
+            }
+            Message::CaseModeSelected(case_mode) => {
+                self.content_query.set_case_mode(case_mode);
+            }
+            Message::ContextLinesSelected(context_lines) => {
+                self.content_query.set_context_lines(context_lines);
+            }
+            Message::StartContentSearch => {
+                self.content_cancelled.store(true, Ordering::Relaxed);
+                self.content_results.clear();
+                self.content_errors.clear();
+                self.content_skipped.clear();
+                self.content_searched = 0;
+                let matcher = match ContentMatcher::build(&self.content_query) {
+                    Ok(matcher) => matcher,
+                    Err(error) => {
+                        self.content_searching = false;
+                        self.content_errors.push(error);
+                        return Task::none();
+                    }
+                };
+                self.content_search_id += 1;
+                self.content_cancelled = Arc::new(AtomicBool::new(false));
+                self.content_root = util::current_path_buf(&self.current_path);
+                self.content_searching = true;
+                let search_id = self.content_search_id;
+                let receiver = content_search::start(
+                    self.content_root.clone(),
+                    matcher,
+                    self.entry_filter.clone(),
+                    self.content_cancelled.clone(),
+                );
+                return Task::run(receiver, move |event| {
+                    Message::ContentSearchEvent(search_id, event)
+                });
+            }
+            Message::StopContentSearch => {
+                self.content_cancelled.store(true, Ordering::Relaxed);
+            }
+            Message::ContentSearchEvent(search_id, event) => {
+                if search_id != self.content_search_id {
+                    return Task::none();
+                }
+                match event {
+                    ContentEvent::Found(results) => self.content_results.extend(results),
+                    ContentEvent::Finished {
+                        searched,
+                        skipped,
+                        errors,
+                    } => {
+                        self.content_searching = false;
+                        self.content_searched = searched;
+                        self.content_skipped = skipped;
+                        self.content_errors.extend(errors);
+                    }
+                }
+            }
+            Message::OrganizeMatchedFiles => {
+                let rules = self.config.get_rules().clone();
+                return self.plan_matched_files(rules);
+            }
+            Message::BulkDestinationChanged(destination) => {
+                self.bulk_destination = destination;
+            }
+            Message::MoveMatchedFiles => {
+                let destination = self.bulk_destination.trim();
+                if destination.is_empty() {
+                    return Task::none();
+                }
+                let rule = Rule::build(&format!("Move to {}", destination), &[], destination);
+                return self.plan_matched_files(vec![rule]);
+            }
+            Message::FindDuplicates => {
+                self.duplicate_report = None;
+                self.layout = layouts::Layout::Duplicates;
+                let root = util::current_path_buf(&self.current_path);
+                let entry_filter = self.entry_filter.clone();
+                return Task::perform(
+                    async move { index::find_duplicates(&root, &entry_filter) },
+                    Message::DuplicatesFound,
+                );
+            }
+            Message::DuplicatesFound(duplicate_report) => {
+                self.duplicate_report = Some(duplicate_report);
+            }
+            Message::SnapshotsLayout => {
+                self.snapshot_result = None;
+                self.snapshot_diff = None;
+                self.export_result = None;
+                self.list_snapshots();
+                self.layout = layouts::Layout::Snapshots;
+            }
+            Message::ToggleSnapshotHash(hash_snapshot) => {
+                self.hash_snapshot = hash_snapshot;
+            }
+            Message::SaveSnapshot => {
+                if self.saving_snapshot {
+                    return Task::none();
+                }
+                self.saving_snapshot = true;
+                self.snapshot_result = None;
+                let root = util::current_path_buf(&self.current_path);
+                let filter = SnapshotFilter::build(
+                    self.config.get_show_hidden(),
+                    self.config.get_ignore_patterns(),
+                );
+                let hashed = self.hash_snapshot;
+                return Task::perform(
+                    async move { snapshot::save_snapshot(&root, filter, hashed) },
+                    Message::SnapshotSaved,
+                );
+            }
+            Message::SnapshotSaved(snapshot_result) => {
+                self.saving_snapshot = false;
+                self.snapshot_result = Some(snapshot_result);
+                self.list_snapshots();
+            }
+            Message::OldSnapshotSelected(choice) => {
+                self.old_snapshot = Some(choice);
+            }
+            Message::NewSnapshotSelected(choice) => {
+                self.new_snapshot = Some(choice);
+            }
+            Message::CompareSnapshots => {
+                let (Some(old), Some(new)) = (self.old_snapshot.clone(), self.new_snapshot.clone())
+                else {
+                    return Task::none();
+                };
+                if self.comparing_snapshots {
+                    return Task::none();
+                }
+                self.comparing_snapshots = true;
+                self.snapshot_diff = None;
+                self.export_result = None;
+                let entry_filter = self.entry_filter.clone();
+                return Task::perform(
+                    async move { snapshot::compare(&old, &new, &entry_filter) },
+                    Message::SnapshotsCompared,
+                );
+            }
+            Message::SnapshotsCompared(snapshot_diff) => {
+                self.comparing_snapshots = false;
+                self.snapshot_diff = Some(snapshot_diff);
+            }
+            Message::ExportSnapshotDiff(format) => {
+                if let Some(snapshot_diff) = self.snapshot_diff.clone() {
+                    self.exporting = true;
+                    self.export_result = None;
+                    return Task::perform(
+                        async move { snapshot::export_diff(&snapshot_diff, format) },
+                        Message::Exported,
+                    );
+                }
+            }
+            Message::ToggleExportRecursive(export_recursive) => {
+                self.export_recursive = export_recursive;
+            }
+            Message::ToggleExportHashes(export_hashes) => {
+                self.export_hashes = export_hashes;
+            }
+            Message::ExportListing(format) => {
+                if self.exporting {
+                    return Task::none();
+                }
+                self.exporting = true;
+                self.export_result = None;
+                let root = util::current_path_buf(&self.current_path);
+                let directory = self.root.find_directory_by_id(&self.id_stack).clone();
+                let recursive = self.export_recursive;
+                let entry_filter = self.entry_filter.clone();
+                let hashed = self.export_hashes;
+                return Task::perform(
+                    async move {
+                        export::export_listing(
+                            &root,
+                            &directory,
+                            recursive,
+                            &entry_filter,
+                            hashed,
+                            format,
+                        )
+                    },
+                    Message::Exported,
+                );
+            }
+            Message::ExportPlan(format) => {
+                if let Some(plan) = self.plan.clone() {
+                    self.exporting = true;
+                    self.export_result = None;
+                    let plan_result = self.plan_result.clone();
+                    return Task::perform(
+                        async move { plan::export_plan(&plan, plan_result.as_ref(), format) },
+                        Message::Exported,
+                    );
+                }
+            }
+            Message::Exported(export_result) => {
+                self.exporting = false;
+                self.export_result = Some(export_result);
+            }
+            Message::UsageLayout => {
+                self.error = None;
+                self.layout = layouts::Layout::Usage;
+                self.usage_path = util::current_path_buf(&self.current_path);
+                // Sizes measured from a directory above are reused
+                if self.get_usage_node(&self.usage_path).is_none() {
+                    return self.measure_usage(self.usage_path.clone());
+                }
+            }
+            Message::MeasureUsage => {
+                return self.measure_usage(self.usage_path.clone());
+            }
+            Message::UsageMeasured(usage) => {
+                self.measuring_usage = false;
+                self.usage_path = PathBuf::from(usage.get_root().get_path());
+                self.usage = Some(usage);
+            }
+            Message::UsageNavigate(path) => {
+                if self.get_usage_node(&path).is_some() {
+                    self.usage_path = path;
+                }
+            }
+            Message::SortUsage(usage_sort) => {
+                // Choosing the same column again reverses the order
+                if self.usage_sort == usage_sort {
+                    self.usage_descending = !self.usage_descending;
+                } else {
+                    self.usage_sort = usage_sort;
+                    self.usage_descending = usage_sort != UsageSort::Name;
+                }
+            }
+            Message::BrowseUsage => {
+                self.error = None;
+                self.layout = layouts::Layout::Templates;
+                let path = self.usage_path.clone();
+                self.navigate(path.as_os_str());
+                return self.visit_current_path(&previous_path);
+            }
+            Message::OpenSearchResult(path) => {
+                let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+                    return Task::none();
+                };
+                let name = OsString::from(name);
+                self.error = None;
+                self.layout = layouts::Layout::Templates;
+                self.navigate(parent.as_os_str());
+                let visit = self.visit_current_path(&previous_path);
+                return Task::batch([visit, Task::done(Message::Select(name))]);
+            }
+            Message::ToggleThumbnails => {
+                self.config
+                    .set_show_thumbnails(!self.config.get_show_thumbnails());
+                self.save_config();
+                return self.load_thumbnails();
+            }
+            Message::ThumbnailsLoaded(thumbnails) => {
+                self.loading_thumbnails = false;
+                for (path, modified, bytes) in thumbnails {
+                    let handle = bytes.map(image::Handle::from_bytes);
+                    self.thumbnails
+                        .insert(path, LoadedThumbnail { modified, handle });
+                }
+                // The directory may have changed while these were loading
+                return self.load_thumbnails();
+            }
+            Message::ArchiveEntriesRead(path, entries) => {
+                self.reading_archive = None;
+                let directory = self.root.find_directory_by_id(&self.id_stack);
+                // The archive may have been left while it was read
+                if !directory.is_unread_archive()
+                    || directory
+                        .get_archive()
+                        .as_ref()
+                        .is_none_or(|location| location.get_path() != path)
+                {
+                    self.pending_components.clear();
+                    return self.read_archive_entries();
+                }
+                match entries {
+                    Ok(entries) => {
+                        directory.insert_archive_entries(
+                            entries,
+                            &mut self.directories_read,
+                            &self.entry_filter,
+                        );
+                        let components = std::mem::take(&mut self.pending_components);
+                        if let Err(error) = self.enter_components(components) {
+                            self.error = Some(error);
+                        }
+                        self.address = util::display_path(&self.current_path);
+                    }
+                    Err(error) => {
+                        self.pending_components.clear();
+                        self.error = Some(Error::other(format!("{}: {}", path.display(), error)));
+                    }
+                }
+                return self.read_archive_entries();
+            }
+            Message::ContentTypesDetected(content_types) => {
+                self.detecting_types = false;
+                for (path, modified, content_type) in content_types {
+                    self.content_types.insert(
+                        path,
+                        DetectedType {
+                            modified,
+                            content_type,
+                        },
+                    );
+                }
+                return self.detect_content_types();
+            }
+            Message::AddBookmark => {
+                let path = util::current_path_buf(&self.current_path);
+                if self.config.add_bookmark(&path) {
+                    self.save_config();
+                }
+            }
+            Message::RemoveBookmark(index) => {
+                if self.config.remove_bookmark(index) {
+                    self.save_config();
+                }
+            }
+            Message::MoveBookmark(from, to) => {
+                if self.config.move_bookmark(from, to) {
+                    self.save_config();
+                }
+            }
+            Message::ToggleHidden => {
+                self.error = None;
+                self.config.set_show_hidden(!self.config.get_show_hidden());
+                self.save_config();
+                match self.config.build_entry_filter() {
+                    Ok(entry_filter) => self.entry_filter = entry_filter,
+                    Err(error) => self.error = Some(error),
+                }
+                self.navigate(util::current_path_buf(&self.current_path).as_os_str());
+                return self.read_archive_entries();
+            }
+            Message::CreatePlan => {
+                self.error = None;
+                self.plan = None;
+                self.plan_result = None;
+                self.export_result = None;
+                self.layout = layouts::Layout::Plan;
+                let root = util::current_path_buf(&self.current_path);
+                let rules = self.config.get_rules().clone();
+                let entry_filter = self.entry_filter.clone();
+                let protection = self.protection.clone();
+                return Task::perform(
+                    async move { plan::build_plan(&root, &rules, &entry_filter, &protection) },
+                    Message::PlanCreated,
+                );
+            }
+            Message::PlanCreated(plan) => {
+                self.plan = Some(plan);
+            }
+            Message::ApplyPlan => {
+                if self.applying_plan || self.plan_result.is_some() {
+                    return Task::none();
+                }
+                if let Some(plan) = self.plan.clone() {
+                    self.applying_plan = true;
+                    self.plan_progress = None;
+                    let receiver = plan::start_apply(plan, self.config.get_verify_copies());
+                    return Task::run(receiver, Message::PlanApplyEvent);
+                }
+            }
+            Message::PlanApplyEvent(event) => match event {
+                ApplyEvent::Progress(progress) => self.plan_progress = Some(progress),
+                ApplyEvent::Finished(plan_result) => {
+                    self.applying_plan = false;
+                    self.plan_progress = None;
+                    self.plan_result = Some(plan_result);
+                }
+            },
+            Message::ToggleVerifyCopies(verify_copies) => {
+                self.config.set_verify_copies(verify_copies);
+                self.save_config();
+            }
+            Message::ClosePlan => {
+                self.plan = None;
+                self.plan_result = None;
+                self.layout = layouts::Layout::Templates;
+                self.navigate(util::current_path_buf(&self.current_path).as_os_str());
+                return self.read_archive_entries();
+            }
+            Message::CreateTypeReport => {
+                self.type_report = None;
+                self.layout = layouts::Layout::TypeReport;
+                let root = util::current_path_buf(&self.current_path);
+                let entry_filter = self.entry_filter.clone();
+                return Task::perform(
+                    async move { content_type::find_type_mismatches(&root, &entry_filter) },
+                    Message::TypeReportCreated,
+                );
+            }
+            Message::TypeReportCreated(type_report) => {
+                self.type_report = Some(type_report);
+            }
+            Message::BackToBrowser => {
+                self.layout = layouts::Layout::Templates;
+                self.navigate(util::current_path_buf(&self.current_path).as_os_str());
+                return self.read_archive_entries();
+            }
+            Message::ExtractDestinationChanged(destination) => {
+                self.extract_destination = destination;
+            }
+            Message::Extract(member_name) => {
+                let directory = self.root.find_directory_by_id(&self.id_stack);
+                let Some(location) = directory.get_archive().clone() else {
+                    return Task::none();
+                };
+                let member_path = match member_name {
+                    Some(name) => location.get_member(&name.to_string_lossy()),
+                    None => String::from(location.get_member_path()),
+                };
+                let destination = self.get_extract_destination();
+                self.extracting = true;
+                self.extract_result = None;
+                return Task::perform(
+                    async move { archive::extract(location.get_path(), &member_path, &destination) },
+                    Message::Extracted,
+                );
+            }
+            Message::Extracted(extract_result) => {
+                self.extracting = false;
+                self.extract_result = Some(extract_result);
+            }
+            // Like a plan, the run to undo is shown before anything changes
+            Message::Undo => {
+                self.plan = None;
+                self.plan_result = None;
+                self.undo_preview = None;
+                self.undo_result = None;
+                self.layout = layouts::Layout::Undo;
+                return Task::perform(
+                    async { journal::find_last().map_err(|error| error.to_string()) },
+                    Message::UndoPreviewRead,
+                );
+            }
+            Message::UndoPreviewRead(undo_preview) => {
+                self.undo_preview = Some(undo_preview);
+            }
+            Message::ConfirmUndo => {
+                let Some(Ok(Some(entry))) = self.undo_preview.take() else {
+                    return Task::none();
+                };
+                self.layout = layouts::Layout::Templates;
+                let verify = self.config.get_verify_copies();
+                return Task::perform(
+                    async move { journal::undo_last(entry.get_time(), verify) },
+                    Message::Undone,
+                );
+            }
+            Message::CloseUndo => {
+                self.undo_preview = None;
+                self.layout = layouts::Layout::Templates;
+            }
+            Message::Undone(undo_result) => {
+                self.undo_result = Some(undo_result);
+                self.navigate(util::current_path_buf(&self.current_path).as_os_str());
+                return self.read_archive_entries();
+            }
+            Message::RetentionLayout => {
+                self.retention_report = None;
+                self.retention_result = None;
+                self.layout = layouts::Layout::Retention;
+            }
+            Message::PreviewPolicy(index) => {
+                if self.previewing_retention || self.applying_retention {
+                    return Task::none();
+                }
+                let Some(policy) = self.config.get_policies().get(index).cloned() else {
+                    return Task::none();
+                };
+                self.retention_report = None;
+                self.retention_result = None;
+                self.previewing_retention = true;
+                let ignore_patterns = self.config.get_ignore_patterns().clone();
+                let protection = self.protection.clone();
+                return Task::perform(
+                    async move { retention::create_report(&policy, &ignore_patterns, &protection) },
+                    Message::PolicyPreviewed,
+                );
+            }
+            Message::PolicyPreviewed(retention_report) => {
+                self.previewing_retention = false;
+                self.retention_report = Some(retention_report);
+            }
+            Message::ApplyRetention => {
+                if self.applying_retention || self.retention_result.is_some() {
+                    return Task::none();
+                }
+                if let Some(retention_report) = self.retention_report.clone() {
+                    self.applying_retention = true;
+                    return Task::perform(
+                        async move { retention::apply_report(&retention_report) },
+                        Message::RetentionApplied,
+                    );
+                }
+            }
+            Message::RetentionApplied(retention_result) => {
+                self.applying_retention = false;
+                self.retention_result = Some(retention_result);
+            }
+            Message::FindEmptyDirectories(root) => {
+                self.plan = None;
+                self.plan_result = None;
+                self.cleanup_plan = None;
+                self.cleanup_result = None;
+                self.layout = layouts::Layout::Cleanup;
+                let protection = self.protection.clone();
+                return Task::perform(
+                    async move { cleanup::find_empty_directories(&root, &protection) },
+                    Message::EmptyDirectoriesFound,
+                );
+            }
+            Message::EmptyDirectoriesFound(cleanup_plan) => {
+                self.cleanup_plan = Some(cleanup_plan);
+            }
+            Message::RemoveEmptyDirectories => {
+                if self.removing_empty_directories || self.cleanup_result.is_some() {
+                    return Task::none();
+                }
+                if let Some(cleanup_plan) = self.cleanup_plan.clone() {
+                    self.removing_empty_directories = true;
+                    return Task::perform(
+                        async move { cleanup::remove_empty_directories(&cleanup_plan) },
+                        Message::EmptyDirectoriesRemoved,
+                    );
+                }
+            }
+            Message::EmptyDirectoriesRemoved(cleanup_result) => {
+                self.removing_empty_directories = false;
+                self.cleanup_result = Some(cleanup_result);
             }
         }
+        Task::none()
     }
 
     pub fn get_root(&self) -> &Directory {
@@ -162,15 +1476,798 @@ impl App {
         &self.id_stack
     }
 
+    pub fn get_current_path(&self) -> &OsStr {
+        self.current_path.as_os_str()
+    }
+
+    pub fn get_address(&self) -> &str {
+        &self.address
+    }
+
+    pub fn get_history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn get_selected(&self) -> &Option<OsString> {
+        &self.selected
+    }
+
+    pub fn get_selected_path(&self) -> Option<PathBuf> {
+        let selected = self.selected.as_ref()?;
+        Some(util::current_path_buf(&self.current_path).join(selected))
+    }
+
+    // Metadata is read in the background, so it may belong to an earlier selection
+    pub fn get_selected_metadata(&self) -> Option<&DocumentMetadata> {
+        let (path, document_metadata) = self.selected_metadata.as_ref()?;
+        if Some(path) != self.get_selected_path().as_ref() {
+            return None;
+        }
+        Some(document_metadata)
+    }
+
+    pub fn get_selected_thumbnail(&self) -> Option<&image::Handle> {
+        let (path, handle) = self.selected_thumbnail.as_ref()?;
+        if Some(path) != self.get_selected_path().as_ref() {
+            return None;
+        }
+        Some(handle)
+    }
+
+    pub fn get_preview(&self) -> Option<&Preview> {
+        let preview = self.preview.as_ref()?;
+        if Some(preview.get_path()) != self.get_selected_path().as_deref() {
+            return None;
+        }
+        Some(preview)
+    }
+
+    // Binary files only have the hex view
+    pub fn get_preview_mode(&self) -> PreviewMode {
+        match self.get_preview() {
+            Some(preview) if preview.get_text().is_none() => PreviewMode::Hex,
+            _ => self.preview_mode,
+        }
+    }
+
+    pub fn get_panes(&self) -> &pane_grid::State<layouts::BrowserPane> {
+        &self.panes
+    }
+
+    pub fn get_filter_text(&self) -> &str {
+        &self.filter_text
+    }
+
+    // The filter of the current directory matches anywhere in the name
+    pub fn matches_filter(&self, name: &OsStr) -> bool {
+        let filter_text = self.filter_text.trim().to_lowercase();
+        filter_text.is_empty() || name.to_string_lossy().to_lowercase().contains(&filter_text)
+    }
+
+    pub fn get_search_query(&self) -> &SearchQuery {
+        &self.search_query
+    }
+
+    pub fn get_search_root(&self) -> &Path {
+        &self.search_root
+    }
+
+    pub fn get_search_results(&self) -> &Vec<SearchResult> {
+        &self.search_results
+    }
+
+    pub fn get_search_errors(&self) -> &Vec<String> {
+        &self.search_errors
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.searching
+    }
+
+    pub fn get_index_update(&self) -> &Option<IndexUpdate> {
+        &self.index_update
+    }
+
+    pub fn is_updating_index(&self) -> bool {
+        self.updating_index
+    }
+
+    pub fn get_duplicate_report(&self) -> &Option<DuplicateReport> {
+        &self.duplicate_report
+    }
+
+    pub fn get_snapshots(&self) -> &Vec<SnapshotInfo> {
+        &self.snapshots
+    }
+
+    pub fn get_snapshot_errors(&self) -> &Vec<String> {
+        &self.snapshot_errors
+    }
+
+    pub fn get_hash_snapshot(&self) -> bool {
+        self.hash_snapshot
+    }
+
+    pub fn is_saving_snapshot(&self) -> bool {
+        self.saving_snapshot
+    }
+
+    pub fn get_snapshot_result(&self) -> &Option<SnapshotResult> {
+        &self.snapshot_result
+    }
+
+    pub fn get_old_snapshot(&self) -> &Option<SnapshotChoice> {
+        &self.old_snapshot
+    }
+
+    pub fn get_new_snapshot(&self) -> &Option<SnapshotChoice> {
+        &self.new_snapshot
+    }
+
+    pub fn is_comparing_snapshots(&self) -> bool {
+        self.comparing_snapshots
+    }
+
+    pub fn get_snapshot_diff(&self) -> &Option<SnapshotDiff> {
+        &self.snapshot_diff
+    }
+
+    pub fn get_export_result(&self) -> &Option<Result<PathBuf, String>> {
+        &self.export_result
+    }
+
+    pub fn get_export_recursive(&self) -> bool {
+        self.export_recursive
+    }
+
+    pub fn get_export_hashes(&self) -> bool {
+        self.export_hashes
+    }
+
+    pub fn is_exporting(&self) -> bool {
+        self.exporting
+    }
+
+    pub fn get_usage(&self) -> &Option<UsageReport> {
+        &self.usage
+    }
+
+    pub fn get_usage_path(&self) -> &Path {
+        &self.usage_path
+    }
+
+    pub fn is_measuring_usage(&self) -> bool {
+        self.measuring_usage
+    }
+
+    pub fn get_usage_sort(&self) -> UsageSort {
+        self.usage_sort
+    }
+
+    pub fn is_usage_descending(&self) -> bool {
+        self.usage_descending
+    }
+
+    // Only directories inside the last measured one have a known size
+    pub fn get_usage_node(&self, path: &Path) -> Option<&UsageNode> {
+        self.usage.as_ref()?.get_root().find(path)
+    }
+
+    pub fn get_content_query(&self) -> &ContentQuery {
+        &self.content_query
+    }
+
+    pub fn get_content_root(&self) -> &Path {
+        &self.content_root
+    }
+
+    pub fn get_content_results(&self) -> &Vec<ContentMatch> {
+        &self.content_results
+    }
+
+    pub fn get_content_errors(&self) -> &Vec<String> {
+        &self.content_errors
+    }
+
+    pub fn get_content_skipped(&self) -> &Vec<PathBuf> {
+        &self.content_skipped
+    }
+
+    pub fn get_content_searched(&self) -> usize {
+        self.content_searched
+    }
+
+    pub fn is_content_searching(&self) -> bool {
+        self.content_searching
+    }
+
+    pub fn get_bulk_destination(&self) -> &str {
+        &self.bulk_destination
+    }
+
+    pub fn get_show_thumbnails(&self) -> bool {
+        self.config.get_show_thumbnails()
+    }
+
+    // A thumbnail made before the file was modified is not shown
+    pub fn get_thumbnail(&self, file: &File) -> Option<&image::Handle> {
+        let path = util::current_path_buf(&self.current_path).join(file.get_name());
+        let loaded = self.thumbnails.get(&path)?;
+        if loaded.modified != file.get_metadata().get_modified() {
+            return None;
+        }
+        loaded.handle.as_ref()
+    }
+
+    pub fn get_reading_archive(&self) -> &Option<PathBuf> {
+        &self.reading_archive
+    }
+
+    pub fn get_content_type(&self, file: &File) -> Option<&ContentType> {
+        let path = util::current_path_buf(&self.current_path).join(file.get_name());
+        let detected = self.content_types.get(&path)?;
+        if detected.modified != file.get_metadata().get_modified() {
+            return None;
+        }
+        detected.content_type.as_ref()
+    }
+
+    pub fn get_config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn get_entry_filter(&self) -> &EntryFilter {
+        &self.entry_filter
+    }
+
+    pub fn get_plan(&self) -> &Option<Plan> {
+        &self.plan
+    }
+
+    pub fn get_plan_result(&self) -> &Option<PlanResult> {
+        &self.plan_result
+    }
+
+    pub fn get_type_report(&self) -> &Option<Vec<TypeMismatch>> {
+        &self.type_report
+    }
+
+    pub fn is_applying_plan(&self) -> bool {
+        self.applying_plan
+    }
+
+    pub fn get_plan_progress(&self) -> &Option<TransferProgress> {
+        &self.plan_progress
+    }
+
+    pub fn get_verify_copies(&self) -> bool {
+        self.config.get_verify_copies()
+    }
+
+    pub fn get_extract_destination_input(&self) -> &str {
+        &self.extract_destination
+    }
+
+    // Without a chosen location archives are extracted next to themselves
+    pub fn get_extract_destination(&self) -> PathBuf {
+        let destination = self.extract_destination.trim();
+        if !destination.is_empty() {
+            return PathBuf::from(destination);
+        }
+        let mut directory = self.get_root();
+        let mut archive_path = None;
+        for id in &self.id_stack {
+            let Some(child) = directory
+                .get_directories()
+                .iter()
+                .find(|child| child.get_directory_id() == *id)
+            else {
+                break;
+            };
+            directory = child;
+            if let Some(location) = directory.get_archive() {
+                archive_path = Some(location.get_path());
+                break;
+            }
+        }
+        archive_path
+            .and_then(|path| path.parent())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| util::current_path_buf(&self.current_path))
+    }
+
+    pub fn get_extract_result(&self) -> &Option<ExtractResult> {
+        &self.extract_result
+    }
+
+    pub fn get_undo_preview(&self) -> &Option<Result<Option<JournalEntry>, String>> {
+        &self.undo_preview
+    }
+
+    pub fn get_undo_result(&self) -> &Option<UndoResult> {
+        &self.undo_result
+    }
+
+    pub fn get_policies(&self) -> &Vec<retention::Policy> {
+        self.config.get_policies()
+    }
+
+    pub fn get_retention_report(&self) -> &Option<RetentionReport> {
+        &self.retention_report
+    }
+
+    pub fn get_retention_result(&self) -> &Option<RetentionResult> {
+        &self.retention_result
+    }
+
+    pub fn is_previewing_retention(&self) -> bool {
+        self.previewing_retention
+    }
+
+    pub fn is_applying_retention(&self) -> bool {
+        self.applying_retention
+    }
+
+    pub fn get_cleanup_plan(&self) -> &Option<CleanupPlan> {
+        &self.cleanup_plan
+    }
+
+    pub fn get_cleanup_result(&self) -> &Option<CleanupResult> {
+        &self.cleanup_result
+    }
+
+    pub fn is_removing_empty_directories(&self) -> bool {
+        self.removing_empty_directories
+    }
+
+    pub fn is_extracting(&self) -> bool {
+        self.extracting
+    }
+
+    pub fn get_user_directories(&self) -> &Vec<(String, PathBuf)> {
+        &self.user_directories
+    }
+
     pub fn get_external_storage_devices(&self) -> &Vec<Directory> {
         &self.external_storage_directories
     }
 
+    pub fn get_device_space(&self, directory_id: usize) -> Option<&SpaceInfo> {
+        self.device_space.get(&directory_id)
+    }
+
+    pub fn get_current_space(&self) -> &Option<SpaceInfo> {
+        &self.current_space
+    }
+
+    pub fn get_camera_volume(&self, directory_id: usize) -> Option<&PathBuf> {
+        self.camera_volumes.get(&directory_id)
+    }
+
+    pub fn get_import_volume(&self) -> &Path {
+        &self.import_volume
+    }
+
+    pub fn get_import_root(&self) -> &str {
+        &self.import_root
+    }
+
+    pub fn get_import_pattern(&self) -> &str {
+        &self.import_pattern
+    }
+
+    pub fn get_delete_after_import(&self) -> bool {
+        self.delete_after_import
+    }
+
+    pub fn get_import_plan(&self) -> &Option<ImportPlan> {
+        &self.import_plan
+    }
+
+    pub fn is_planning_import(&self) -> bool {
+        self.planning_import
+    }
+
+    pub fn is_importing(&self) -> bool {
+        self.importing
+    }
+
+    pub fn get_import_progress(&self) -> &Option<TransferProgress> {
+        &self.import_progress
+    }
+
+    pub fn get_import_result(&self) -> &Option<ImportResult> {
+        &self.import_result
+    }
+
+    pub fn get_backup_source(&self) -> &Path {
+        &self.backup_source
+    }
+
+    pub fn get_backup_devices(&self) -> &Vec<String> {
+        &self.backup_devices
+    }
+
+    pub fn get_backup_target(&self) -> &str {
+        &self.backup_target
+    }
+
+    pub fn get_backup_compare(&self) -> BackupCompare {
+        self.backup_compare
+    }
+
+    pub fn get_keep_removed_in_backup(&self) -> bool {
+        self.keep_removed_in_backup
+    }
+
+    pub fn get_backup_plan(&self) -> &Option<BackupPlan> {
+        &self.backup_plan
+    }
+
+    pub fn is_planning_backup(&self) -> bool {
+        self.planning_backup
+    }
+
+    pub fn is_backing_up(&self) -> bool {
+        self.backing_up
+    }
+
+    pub fn get_backup_progress(&self) -> &Option<TransferProgress> {
+        &self.backup_progress
+    }
+
+    pub fn get_backup_result(&self) -> &Option<BackupResult> {
+        &self.backup_result
+    }
+
     pub fn get_error(&self) -> &Option<Error> {
         &self.error
     }
 
-    fn initialize_external_devices(&mut self, external_storage_paths: &Vec<OsString>) {
+    fn enter_directory(&mut self, selected_directory_id: usize) -> std::io::Result<()> {
+        self.id_stack.push(selected_directory_id);
+        let temp_directories_read = self.directories_read;
+        if let Err(error) = self.root.insert_new_sub_directory(
+            &self.id_stack,
+            &mut self.current_path,
+            &mut self.directories_read,
+            selected_directory_id,
+            &self.entry_filter,
+        ) {
+            self.directories_read = temp_directories_read;
+            self.current_path =
+                util::remove_directory_from_path(&self.current_path, &self.operating_system);
+            self.id_stack.pop();
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    fn visit_current_path(&mut self, previous_path: &OsStr) -> Task<Message> {
+        if self.current_path == previous_path {
+            return Task::none();
+        }
+        self.history.push(&self.current_path);
+        if self
+            .config
+            .add_recent_location(&util::current_path_buf(&self.current_path))
+        {
+            self.save_config();
+        }
+        self.selected = None;
+        self.filter_text.clear();
+        self.export_result = None;
+        self.scroll_offset = AbsoluteOffset::default();
+        Task::batch([
+            scrollable::scroll_to(scrollable::Id::new(DIRECTORY_LIST_ID), self.scroll_offset),
+            self.read_archive_entries(),
+            self.load_thumbnails(),
+            self.detect_content_types(),
+            self.read_current_space(),
+        ])
+    }
+
+    fn plan_import(&mut self) -> Task<Message> {
+        if self.planning_import || self.importing {
+            return Task::none();
+        }
+        self.planning_import = true;
+        self.import_plan = None;
+        let volume = self.import_volume.clone();
+        let root = PathBuf::from(self.config.get_import_root());
+        let destination_pattern = String::from(self.config.get_import_pattern());
+        let entry_filter = self.entry_filter.clone();
+        Task::perform(
+            async move { import::plan_import(&volume, &root, &destination_pattern, &entry_filter) },
+            Message::ImportPlanned,
+        )
+    }
+
+    // A changed setting needs a new dry run before anything is copied
+    fn clear_backup_plan(&mut self) {
+        self.backup_plan = None;
+        self.backup_result = None;
+    }
+
+    fn read_current_space(&self) -> Task<Message> {
+        let path = util::current_path_buf(&self.current_path);
+        Task::perform(
+            async move {
+                let space = storage::get_space_info(&path).ok();
+                (path, space)
+            },
+            |(path, space)| Message::CurrentSpaceRead(path, space),
+        )
+    }
+
+    // Matched files are organized through the plan view like a whole
+    // directory, relative to where the search started
+    fn plan_matched_files(&mut self, rules: Vec<Rule>) -> Task<Message> {
+        if self.content_searching || self.content_results.is_empty() {
+            return Task::none();
+        }
+        self.error = None;
+        self.plan = None;
+        self.plan_result = None;
+        self.export_result = None;
+        self.layout = layouts::Layout::Plan;
+        let root = self.content_root.clone();
+        let files: Vec<PathBuf> = self
+            .content_results
+            .iter()
+            .map(|content_match| PathBuf::from(content_match.get_path()))
+            .collect();
+        let protection = self.protection.clone();
+        Task::perform(
+            async move { plan::build_plan_for_files(&root, &files, &rules, &protection) },
+            Message::PlanCreated,
+        )
+    }
+
+    fn measure_usage(&mut self, root: PathBuf) -> Task<Message> {
+        if self.measuring_usage {
+            return Task::none();
+        }
+        self.measuring_usage = true;
+        let entry_filter = self.entry_filter.clone();
+        Task::perform(
+            async move { usage::measure(&root, &entry_filter) },
+            Message::UsageMeasured,
+        )
+    }
+
+    // Choices that point at snapshots that are gone are dropped
+    fn list_snapshots(&mut self) {
+        let (snapshots, errors) = snapshot::list_snapshots();
+        self.snapshots = snapshots;
+        self.snapshot_errors = errors;
+        for choice in [&mut self.old_snapshot, &mut self.new_snapshot] {
+            if let Some(SnapshotChoice::Saved(info)) = choice {
+                if !self.snapshots.contains(info) {
+                    *choice = None;
+                }
+            }
+        }
+    }
+
+    // Thumbnails for the images of the current directory are made in the
+    // background, one directory at a time
+    fn load_thumbnails(&mut self) -> Task<Message> {
+        if !self.config.get_show_thumbnails() || self.loading_thumbnails {
+            return Task::none();
+        }
+        let directory_path = util::current_path_buf(&self.current_path);
+        let directory = self.root.find_directory_by_id(&self.id_stack);
+        if directory.get_archive().is_some() {
+            return Task::none();
+        }
+        let missing: Vec<(PathBuf, Option<DateTime<Local>>)> = directory
+            .get_files()
+            .iter()
+            .map(|file| (directory_path.join(file.get_name()), file))
+            .filter(|(path, file)| {
+                thumbnail::is_thumbnailable(path)
+                    && self
+                        .thumbnails
+                        .get(path)
+                        .is_none_or(|loaded| loaded.modified != file.get_metadata().get_modified())
+            })
+            .map(|(path, file)| (path, file.get_metadata().get_modified()))
+            .collect();
+        if missing.is_empty() {
+            return Task::none();
+        }
+        self.loading_thumbnails = true;
+        Task::perform(
+            async move {
+                missing
+                    .into_iter()
+                    .map(|(path, modified)| {
+                        let bytes = thumbnail::load(&path, ThumbnailSize::Normal).ok();
+                        (path, modified, bytes)
+                    })
+                    .collect()
+            },
+            Message::ThumbnailsLoaded,
+        )
+    }
+
+    fn detect_content_types(&mut self) -> Task<Message> {
+        if self.detecting_types {
+            return Task::none();
+        }
+        let directory_path = util::current_path_buf(&self.current_path);
+        let directory = self.root.find_directory_by_id(&self.id_stack);
+        if directory.get_archive().is_some() {
+            return Task::none();
+        }
+        let missing: Vec<(PathBuf, Option<DateTime<Local>>)> = directory
+            .get_files()
+            .iter()
+            .map(|file| (directory_path.join(file.get_name()), file))
+            .filter(|(path, file)| {
+                self.content_types
+                    .get(path)
+                    .is_none_or(|detected| detected.modified != file.get_metadata().get_modified())
+            })
+            .map(|(path, file)| (path, file.get_metadata().get_modified()))
+            .collect();
+        if missing.is_empty() {
+            return Task::none();
+        }
+        self.detecting_types = true;
+        Task::perform(
+            async move {
+                missing
+                    .into_iter()
+                    .map(|(path, modified)| {
+                        let content_type = ContentType::detect(&path).ok().flatten();
+                        (path, modified, content_type)
+                    })
+                    .collect()
+            },
+            Message::ContentTypesDetected,
+        )
+    }
+
+    // Listing an archive can mean decompressing all of it, so its entries are
+    // read in the background once it is entered
+    fn read_archive_entries(&mut self) -> Task<Message> {
+        if self.reading_archive.is_some() {
+            return Task::none();
+        }
+        let directory = self.root.find_directory_by_id(&self.id_stack);
+        if !directory.is_unread_archive() {
+            return Task::none();
+        }
+        let Some(location) = directory.get_archive() else {
+            return Task::none();
+        };
+        let path = PathBuf::from(location.get_path());
+        self.reading_archive = Some(path.clone());
+        Task::perform(
+            async move {
+                let entries = archive::read_entries(&path)
+                    .map(Arc::new)
+                    .map_err(|error| error.to_string());
+                (path, entries)
+            },
+            |(path, entries)| Message::ArchiveEntriesRead(path, entries),
+        )
+    }
+
+    fn restore_history_entry(&mut self, entry: HistoryEntry) -> Task<Message> {
+        self.error = None;
+        self.navigate(util::current_path_buf(entry.get_path()).as_os_str());
+        self.selected = entry.get_selected().clone();
+        self.scroll_offset = entry.get_scroll_offset();
+        Task::batch([
+            scrollable::scroll_to(scrollable::Id::new(DIRECTORY_LIST_ID), self.scroll_offset),
+            self.read_archive_entries(),
+            self.load_thumbnails(),
+            self.detect_content_types(),
+            self.read_current_space(),
+        ])
+    }
+
+    fn save_config(&mut self) {
+        if let Err(error) = self.config.save() {
+            self.error = Some(error);
+        }
+    }
+
+    fn navigate(&mut self, path: &OsStr) {
+        let previous_path = util::current_path_buf(&self.current_path);
+        if let Err(error) = self.navigate_to_path(path) {
+            self.error = Some(error);
+            if self.navigate_to_path(previous_path.as_os_str()).is_err() {
+                self.root.clear_directories();
+                self.id_stack.clear();
+            }
+        }
+        self.address = util::display_path(&self.current_path);
+    }
+
+    fn navigate_to_path(&mut self, path: &OsStr) -> std::io::Result<()> {
+        // Paths inside an archive are resolved from the archive file
+        let requested = Path::new(path);
+        let archive_path = requested
+            .ancestors()
+            .find(|ancestor| ancestor.is_file() && archive::is_archive(ancestor));
+        let canonical = archive_path.unwrap_or(requested);
+        let mut path = fs::canonicalize(canonical).map_err(|error| {
+            Error::new(
+                error.kind(),
+                format!("Path does not exist: {}", path.to_string_lossy()),
+            )
+        })?;
+        match archive_path.and_then(|archive_path| requested.strip_prefix(archive_path).ok()) {
+            Some(member_path) => path.extend(member_path.components()),
+            None if !path.is_dir() => {
+                return Err(Error::other(format!(
+                    "Not a directory: {}",
+                    path.to_string_lossy()
+                )));
+            }
+            None => {}
+        }
+
+        let (root_path, current_path, components) = util::split_path(&path);
+        self.root.clear_directories();
+        self.id_stack.clear();
+        self.directories_read = 0;
+        self.root.write_directory_content(
+            &root_path,
+            &mut self.directories_read,
+            &self.entry_filter,
+        )?;
+        self.current_path = current_path;
+        self.enter_components(components)
+    }
+
+    // Typed paths are resolved on the filesystem, the filter only decides
+    // what is listed inside them. Components inside an archive wait until its
+    // entries are read.
+    fn enter_components(&mut self, components: Vec<OsString>) -> std::io::Result<()> {
+        self.pending_components.clear();
+        for (position, component) in components.iter().enumerate() {
+            if self
+                .root
+                .find_directory_by_id(&self.id_stack)
+                .is_unread_archive()
+            {
+                self.pending_components = components[position..].to_vec();
+                return Ok(());
+            }
+            let mut directory_path = self.current_path.clone();
+            directory_path.push("/");
+            directory_path.push(component);
+            let directory = self.root.find_directory_by_id(&self.id_stack);
+            let id = directory.find_directory_id_by_name(component).or_else(|| {
+                directory.insert_unlisted_directory(
+                    component,
+                    Path::new(&directory_path),
+                    &mut self.directories_read,
+                )
+            });
+            match id {
+                Some(id) => self.enter_directory(id)?,
+                None => {
+                    return Err(Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!(
+                            "Could not open {} in {}",
+                            component.to_string_lossy(),
+                            util::display_path(&self.current_path)
+                        ),
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn initialize_external_devices(&mut self, external_storage_paths: &[OsString]) {
         for (i, path) in external_storage_paths.iter().enumerate() {
             if let Some(path_str) = path.to_str() {
                 let mut last = "";
@@ -204,6 +2301,7 @@ impl App {
                 if let Err(error) = self.root.write_directory_content(
                     path_to_external_dir.as_os_str(),
                     &mut self.directories_read,
+                    &self.entry_filter,
                 ) {
                     self.error = Some(error);
                 }
@@ -227,6 +2325,7 @@ impl App {
                 if let Err(error) = self.root.write_directory_content(
                     path_to_external_dir.as_os_str(),
                     &mut self.directories_read,
+                    &self.entry_filter,
                 ) {
                     self.error = Some(error);
                 }
@@ -0,0 +1,123 @@
+use std::{io::Error, path::Path};
+
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
+
+pub const IGNORE_FILE_NAME: &str = ".fileorderignore";
+
+#[derive(Debug, Clone)]
+pub struct EntryFilter {
+    show_hidden: bool,
+    patterns: Vec<Gitignore>,
+    // The patterns as they were given, to tell filters apart
+    pattern_lines: Vec<String>,
+}
+
+impl EntryFilter {
+    pub fn new() -> Self {
+        Self {
+            show_hidden: false,
+            patterns: Vec::new(),
+            pattern_lines: Vec::new(),
+        }
+    }
+
+    pub fn build(show_hidden: bool, patterns: &[String]) -> Result<Self, Error> {
+        Self::build_rooted(show_hidden, Path::new("/"), patterns)
+    }
+
+    // Patterns containing a slash are matched relative to root
+    pub fn build_rooted(
+        show_hidden: bool,
+        root: &Path,
+        patterns: &[String],
+    ) -> Result<Self, Error> {
+        Self {
+            show_hidden,
+            patterns: Vec::new(),
+            pattern_lines: Vec::new(),
+        }
+        .with_rooted_patterns(root, patterns)
+    }
+
+    // Adds patterns relative to another root, such as the exclusions of a
+    // retention policy. They take precedence over the patterns added before.
+    pub fn with_rooted_patterns(mut self, root: &Path, patterns: &[String]) -> Result<Self, Error> {
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in patterns {
+            builder.add_line(None, pattern).map_err(Error::other)?;
+        }
+        self.patterns
+            .insert(0, builder.build().map_err(Error::other)?);
+        let mut lines = vec![root.to_string_lossy().into_owned()];
+        lines.extend(patterns.iter().cloned());
+        self.pattern_lines.insert(0, lines.join("\n"));
+        Ok(self)
+    }
+
+    pub fn get_show_hidden(&self) -> bool {
+        self.show_hidden
+    }
+
+    // Equal for filters built from the same settings and patterns
+    pub fn get_key(&self) -> String {
+        let mut key = format!("show_hidden={}", self.show_hidden);
+        for lines in &self.pattern_lines {
+            key.push_str("\n\n");
+            key.push_str(lines);
+        }
+        key
+    }
+
+    // Collects the ignore files of the directory and all of its ancestors so
+    // the entries of one directory can be matched without rereading them
+    pub fn for_directory(&self, directory: &Path) -> DirectoryFilter<'_> {
+        let mut ignore_files: Vec<Gitignore> = Vec::new();
+        for ancestor in directory.ancestors() {
+            let ignore_file = ancestor.join(IGNORE_FILE_NAME);
+            if ignore_file.is_file() {
+                let (gitignore, error) = Gitignore::new(&ignore_file);
+                if let Some(error) = error {
+                    eprintln!("Error reading {}: {}", ignore_file.display(), error);
+                }
+                ignore_files.push(gitignore);
+            }
+        }
+        DirectoryFilter {
+            filter: self,
+            ignore_files,
+        }
+    }
+}
+
+pub struct DirectoryFilter<'a> {
+    filter: &'a EntryFilter,
+    ignore_files: Vec<Gitignore>,
+}
+
+impl DirectoryFilter<'_> {
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if !self.filter.show_hidden && is_hidden(path) {
+            return true;
+        }
+        // Ignore files closest to the entry take precedence over the ones
+        // further up the tree and over the global patterns
+        let matchers = self.ignore_files.iter().chain(self.filter.patterns.iter());
+        for matcher in matchers {
+            match matcher.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+}
+
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
@@ -0,0 +1,151 @@
+use std::ffi::{OsStr, OsString};
+
+use iced::widget::scrollable::AbsoluteOffset;
+
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    path: OsString,
+    scroll_offset: AbsoluteOffset,
+    selected: Option<OsString>,
+}
+
+impl HistoryEntry {
+    pub fn build(path: &OsStr) -> Self {
+        Self {
+            path: OsString::from(path),
+            scroll_offset: AbsoluteOffset::default(),
+            selected: None,
+        }
+    }
+
+    pub fn get_path(&self) -> &OsStr {
+        self.path.as_os_str()
+    }
+
+    pub fn get_scroll_offset(&self) -> AbsoluteOffset {
+        self.scroll_offset
+    }
+
+    pub fn get_selected(&self) -> &Option<OsString> {
+        &self.selected
+    }
+}
+
+#[derive(Debug)]
+pub struct History {
+    entries: Vec<HistoryEntry>,
+    position: usize,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            position: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.position = 0;
+    }
+
+    // Records a newly visited location and drops every entry ahead of the
+    // current one, the same way a browser does after following a link
+    pub fn push(&mut self, path: &OsStr) {
+        if let Some(current) = self.entries.get(self.position) {
+            if current.path == path {
+                return;
+            }
+            self.entries.truncate(self.position + 1);
+            self.position += 1;
+        }
+        self.entries.push(HistoryEntry::build(path));
+    }
+
+    pub fn save_state(&mut self, scroll_offset: AbsoluteOffset, selected: &Option<OsString>) {
+        if let Some(current) = self.entries.get_mut(self.position) {
+            current.scroll_offset = scroll_offset;
+            current.selected = selected.clone();
+        }
+    }
+
+    pub fn back(&mut self) -> Option<&HistoryEntry> {
+        if !self.can_go_back() {
+            return None;
+        }
+        self.position -= 1;
+        self.entries.get(self.position)
+    }
+
+    pub fn forward(&mut self) -> Option<&HistoryEntry> {
+        if !self.can_go_forward() {
+            return None;
+        }
+        self.position += 1;
+        self.entries.get(self.position)
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        self.position > 0
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        self.position + 1 < self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visit(history: &mut History, paths: &[&str]) {
+        for path in paths {
+            history.push(OsStr::new(path));
+        }
+    }
+
+    #[test]
+    fn back_and_forward_walk_the_visited_paths() {
+        let mut history = History::new();
+        visit(&mut history, &["/", "/home", "/home/user"]);
+        assert!(!history.can_go_forward());
+        assert_eq!(history.back().unwrap().get_path(), "/home");
+        assert_eq!(history.back().unwrap().get_path(), "/");
+        assert!(history.back().is_none());
+        assert_eq!(history.forward().unwrap().get_path(), "/home");
+        assert_eq!(history.forward().unwrap().get_path(), "/home/user");
+        assert!(history.forward().is_none());
+    }
+
+    #[test]
+    fn visiting_after_going_back_drops_forward_entries() {
+        let mut history = History::new();
+        visit(&mut history, &["/", "/home", "/home/user"]);
+        history.back();
+        history.push(OsStr::new("/tmp"));
+        assert!(!history.can_go_forward());
+        assert_eq!(history.back().unwrap().get_path(), "/home");
+    }
+
+    #[test]
+    fn visiting_the_current_path_again_is_ignored() {
+        let mut history = History::new();
+        visit(&mut history, &["/", "/home", "/home"]);
+        assert_eq!(history.back().unwrap().get_path(), "/");
+        assert!(!history.can_go_back());
+    }
+
+    #[test]
+    fn saved_state_is_restored_with_the_entry() {
+        let mut history = History::new();
+        visit(&mut history, &["/", "/home"]);
+        history.back();
+        let offset = AbsoluteOffset { x: 0.0, y: 120.0 };
+        history.save_state(offset, &Some(OsString::from("file.txt")));
+        history.forward();
+        let entry = history.back().unwrap();
+        assert_eq!(entry.get_scroll_offset(), offset);
+        assert_eq!(entry.get_selected(), &Some(OsString::from("file.txt")));
+    }
+}
@@ -0,0 +1,500 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use chrono::Local;
+use iced::futures::channel::mpsc::{self as async_mpsc, UnboundedReceiver};
+use rusqlite::{params, Connection};
+
+use crate::{
+    file::FileMetadata,
+    filter::EntryFilter,
+    index,
+    pattern::{self, Tokens},
+    plan, scan,
+    storage::{self, SpaceShortage},
+    transfer::{self, ProgressThrottle, TransferProgress},
+};
+
+const IMPORT_DIRECTORY: &str = "fileorder";
+const IMPORT_FILE: &str = "imports.sqlite";
+pub const CAMERA_DIRECTORY: &str = "DCIM";
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DEFAULT_IMPORT_PATTERN: &str = "Photos/{exif:%Y}/{exif:%Y-%m-%d}";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS imported (
+        hash TEXT PRIMARY KEY,
+        size INTEGER NOT NULL,
+        source TEXT NOT NULL,
+        destination TEXT NOT NULL,
+        imported INTEGER NOT NULL
+    );
+";
+
+// Every file ever imported by its hash, so a card that is imported again
+// only brings the new photos, even after the old ones were moved or renamed
+struct ImportDatabase {
+    connection: Connection,
+}
+
+impl ImportDatabase {
+    fn open() -> Result<Self, String> {
+        let path = dirs::data_dir()
+            .map(|directory| directory.join(IMPORT_DIRECTORY).join(IMPORT_FILE))
+            .ok_or_else(|| String::from("Could not find data directory"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+        }
+        Self::open_path(&path)
+    }
+
+    fn open_path(path: &Path) -> Result<Self, String> {
+        let connection = Connection::open(path).map_err(|error| error.to_string())?;
+        connection
+            .busy_timeout(BUSY_TIMEOUT)
+            .and_then(|_| connection.execute_batch(SCHEMA))
+            .map_err(|error| error.to_string())?;
+        Ok(Self { connection })
+    }
+
+    fn contains(&self, hash: &str) -> Result<bool, String> {
+        self.connection
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM imported WHERE hash = ?1)",
+                params![hash],
+                |row| row.get(0),
+            )
+            .map_err(|error| error.to_string())
+    }
+
+    fn record(&self, item: &ImportItem) -> Result<(), String> {
+        self.connection
+            .execute(
+                "INSERT OR REPLACE INTO imported (hash, size, source, destination, imported)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    item.hash,
+                    item.size as i64,
+                    item.source.to_string_lossy(),
+                    item.destination.to_string_lossy(),
+                    Local::now().timestamp()
+                ],
+            )
+            .map(|_| ())
+            .map_err(|error| error.to_string())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportItem {
+    source: PathBuf,
+    destination: PathBuf,
+    size: u64,
+    hash: String,
+}
+
+impl ImportItem {
+    pub fn get_source(&self) -> &Path {
+        &self.source
+    }
+
+    pub fn get_destination(&self) -> &Path {
+        &self.destination
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportPlan {
+    volume: PathBuf,
+    root: PathBuf,
+    items: Vec<ImportItem>,
+    already_imported: usize,
+    space_shortages: Vec<SpaceShortage>,
+    errors: Vec<String>,
+}
+
+impl ImportPlan {
+    pub fn get_volume(&self) -> &Path {
+        &self.volume
+    }
+
+    pub fn get_root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn get_items(&self) -> &Vec<ImportItem> {
+        &self.items
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.items.iter().map(|item| item.size).sum()
+    }
+
+    pub fn get_already_imported(&self) -> usize {
+        self.already_imported
+    }
+
+    pub fn get_space_shortages(&self) -> &Vec<SpaceShortage> {
+        &self.space_shortages
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportResult {
+    imported: usize,
+    deleted: usize,
+    errors: Vec<String>,
+}
+
+impl ImportResult {
+    pub fn get_imported(&self) -> usize {
+        self.imported
+    }
+
+    pub fn get_deleted(&self) -> usize {
+        self.deleted
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ImportEvent {
+    Progress(TransferProgress),
+    Finished(ImportResult),
+}
+
+// Memory cards of cameras and phones keep their photos in a DCIM folder
+pub fn is_camera_volume(path: &Path) -> bool {
+    path.join(CAMERA_DIRECTORY).is_dir()
+}
+
+// Lists the files on the card that were never imported, with where the
+// pattern puts them below root. Copies of one photo on the card are only
+// imported once.
+pub fn plan_import(
+    volume: &Path,
+    root: &Path,
+    destination_pattern: &str,
+    entry_filter: &EntryFilter,
+) -> ImportPlan {
+    let mut import_plan = ImportPlan {
+        volume: PathBuf::from(volume),
+        root: PathBuf::from(root),
+        items: Vec::new(),
+        already_imported: 0,
+        space_shortages: Vec::new(),
+        errors: Vec::new(),
+    };
+    match ImportDatabase::open() {
+        Ok(database) => plan_items(
+            &mut import_plan,
+            &database,
+            destination_pattern,
+            entry_filter,
+        ),
+        Err(error) => import_plan
+            .errors
+            .push(format!("Could not open the import history: {}", error)),
+    }
+    import_plan
+}
+
+fn plan_items(
+    import_plan: &mut ImportPlan,
+    database: &ImportDatabase,
+    destination_pattern: &str,
+    entry_filter: &EntryFilter,
+) {
+    let volume = import_plan.volume.clone();
+    let root = import_plan.root.clone();
+    let token_names: HashSet<String> = pattern::get_token_names(destination_pattern)
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let mut files = Vec::new();
+    scan::walk(
+        &volume.join(CAMERA_DIRECTORY),
+        entry_filter,
+        &mut import_plan.errors,
+        |path, metadata| {
+            if metadata.is_file() {
+                files.push((PathBuf::from(path), FileMetadata::read(metadata)));
+            }
+            true
+        },
+    );
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut hashes: HashSet<String> = HashSet::new();
+    let mut planned_destinations: HashSet<PathBuf> = HashSet::new();
+    for (source, metadata) in files {
+        let hash = match index::hash_file(&source) {
+            Ok(hash) => hash,
+            Err(error) => {
+                import_plan
+                    .errors
+                    .push(format!("{}: {}", source.display(), error));
+                continue;
+            }
+        };
+        match database.contains(&hash) {
+            Ok(false) if hashes.insert(hash.clone()) => {}
+            Ok(_) => {
+                import_plan.already_imported += 1;
+                continue;
+            }
+            Err(error) => {
+                import_plan
+                    .errors
+                    .push(format!("{}: {}", source.display(), error));
+                continue;
+            }
+        }
+        let tokens = Tokens::from_file(&source, &metadata, &token_names);
+        let directory = match pattern::expand(destination_pattern, &tokens) {
+            Ok(expanded) if plan::is_relative_inside(Path::new(&expanded)) => root.join(expanded),
+            Ok(expanded) => {
+                import_plan.errors.push(format!(
+                    "Pattern points outside of {}: {}",
+                    root.display(),
+                    expanded
+                ));
+                continue;
+            }
+            Err(error) => {
+                import_plan
+                    .errors
+                    .push(format!("{}: {}", source.display(), error));
+                continue;
+            }
+        };
+        let Some(file_name) = source.file_name() else {
+            continue;
+        };
+        let destination = plan::unique_destination(&directory, file_name, &planned_destinations);
+        planned_destinations.insert(destination.clone());
+        import_plan.items.push(ImportItem {
+            source,
+            destination,
+            size: metadata.get_size(),
+            hash,
+        });
+    }
+
+    let writes: Vec<(&Path, u64)> = import_plan
+        .items
+        .iter()
+        .map(|item| (item.destination.as_path(), item.size))
+        .collect();
+    import_plan.space_shortages = storage::find_space_shortages(&writes);
+}
+
+// Every copy is read back before it counts as imported, and has to be the
+// file that was planned. Originals are only removed from the card once their
+// copy is verified and recorded.
+pub fn start_import(
+    import_plan: ImportPlan,
+    delete_originals: bool,
+) -> UnboundedReceiver<ImportEvent> {
+    let (sender, receiver) = async_mpsc::unbounded();
+    thread::spawn(move || {
+        let mut result = ImportResult::default();
+        let database = match ImportDatabase::open() {
+            Ok(database) => database,
+            Err(error) => {
+                result
+                    .errors
+                    .push(format!("Could not open the import history: {}", error));
+                let _ = sender.unbounded_send(ImportEvent::Finished(result));
+                return;
+            }
+        };
+        let count = import_plan.items.len();
+        let mut throttle = ProgressThrottle::new();
+        for (done, item) in import_plan.items.iter().enumerate() {
+            let mut progress =
+                |copied, size| {
+                    if throttle.is_due() {
+                        let _ = sender.unbounded_send(ImportEvent::Progress(
+                            TransferProgress::build(done, count, &item.source, copied, size),
+                        ));
+                    }
+                };
+            if let Err(error) = import_file(item, &mut progress) {
+                result.errors.push(format!(
+                    "{} -> {}: {}",
+                    item.source.display(),
+                    item.destination.display(),
+                    error
+                ));
+                continue;
+            }
+            if let Err(error) = database.record(item) {
+                result.errors.push(format!(
+                    "{}: Imported but not recorded, kept on the card: {}",
+                    item.source.display(),
+                    error
+                ));
+                continue;
+            }
+            result.imported += 1;
+            if delete_originals {
+                match fs::remove_file(&item.source) {
+                    Ok(()) => result.deleted += 1,
+                    Err(error) => {
+                        result
+                            .errors
+                            .push(format!("{}: {}", item.source.display(), error))
+                    }
+                }
+            }
+        }
+        let _ = sender.unbounded_send(ImportEvent::Finished(result));
+    });
+    receiver
+}
+
+fn import_file(item: &ImportItem, progress: &mut impl FnMut(u64, u64)) -> std::io::Result<()> {
+    if item.destination.exists() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            "Destination already exists",
+        ));
+    }
+    if let Some(parent) = item.destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    transfer::copy_file(&item.source, &item.destination, true, progress)?;
+    // The hash is what is recorded, so a file that changed on the card since
+    // the import was planned is not taken for the planned one
+    if index::hash_file(&item.destination)? != item.hash {
+        fs::remove_file(&item.destination)?;
+        return Err(std::io::Error::other(
+            "Changed since the import was planned",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_plan(directory: &Path, destination_pattern: &str) -> ImportPlan {
+        let mut import_plan = ImportPlan {
+            volume: directory.join("card"),
+            root: directory.join("library"),
+            items: Vec::new(),
+            already_imported: 0,
+            space_shortages: Vec::new(),
+            errors: Vec::new(),
+        };
+        let database = ImportDatabase::open_path(&directory.join(IMPORT_FILE)).unwrap();
+        plan_items(
+            &mut import_plan,
+            &database,
+            destination_pattern,
+            &EntryFilter::new(),
+        );
+        import_plan
+    }
+
+    #[test]
+    fn copies_are_imported_once_to_unique_destinations() {
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-import-plan-{}", std::process::id()));
+        let card = directory.join("card").join(CAMERA_DIRECTORY);
+        fs::create_dir_all(card.join("100CANON")).unwrap();
+        fs::create_dir_all(card.join("101CANON")).unwrap();
+        fs::create_dir_all(directory.join("library/Photos")).unwrap();
+        fs::write(card.join("100CANON/IMG_1.jpg"), "first").unwrap();
+        fs::write(card.join("100CANON/IMG_2.jpg"), "first").unwrap();
+        fs::write(card.join("101CANON/IMG_1.jpg"), "second").unwrap();
+        fs::write(directory.join("library/Photos/IMG_1.jpg"), "older").unwrap();
+
+        let import_plan = build_plan(&directory, "Photos");
+        assert!(
+            import_plan.get_errors().is_empty(),
+            "{:?}",
+            import_plan.get_errors()
+        );
+        assert_eq!(import_plan.get_already_imported(), 1);
+        let items: Vec<(&Path, &Path)> = import_plan
+            .get_items()
+            .iter()
+            .map(|item| (item.get_source(), item.get_destination()))
+            .collect();
+        let photos = directory.join("library/Photos");
+        assert_eq!(
+            items,
+            vec![
+                (
+                    card.join("100CANON/IMG_1.jpg").as_path(),
+                    photos.join("IMG_1 (1).jpg").as_path()
+                ),
+                (
+                    card.join("101CANON/IMG_1.jpg").as_path(),
+                    photos.join("IMG_1 (2).jpg").as_path()
+                ),
+            ]
+        );
+
+        // Recorded files are not planned again
+        let database = ImportDatabase::open_path(&directory.join(IMPORT_FILE)).unwrap();
+        database.record(&import_plan.get_items()[0]).unwrap();
+        let import_plan = build_plan(&directory, "Photos");
+        assert_eq!(import_plan.get_already_imported(), 2);
+        assert_eq!(import_plan.get_items().len(), 1);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn patterns_pointing_outside_are_rejected() {
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-import-outside-{}", std::process::id()));
+        let card = directory.join("card").join(CAMERA_DIRECTORY);
+        fs::create_dir_all(&card).unwrap();
+        fs::write(card.join("IMG_1.jpg"), "").unwrap();
+
+        let import_plan = build_plan(&directory, "../Photos");
+        assert!(import_plan.get_items().is_empty());
+        assert_eq!(import_plan.get_errors().len(), 1);
+        assert!(import_plan.get_errors()[0].starts_with("Pattern points outside"));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn files_changed_since_planning_are_not_imported() {
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-import-changed-{}", std::process::id()));
+        let card = directory.join("card").join(CAMERA_DIRECTORY);
+        fs::create_dir_all(&card).unwrap();
+        fs::write(card.join("IMG_1.jpg"), "planned").unwrap();
+        fs::write(card.join("IMG_2.jpg"), "kept").unwrap();
+
+        let import_plan = build_plan(&directory, "Photos");
+        fs::write(card.join("IMG_1.jpg"), "changed").unwrap();
+        let (changed, kept) = (&import_plan.get_items()[0], &import_plan.get_items()[1]);
+
+        assert!(import_file(changed, &mut |_, _| {}).is_err());
+        assert!(!changed.get_destination().exists());
+        import_file(kept, &mut |_, _| {}).unwrap();
+        assert_eq!(fs::read(kept.get_destination()).unwrap(), b"kept");
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
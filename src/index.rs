@@ -0,0 +1,739 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    fs::{self, Metadata},
+    io,
+    path::{Path, PathBuf, MAIN_SEPARATOR},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Local};
+use rusqlite::{params, Connection, Transaction};
+
+use crate::{
+    file::FileMetadata,
+    filter::{DirectoryFilter, EntryFilter},
+};
+
+const INDEX_DIRECTORY: &str = "fileorder";
+const INDEX_FILE: &str = "index.sqlite";
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const SCHEMA: &str = "
+    PRAGMA journal_mode = WAL;
+    CREATE TABLE IF NOT EXISTS entries (
+        path TEXT PRIMARY KEY,
+        is_dir INTEGER NOT NULL,
+        size INTEGER NOT NULL,
+        modified INTEGER,
+        device INTEGER,
+        inode INTEGER,
+        hash TEXT
+    );
+    CREATE INDEX IF NOT EXISTS entries_size ON entries (size);
+    CREATE TABLE IF NOT EXISTS roots (
+        path TEXT PRIMARY KEY,
+        updated INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS directories (
+        path TEXT PRIMARY KEY,
+        filter TEXT NOT NULL
+    );
+";
+
+// What is compared to tell whether an entry changed since it was indexed
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Stamp {
+    is_dir: bool,
+    size: i64,
+    modified: Option<i64>,
+    device: Option<i64>,
+    inode: Option<i64>,
+}
+
+impl Stamp {
+    fn read(metadata: &Metadata) -> Self {
+        let (device, inode) = get_file_id(metadata);
+        Self {
+            is_dir: metadata.is_dir(),
+            size: metadata.len() as i64,
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_nanos() as i64),
+            device: device.map(|device| device as i64),
+            inode: inode.map(|inode| inode as i64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IndexUpdate {
+    root: PathBuf,
+    added: usize,
+    changed: usize,
+    removed: usize,
+    unchanged: usize,
+    errors: Vec<String>,
+}
+
+impl IndexUpdate {
+    pub fn get_root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn get_added(&self) -> usize {
+        self.added
+    }
+
+    pub fn get_changed(&self) -> usize {
+        self.changed
+    }
+
+    pub fn get_removed(&self) -> usize {
+        self.removed
+    }
+
+    pub fn get_unchanged(&self) -> usize {
+        self.unchanged
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+}
+
+// Files with the same size and content. Hard links to one file are listed once.
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    hash: String,
+    size: u64,
+    paths: Vec<PathBuf>,
+}
+
+impl DuplicateGroup {
+    pub fn get_hash(&self) -> &str {
+        &self.hash
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn get_paths(&self) -> &Vec<PathBuf> {
+        &self.paths
+    }
+
+    pub fn get_wasted_size(&self) -> u64 {
+        self.size * (self.paths.len() as u64 - 1)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DuplicateReport {
+    update: IndexUpdate,
+    groups: Vec<DuplicateGroup>,
+    hashed: usize,
+    errors: Vec<String>,
+}
+
+impl DuplicateReport {
+    pub fn get_update(&self) -> &IndexUpdate {
+        &self.update
+    }
+
+    pub fn get_groups(&self) -> &Vec<DuplicateGroup> {
+        &self.groups
+    }
+
+    pub fn get_hashed(&self) -> usize {
+        self.hashed
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+
+    pub fn get_wasted_size(&self) -> u64 {
+        self.groups
+            .iter()
+            .map(DuplicateGroup::get_wasted_size)
+            .sum()
+    }
+}
+
+// Paths, sizes, modification times, inodes and content hashes of the trees
+// that were indexed, kept in the cache directory between runs. Paths that are
+// not valid UTF-8 are not indexed.
+pub struct Index {
+    connection: Connection,
+}
+
+impl Index {
+    pub fn open() -> Result<Self, String> {
+        let path =
+            get_index_path().ok_or_else(|| String::from("Could not find cache directory"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+        }
+        Self::open_path(&path)
+    }
+
+    // The index, when root was indexed before. Without an index nothing is
+    // created in the cache directory.
+    pub fn open_indexed(root: &Path) -> Option<Self> {
+        let path = get_index_path()?;
+        if !path.is_file() {
+            return None;
+        }
+        let index = Self::open_path(&path).ok()?;
+        index
+            .get_updated(root)
+            .ok()
+            .flatten()
+            .is_some()
+            .then_some(index)
+    }
+
+    pub fn open_path(path: &Path) -> Result<Self, String> {
+        let connection = Connection::open(path).map_err(|error| error.to_string())?;
+        connection
+            .busy_timeout(BUSY_TIMEOUT)
+            .and_then(|_| connection.execute_batch(SCHEMA))
+            .map_err(|error| error.to_string())?;
+        Ok(Self { connection })
+    }
+
+    // Walks root and only writes the entries whose size, modification time or
+    // inode changed. Entries that are gone or now ignored are dropped, and
+    // changed files lose their hash.
+    pub fn update(&mut self, root: &Path, entry_filter: &EntryFilter) -> IndexUpdate {
+        self.update_visiting(root, entry_filter, |_, _| {})
+    }
+
+    // Updates the index like `update` and hands every entry that is still
+    // there to visit, as `scan::walk` does
+    pub fn update_visiting(
+        &mut self,
+        root: &Path,
+        entry_filter: &EntryFilter,
+        mut visit: impl FnMut(&Path, &Metadata),
+    ) -> IndexUpdate {
+        let mut update = IndexUpdate {
+            root: PathBuf::from(root),
+            ..IndexUpdate::default()
+        };
+        let Some(root_key) = root.to_str() else {
+            update
+                .errors
+                .push(format!("{}: Not a valid UTF-8 path", root.display()));
+            return update;
+        };
+        let result = self.connection.transaction().and_then(|transaction| {
+            update_entries(
+                &transaction,
+                root,
+                root_key,
+                entry_filter,
+                &mut update,
+                &mut visit,
+            )?;
+            transaction.commit()
+        });
+        if let Err(error) = result {
+            update.errors.push(error.to_string());
+        }
+        update
+    }
+
+    // When root was last indexed, by itself or as part of a parent
+    pub fn get_updated(&self, root: &Path) -> Result<Option<DateTime<Local>>, String> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT path, updated FROM roots")
+            .map_err(|error| error.to_string())?;
+        let roots = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })
+            .map_err(|error| error.to_string())?;
+        let mut updated = None;
+        for indexed_root in roots {
+            let (path, timestamp) = indexed_root.map_err(|error| error.to_string())?;
+            if root.starts_with(&path) {
+                updated = updated.max(DateTime::from_timestamp(timestamp, 0));
+            }
+        }
+        Ok(updated.map(|updated| updated.with_timezone(&Local)))
+    }
+
+    // Hands every indexed entry under root that entry_filter does not ignore
+    // to visit, like `scan::walk` does with the entries on disk. The walk
+    // stops when visit returns false.
+    pub fn visit(
+        &self,
+        root: &Path,
+        entry_filter: &EntryFilter,
+        mut visit: impl FnMut(&Path, bool, FileMetadata) -> bool,
+    ) -> Result<(), String> {
+        if self.get_updated(root)?.is_none() {
+            return Err(format!(
+                "{} is not indexed yet, update the index first",
+                root.display()
+            ));
+        }
+        let (lower, upper) = get_prefix_range(&root.to_string_lossy());
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT path, is_dir, size, modified FROM entries
+                 WHERE path >= ?1 AND path < ?2 ORDER BY path",
+            )
+            .map_err(|error| error.to_string())?;
+        let mut rows = statement
+            .query(params![lower, upper])
+            .map_err(|error| error.to_string())?;
+        // Entries are sorted by path, so the entries of one directory mostly
+        // follow each other and its filter is kept until the next one
+        let mut directory_filter: Option<(PathBuf, DirectoryFilter)> = None;
+        let mut ignored_directories: HashSet<PathBuf> = HashSet::new();
+        while let Some(row) = rows.next().map_err(|error| error.to_string())? {
+            let read_row = || -> rusqlite::Result<_> {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, bool>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, Option<i64>>(3)?,
+                ))
+            };
+            let (path, is_dir, size, modified) = read_row().map_err(|error| error.to_string())?;
+            let path = Path::new(&path);
+            let Some(parent) = path.parent() else {
+                continue;
+            };
+            if parent
+                .ancestors()
+                .take_while(|ancestor| *ancestor != root)
+                .any(|ancestor| ignored_directories.contains(ancestor))
+            {
+                continue;
+            }
+            if directory_filter
+                .as_ref()
+                .is_none_or(|(directory, _)| directory != parent)
+            {
+                directory_filter =
+                    Some((PathBuf::from(parent), entry_filter.for_directory(parent)));
+            }
+            if let Some((_, filter)) = &directory_filter {
+                if filter.is_ignored(path, is_dir) {
+                    if is_dir {
+                        ignored_directories.insert(PathBuf::from(path));
+                    }
+                    continue;
+                }
+            }
+            let modified = modified
+                .map(|modified| DateTime::from_timestamp_nanos(modified).with_timezone(&Local));
+            let metadata = FileMetadata::build(None, modified, None, size as u64);
+            if !visit(path, is_dir, metadata) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // Only files sharing their size with another file are hashed, and hashes
+    // from earlier runs are reused while the file is unchanged
+    fn find_duplicates(
+        &mut self,
+        root: &Path,
+        report: &mut DuplicateReport,
+    ) -> rusqlite::Result<()> {
+        let (lower, upper) = get_prefix_range(&root.to_string_lossy());
+        let transaction = self.connection.transaction()?;
+        let candidates = {
+            let mut statement = transaction.prepare(
+                "SELECT path, size, device, inode, hash FROM entries
+                 WHERE path >= ?1 AND path < ?2 AND is_dir = 0 AND size > 0 AND size IN (
+                     SELECT size FROM entries
+                     WHERE path >= ?1 AND path < ?2 AND is_dir = 0 AND size > 0
+                     GROUP BY size HAVING COUNT(*) > 1
+                 )
+                 ORDER BY path",
+            )?;
+            let rows = statement.query_map(params![lower, upper], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, Option<i64>>(2)?,
+                    row.get::<_, Option<i64>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                ))
+            })?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+
+        let mut files = HashSet::new();
+        let mut groups: HashMap<(i64, String), Vec<PathBuf>> = HashMap::new();
+        {
+            let mut set_hash =
+                transaction.prepare("UPDATE entries SET hash = ?2 WHERE path = ?1")?;
+            for (path, size, device, inode, hash) in candidates {
+                if let (Some(device), Some(inode)) = (device, inode) {
+                    if !files.insert((device, inode)) {
+                        continue;
+                    }
+                }
+                let hash = match hash {
+                    Some(hash) => hash,
+                    None => match hash_file(Path::new(&path)) {
+                        Ok(hash) => {
+                            set_hash.execute(params![path, hash])?;
+                            report.hashed += 1;
+                            hash
+                        }
+                        Err(error) => {
+                            report.errors.push(format!("{}: {}", path, error));
+                            continue;
+                        }
+                    },
+                };
+                groups
+                    .entry((size, hash))
+                    .or_default()
+                    .push(PathBuf::from(path));
+            }
+        }
+        transaction.commit()?;
+
+        report.groups = groups
+            .into_iter()
+            .filter(|(_, paths)| paths.len() > 1)
+            .map(|((size, hash), paths)| DuplicateGroup {
+                hash,
+                size: size as u64,
+                paths,
+            })
+            .collect();
+        report
+            .groups
+            .sort_by_key(|group| Reverse(group.get_wasted_size()));
+        Ok(())
+    }
+}
+
+pub fn update_index(root: &Path, entry_filter: &EntryFilter) -> IndexUpdate {
+    match Index::open() {
+        Ok(mut index) => index.update(root, entry_filter),
+        Err(error) => IndexUpdate {
+            root: PathBuf::from(root),
+            errors: vec![error],
+            ..IndexUpdate::default()
+        },
+    }
+}
+
+// Brings the index of root up to date before comparing the files in it
+pub fn find_duplicates(root: &Path, entry_filter: &EntryFilter) -> DuplicateReport {
+    let mut report = DuplicateReport::default();
+    let mut index = match Index::open() {
+        Ok(index) => index,
+        Err(error) => {
+            report.errors.push(error);
+            return report;
+        }
+    };
+    report.update = index.update(root, entry_filter);
+    if let Err(error) = index.find_duplicates(root, &mut report) {
+        report.errors.push(error.to_string());
+    }
+    report
+}
+
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(fs::File::open(path)?)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+// Directories whose modification time and inode did not change since they
+// were last read with the same filter still hold the same entries, so the
+// indexed entries are checked again instead of reading the directory
+fn update_entries(
+    transaction: &Transaction,
+    root: &Path,
+    root_key: &str,
+    entry_filter: &EntryFilter,
+    update: &mut IndexUpdate,
+    visit: &mut impl FnMut(&Path, &Metadata),
+) -> rusqlite::Result<()> {
+    let (lower, upper) = get_prefix_range(root_key);
+    let mut known: HashMap<String, Stamp> = {
+        let mut statement = transaction.prepare(
+            "SELECT path, is_dir, size, modified, device, inode FROM entries
+             WHERE path >= ?1 AND path < ?2",
+        )?;
+        let rows = statement.query_map(params![lower, upper], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                Stamp {
+                    is_dir: row.get(1)?,
+                    size: row.get(2)?,
+                    modified: row.get(3)?,
+                    device: row.get(4)?,
+                    inode: row.get(5)?,
+                },
+            ))
+        })?;
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+    let mut filters: HashMap<String, String> = {
+        let mut statement = transaction
+            .prepare("SELECT path, filter FROM directories WHERE path >= ?1 AND path < ?2")?;
+        let rows = statement.query_map(params![lower, upper], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+    let filter_key = entry_filter.get_key();
+    let mut children: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+    for path in known.keys() {
+        let path = PathBuf::from(path);
+        if let Some(parent) = path.parent() {
+            children
+                .entry(PathBuf::from(parent))
+                .or_default()
+                .push(path);
+        }
+    }
+
+    let mut upsert = transaction.prepare(
+        "INSERT INTO entries (path, is_dir, size, modified, device, inode, hash)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, NULL)
+         ON CONFLICT (path) DO UPDATE SET is_dir = excluded.is_dir, size = excluded.size,
+             modified = excluded.modified, device = excluded.device,
+             inode = excluded.inode, hash = NULL",
+    )?;
+    let mut upsert_filter = transaction.prepare(
+        "INSERT INTO directories (path, filter) VALUES (?1, ?2)
+         ON CONFLICT (path) DO UPDATE SET filter = excluded.filter",
+    )?;
+    let mut stack: Vec<(PathBuf, bool)> = vec![(PathBuf::from(root), false)];
+    while let Some((directory, unchanged)) = stack.pop() {
+        let entries: Vec<PathBuf> = if unchanged {
+            children.remove(&directory).unwrap_or_default()
+        } else {
+            match fs::read_dir(&directory) {
+                Ok(entries) => entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .collect(),
+                Err(error) => {
+                    update
+                        .errors
+                        .push(format!("{}: {}", directory.display(), error));
+                    continue;
+                }
+            }
+        };
+        if !unchanged {
+            if let Some(key) = directory.to_str() {
+                upsert_filter.execute(params![key, filter_key])?;
+            }
+        }
+        let directory_filter = entry_filter.for_directory(&directory);
+
+        for path in entries {
+            let Ok(metadata) = fs::symlink_metadata(&path) else {
+                continue;
+            };
+            if !metadata.is_dir() && !metadata.is_file() {
+                continue;
+            }
+            if directory_filter.is_ignored(&path, metadata.is_dir()) {
+                continue;
+            }
+            visit(&path, &metadata);
+            let Some(key) = path.to_str() else {
+                continue;
+            };
+            let stamp = Stamp::read(&metadata);
+            let known_stamp = known.remove(key);
+            if metadata.is_dir() {
+                let same_filter = filters.remove(key).as_ref() == Some(&filter_key);
+                stack.push((path.clone(), same_filter && known_stamp == Some(stamp)));
+            }
+            match known_stamp {
+                Some(known_stamp) if known_stamp == stamp => {
+                    update.unchanged += 1;
+                    continue;
+                }
+                Some(_) => update.changed += 1,
+                None => update.added += 1,
+            }
+            upsert.execute(params![
+                key,
+                stamp.is_dir,
+                stamp.size,
+                stamp.modified,
+                stamp.device,
+                stamp.inode
+            ])?;
+        }
+    }
+
+    let mut delete = transaction.prepare("DELETE FROM entries WHERE path = ?1")?;
+    for path in known.keys() {
+        delete.execute([path])?;
+    }
+    let mut delete_filter = transaction.prepare("DELETE FROM directories WHERE path = ?1")?;
+    for path in filters.keys() {
+        delete_filter.execute([path])?;
+    }
+    update.removed = known.len();
+    transaction.execute(
+        "INSERT INTO roots (path, updated) VALUES (?1, ?2)
+         ON CONFLICT (path) DO UPDATE SET updated = excluded.updated",
+        params![root_key, Local::now().timestamp()],
+    )?;
+    Ok(())
+}
+
+// The bounds of all paths below root, as the separator sorts right before
+// the character that follows it
+fn get_prefix_range(root: &str) -> (String, String) {
+    let mut lower = String::from(root);
+    if !lower.ends_with(MAIN_SEPARATOR) {
+        lower.push(MAIN_SEPARATOR);
+    }
+    let mut upper = String::from(&lower[..lower.len() - 1]);
+    upper.push(char::from(MAIN_SEPARATOR as u8 + 1));
+    (lower, upper)
+}
+
+#[cfg(unix)]
+pub fn get_file_id(metadata: &Metadata) -> (Option<u64>, Option<u64>) {
+    use std::os::unix::fs::MetadataExt;
+    (Some(metadata.dev()), Some(metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub fn get_file_id(_: &Metadata) -> (Option<u64>, Option<u64>) {
+    (None, None)
+}
+
+fn get_index_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|directory| directory.join(INDEX_DIRECTORY).join(INDEX_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_counts(update: &IndexUpdate) -> (usize, usize, usize, usize) {
+        assert!(update.get_errors().is_empty(), "{:?}", update.get_errors());
+        (
+            update.get_added(),
+            update.get_changed(),
+            update.get_removed(),
+            update.get_unchanged(),
+        )
+    }
+
+    #[test]
+    fn update_checks_entries_of_unchanged_directories() {
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-index-{}", std::process::id()));
+        let root = directory.join("tree");
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::create_dir_all(root.join(".hidden")).unwrap();
+        fs::write(root.join("a/one.txt"), "one").unwrap();
+        fs::write(root.join(".hidden/two.txt"), "two").unwrap();
+        let mut index = Index::open_path(&directory.join(INDEX_FILE)).unwrap();
+        let entry_filter = EntryFilter::build(true, &[]).unwrap();
+
+        assert_eq!(
+            get_counts(&index.update(&root, &entry_filter)),
+            (4, 0, 0, 0)
+        );
+        assert_eq!(
+            get_counts(&index.update(&root, &entry_filter)),
+            (0, 0, 0, 4)
+        );
+
+        // Writing a file leaves the modification time of its directory alone
+        fs::write(root.join("a/one.txt"), "changed").unwrap();
+        assert_eq!(
+            get_counts(&index.update(&root, &entry_filter)),
+            (0, 1, 0, 3)
+        );
+
+        fs::write(root.join("a/three.txt"), "three").unwrap();
+        fs::remove_file(root.join(".hidden/two.txt")).unwrap();
+        assert_eq!(
+            get_counts(&index.update(&root, &entry_filter)),
+            (1, 2, 1, 1)
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn update_rereads_directories_read_with_another_filter() {
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-index-filter-{}", std::process::id()));
+        let root = directory.join("tree");
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::write(root.join("a/one.txt"), "").unwrap();
+        fs::write(root.join("a/skip.tmp"), "").unwrap();
+        fs::write(root.join("two.tmp"), "").unwrap();
+        let mut index = Index::open_path(&directory.join(INDEX_FILE)).unwrap();
+        let ignoring = EntryFilter::build(false, &[String::from("*.tmp")]).unwrap();
+        let entry_filter = EntryFilter::build(false, &[]).unwrap();
+
+        assert_eq!(get_counts(&index.update(&root, &ignoring)), (2, 0, 0, 0));
+        // The unchanged directory is read again to find what was ignored
+        assert_eq!(
+            get_counts(&index.update(&root, &entry_filter)),
+            (2, 0, 0, 2)
+        );
+        assert_eq!(
+            get_counts(&index.update(&root, &entry_filter)),
+            (0, 0, 0, 4)
+        );
+        assert_eq!(get_counts(&index.update(&root, &ignoring)), (0, 0, 2, 2));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn visit_skips_ignored_entries_and_their_contents() {
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-index-visit-{}", std::process::id()));
+        let root = directory.join("tree");
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::create_dir_all(root.join(".hidden/inner")).unwrap();
+        fs::write(root.join("a/one.txt"), "").unwrap();
+        fs::write(root.join("a/skip.tmp"), "").unwrap();
+        fs::write(root.join(".hidden/inner/two.txt"), "").unwrap();
+        let mut index = Index::open_path(&directory.join(INDEX_FILE)).unwrap();
+        index.update(&root, &EntryFilter::build(true, &[]).unwrap());
+
+        let mut visited = Vec::new();
+        let entry_filter = EntryFilter::build(false, &[String::from("*.tmp")]).unwrap();
+        index
+            .visit(&root, &entry_filter, |path, _, _| {
+                visited.push(PathBuf::from(path.strip_prefix(&root).unwrap()));
+                true
+            })
+            .unwrap();
+        assert_eq!(
+            visited,
+            vec![PathBuf::from("a"), PathBuf::from("a/one.txt")]
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Error,
+    path::{Path, PathBuf},
+};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    archive,
+    plan::{self, Operation, OperationKind},
+    transfer, util,
+};
+
+const JOURNAL_DIRECTORY: &str = "fileorder";
+const JOURNAL_FILE: &str = "journal.toml";
+const MAX_JOURNAL_ENTRIES: usize = 100;
+
+// Archive members are stored relative to the root of the run, so undoing
+// extracts them back to where they were
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum JournalAction {
+    Move {
+        source: PathBuf,
+        destination: PathBuf,
+    },
+    Archive {
+        archive: PathBuf,
+        members: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    time: String,
+    root: PathBuf,
+    actions: Vec<JournalAction>,
+    #[serde(default)]
+    undone: bool,
+}
+
+impl JournalEntry {
+    pub fn get_time(&self) -> &str {
+        &self.time
+    }
+
+    pub fn get_root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn get_actions(&self) -> &Vec<JournalAction> {
+        &self.actions
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Journal {
+    entries: Vec<JournalEntry>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UndoResult {
+    restored: usize,
+    errors: Vec<String>,
+}
+
+impl UndoResult {
+    pub fn get_restored(&self) -> usize {
+        self.restored
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+}
+
+impl Journal {
+    pub fn load() -> Result<Self, Error> {
+        let Some(path) = get_journal_path() else {
+            return Ok(Self::default());
+        };
+        match fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content).map_err(Error::other),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let Some(path) = get_journal_path() else {
+            return Err(Error::other("Could not find data directory"));
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = toml::to_string_pretty(self).map_err(Error::other)?;
+        util::write_file_atomically(&path, content.as_bytes())
+    }
+}
+
+pub fn record(root: &Path, operations: &[Operation]) -> Result<(), Error> {
+    let mut actions = Vec::new();
+    let mut archives: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    for operation in operations {
+        match operation.get_kind() {
+            OperationKind::Move => actions.push(JournalAction::Move {
+                source: PathBuf::from(operation.get_source()),
+                destination: PathBuf::from(operation.get_destination()),
+            }),
+            OperationKind::Archive => archives
+                .entry(PathBuf::from(operation.get_destination()))
+                .or_default()
+                .push(plan::get_member_path(root, operation.get_source())),
+        }
+    }
+    for (archive, members) in archives {
+        actions.push(JournalAction::Archive { archive, members });
+    }
+
+    let mut journal = Journal::load()?;
+    journal.entries.push(JournalEntry {
+        time: Local::now().to_rfc3339(),
+        root: PathBuf::from(root),
+        actions,
+        undone: false,
+    });
+    let excess = journal.entries.len().saturating_sub(MAX_JOURNAL_ENTRIES);
+    journal.entries.drain(..excess);
+    journal.save()
+}
+
+// The latest run that has not been undone yet, shown before undoing it
+pub fn find_last() -> Result<Option<JournalEntry>, Error> {
+    let journal = Journal::load()?;
+    Ok(journal
+        .entries
+        .into_iter()
+        .rev()
+        .find(|entry| !entry.undone))
+}
+
+// Reverts the latest run that has not been undone yet, newest action first.
+// Nothing is undone unless that is still the run from the given time. Actions
+// that fail stay in the journal, so undoing again retries only those.
+pub fn undo_last(time: &str, verify: bool) -> UndoResult {
+    let mut result = UndoResult::default();
+    let mut journal = match Journal::load() {
+        Ok(journal) => journal,
+        Err(error) => {
+            result
+                .errors
+                .push(format!("Could not read the journal: {}", error));
+            return result;
+        }
+    };
+    let Some(entry) = journal.entries.iter_mut().rev().find(|entry| !entry.undone) else {
+        result.errors.push(String::from("Nothing to undo"));
+        return result;
+    };
+    if entry.time != time {
+        result.errors.push(String::from(
+            "The journal changed since it was shown, nothing was undone",
+        ));
+        return result;
+    }
+
+    let mut failed = Vec::new();
+    for action in entry.actions.iter().rev() {
+        match action {
+            JournalAction::Move {
+                source,
+                destination,
+            } => match undo_move(source, destination, verify) {
+                Ok(()) => result.restored += 1,
+                Err(error) => {
+                    result.errors.push(format!(
+                        "{} -> {}: {}",
+                        destination.display(),
+                        source.display(),
+                        error
+                    ));
+                    failed.push(action.clone());
+                }
+            },
+            JournalAction::Archive { archive, members } => {
+                let extract_result = archive::extract_members(archive, members, &entry.root);
+                result.restored += extract_result.get_extracted();
+                result
+                    .errors
+                    .extend(extract_result.get_errors().iter().cloned());
+                // The archive is only discarded once everything is back
+                if !extract_result.get_errors().is_empty() {
+                    failed.push(action.clone());
+                } else if let Err(error) = trash::delete(archive) {
+                    result
+                        .errors
+                        .push(format!("{}: {}", archive.display(), error));
+                }
+            }
+        }
+    }
+    failed.reverse();
+    entry.undone = failed.is_empty();
+    entry.actions = failed;
+    if let Err(error) = journal.save() {
+        result
+            .errors
+            .push(format!("Could not write the journal: {}", error));
+    }
+    result
+}
+
+fn undo_move(source: &Path, destination: &Path, verify: bool) -> Result<(), Error> {
+    if source.exists() {
+        return Err(Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            "Original location is taken",
+        ));
+    }
+    if let Some(parent) = source.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    transfer::move_file(destination, source, verify, &mut |_, _| {})
+}
+
+fn get_journal_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|directory| directory.join(JOURNAL_DIRECTORY).join(JOURNAL_FILE))
+}
@@ -1,14 +1,58 @@
 use chrono::{DateTime, Datelike, Local, Timelike};
+use std::{
+    cmp::Reverse,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
 
 use iced::{
     alignment::{Horizontal, Vertical},
-    widget::{button, column, container, row, scrollable, text, Column, Container, Row},
-    Element, Theme,
+    widget::{
+        button, checkbox, column, container, image, pane_grid, pick_list, progress_bar, rich_text,
+        row, scrollable, span, text, text_input, Column, Container, PaneGrid, Row, Space,
+    },
+    Color, Element, Font, Theme,
 };
 
+use crate::archive::ArchiveLocation;
+use crate::backup::{BackupActionKind, BackupCompare, DELETED_DIRECTORY};
+use crate::content_search::{self, CaseMode, CONTEXT_LINE_CHOICES};
 use crate::directory::Directory;
+use crate::export::ExportFormat;
 use crate::file::FileMetadata;
-use crate::fileorder::{App, Message};
+use crate::fileorder::{App, Message, ADDRESS_BAR_ID, DIRECTORY_LIST_ID};
+use crate::import;
+use crate::index::IndexUpdate;
+use crate::journal::JournalAction;
+use crate::preview::PreviewMode;
+use crate::retention::PolicyAction;
+use crate::search::SearchField;
+use crate::snapshot::{ChangeKind, SnapshotChoice};
+use crate::storage::SpaceShortage;
+use crate::transfer::TransferProgress;
+use crate::usage::{UsageNode, UsageSort};
+use crate::util;
+
+const THUMBNAIL_CELL_SIZE: f32 = 32.0;
+const THUMBNAIL_PREVIEW_SIZE: f32 = 230.0;
+const LIST_PANE_RATIO: f32 = 0.7;
+const MAX_SHOWN_SEARCH_RESULTS: usize = 1000;
+const MAX_SHOWN_CONTENT_MATCHES: usize = 200;
+const MAX_SHOWN_CHANGES: usize = 1000;
+const MAX_SHOWN_IMPORT_ITEMS: usize = 1000;
+const MAX_SHOWN_BACKUP_ACTIONS: usize = 1000;
+const MAX_SHOWN_OPERATIONS: usize = 1000;
+const MAX_TREEMAP_BLOCKS: usize = 40;
+const TREEMAP_COLORS: [(u8, u8, u8); 8] = [
+    (141, 211, 199),
+    (255, 255, 179),
+    (190, 186, 218),
+    (251, 128, 114),
+    (128, 177, 211),
+    (253, 180, 98),
+    (179, 222, 105),
+    (252, 205, 229),
+];
 
 pub struct FormattedDates {
     pub created: String,
@@ -16,10 +60,36 @@ pub struct FormattedDates {
     pub accessed: String,
 }
 
+#[derive(Debug, Clone, Copy)]
+pub enum BrowserPane {
+    List,
+    Details,
+}
+
+pub fn create_browser_panes() -> pane_grid::State<BrowserPane> {
+    let (mut panes, list) = pane_grid::State::new(BrowserPane::List);
+    if let Some((_, split)) = panes.split(pane_grid::Axis::Vertical, list, BrowserPane::Details) {
+        panes.resize(split, LIST_PANE_RATIO);
+    }
+    panes
+}
+
 #[derive(Debug)]
 pub enum Layout {
     Home,
     Templates,
+    Plan,
+    Undo,
+    TypeReport,
+    Retention,
+    Cleanup,
+    Search,
+    ContentSearch,
+    Duplicates,
+    Snapshots,
+    Usage,
+    Import,
+    Backup,
 }
 
 pub fn home_layout<'a>() -> Element<'a, Message> {
@@ -40,61 +110,1542 @@ pub fn error_layout<'a>(_: &'a App, error_message: &'a str) -> Element<'a, Messa
 }
 
 pub fn file_browser<'a>(app: &'a App) -> Element<'a, Message> {
-    container(
+    let current_directory = find_current_directory(&mut app.get_id_stack().iter(), app.get_root());
+    let archive = current_directory.get_archive();
+    container(row![
+        display_sidebar(app),
         column![
             column![
-                button("Home")
-                    .on_press(Message::HomeLayout)
-                    .style(|theme: &Theme, status| {
-                        let palette = theme.extended_palette();
-                        match status {
-                            button::Status::Active => button::Style::default()
-                                .with_background(palette.secondary.strong.color),
-                            _ => button::primary(theme, status),
-                        }
-                    }),
+                row![
+                    button("Home")
+                        .on_press(Message::HomeLayout)
+                        .style(|theme: &Theme, status| {
+                            let palette = theme.extended_palette();
+                            match status {
+                                button::Status::Active => button::Style::default()
+                                    .with_background(palette.secondary.strong.color),
+                                _ => button::primary(theme, status),
+                            }
+                        }),
+                    display_history_buttons(app),
+                ]
+                .spacing(5),
                 text("Select directory to order").size(25).center(),
                 display_external_storage_devices(app),
-                button(text("..").center().size(15)).on_press(Message::Out),
-                error_text(app)
+                display_address_bar(app),
+                display_breadcrumbs(app),
+                display_current_space(app),
+                row![
+                    button(text("..").center().size(15)).on_press(Message::Out),
+                    text_input("Filter", app.get_filter_text())
+                        .on_input(Message::FilterChanged)
+                        .size(15)
+                        .width(200),
+                    button(text("Search").size(15))
+                        .on_press_maybe(archive.is_none().then_some(Message::SearchLayout)),
+                    button(text("Search contents").size(15))
+                        .on_press_maybe(archive.is_none().then_some(Message::ContentSearchLayout)),
+                    button(text("Find duplicates").size(15))
+                        .on_press_maybe(archive.is_none().then_some(Message::FindDuplicates)),
+                    button(text("Snapshots").size(15))
+                        .on_press_maybe(archive.is_none().then_some(Message::SnapshotsLayout)),
+                    button(text("Disk usage").size(15))
+                        .on_press_maybe(archive.is_none().then_some(Message::UsageLayout)),
+                    button(text("Import photos").size(15)).on_press_maybe(
+                        (archive.is_none() && has_camera_folder(current_directory)).then(|| {
+                            Message::ImportLayout(util::current_path_buf(app.get_current_path()))
+                        })
+                    ),
+                    button(text("Back up").size(15))
+                        .on_press_maybe(archive.is_none().then_some(Message::BackupLayout)),
+                    button(text("Organize this directory").size(15))
+                        .on_press_maybe(archive.is_none().then_some(Message::CreatePlan)),
+                    button(text("Find mismatched types").size(15))
+                        .on_press_maybe(archive.is_none().then_some(Message::CreateTypeReport)),
+                    button(text("Undo last organize").size(15)).on_press(Message::Undo),
+                    button(text("Retention policies").size(15)).on_press(Message::RetentionLayout),
+                    button(text("Remove empty folders").size(15)).on_press_maybe(
+                        archive.is_none().then(|| Message::FindEmptyDirectories(
+                            util::current_path_buf(app.get_current_path())
+                        ))
+                    ),
+                ]
+                .spacing(5),
+                display_archive_actions(app, archive),
+                display_export_actions(app, archive),
+                error_text(app),
+                undo_text(app)
             ]
             .padding(10),
-            scrollable(get_directory_buttons(app).padding(25)).height(500),
+            PaneGrid::new(app.get_panes(), |_, pane, _| {
+                let content: Element<'a, Message> = match pane {
+                    BrowserPane::List => scrollable(get_directory_buttons(app).padding(25))
+                        .id(scrollable::Id::new(DIRECTORY_LIST_ID))
+                        .on_scroll(Message::Scrolled)
+                        .into(),
+                    BrowserPane::Details => display_info_panel(app),
+                };
+                pane_grid::Content::new(content)
+            })
+            .on_resize(10, Message::PaneResized)
+            .spacing(5)
+            .height(500),
         ]
         .spacing(10),
+    ])
+    .center(iced::Fill)
+    .into()
+}
+
+pub fn plan_layout<'a>(app: &'a App) -> Element<'a, Message> {
+    let Some(plan) = app.get_plan() else {
+        return container(text("Scanning...").size(20))
+            .center(iced::Fill)
+            .into();
+    };
+
+    let mut column = Column::new().spacing(10).padding(10);
+    column = column.push(text(format!("Organize {}", plan.get_root().display())).size(25));
+
+    if let Some(refused) = plan.get_refused() {
+        column = column.push(text(refused.clone()).size(15));
+    } else {
+        column = column.push(
+            text(format!(
+                "{} files will be organized",
+                plan.get_operations().len()
+            ))
+            .size(18),
+        );
+    }
+
+    column = column.push(display_space_shortages(plan.get_space_shortages()));
+
+    let mut details = Column::new().spacing(5);
+    for operation in plan.get_operations().iter().take(MAX_SHOWN_OPERATIONS) {
+        details = details.push(
+            row![
+                text(format!("{:?}", operation.get_kind()))
+                    .size(15)
+                    .width(60),
+                text(get_relative_path(plan.get_root(), operation.get_source()))
+                    .size(15)
+                    .width(iced::FillPortion(2)),
+                text(get_relative_path(
+                    plan.get_root(),
+                    operation.get_destination()
+                ))
+                .size(15)
+                .width(iced::FillPortion(2)),
+                text(operation.get_rule())
+                    .size(15)
+                    .width(iced::FillPortion(1)),
+            ]
+            .spacing(5),
+        );
+    }
+    if plan.get_operations().len() > MAX_SHOWN_OPERATIONS {
+        details = details.push(
+            text(format!(
+                "and {} more",
+                plan.get_operations().len() - MAX_SHOWN_OPERATIONS
+            ))
+            .size(15),
+        );
+    }
+    if !plan.get_protected().is_empty() {
+        details = details.push(text("Protected, left untouched").size(18));
+    }
+    for protected in plan.get_protected() {
+        details = details.push(
+            text(format!(
+                "{}: {}",
+                protected.get_path().display(),
+                protected.get_reason()
+            ))
+            .size(15),
+        );
+    }
+    for error in plan.get_errors() {
+        details = details.push(text(error.clone()).size(15));
+    }
+    column = column.push(scrollable(details).height(400));
+
+    if let Some(plan_result) = app.get_plan_result() {
+        column = column.push(
+            text(format!(
+                "Organized {} files",
+                plan_result.get_completed().len()
+            ))
+            .size(18),
+        );
+        for error in plan_result.get_errors() {
+            column = column.push(text(error.clone()).size(15));
+        }
+    } else if let Some(progress) = app.get_plan_progress() {
+        column = column.push(display_transfer_progress(
+            "Organizing",
+            plan.get_root(),
+            progress,
+        ));
+    } else if app.is_applying_plan() {
+        column = column.push(text("Organizing...").size(18));
+    }
+
+    let can_apply = plan.can_apply() && app.get_plan_result().is_none() && !app.is_applying_plan();
+    column = column.push(
+        row![
+            button(text("Apply").size(15)).on_press_maybe(can_apply.then_some(Message::ApplyPlan)),
+            button(text("Undo").size(15)).on_press_maybe(
+                app.get_plan_result()
+                    .as_ref()
+                    .filter(|plan_result| !plan_result.get_completed().is_empty())
+                    .map(|_| Message::Undo)
+            ),
+            button(text("Remove empty folders").size(15)).on_press_maybe(
+                app.get_plan_result()
+                    .as_ref()
+                    .map(|_| Message::FindEmptyDirectories(PathBuf::from(plan.get_root())))
+            ),
+            button(text("Back").size(15)).on_press(Message::ClosePlan),
+            checkbox("Verify copies to other drives", app.get_verify_copies())
+                .on_toggle_maybe((!app.is_applying_plan()).then_some(Message::ToggleVerifyCopies))
+                .size(15),
+        ]
+        .spacing(5)
+        .align_y(Vertical::Center),
+    );
+    column = column.push(export_buttons(app, Message::ExportPlan));
+
+    container(column).center(iced::Fill).into()
+}
+
+pub fn undo_layout<'a>(app: &'a App) -> Element<'a, Message> {
+    let entry = match app.get_undo_preview() {
+        None => {
+            return container(text("Reading the journal...").size(20))
+                .center(iced::Fill)
+                .into();
+        }
+        Some(Err(error)) => {
+            return container(column![
+                text(format!("Could not read the journal: {}", error)).size(18),
+                button(text("Back").size(15)).on_press(Message::CloseUndo),
+            ])
+            .center(iced::Fill)
+            .into();
+        }
+        Some(Ok(None)) => {
+            return container(column![
+                text("Nothing to undo").size(18),
+                button(text("Back").size(15)).on_press(Message::CloseUndo),
+            ])
+            .center(iced::Fill)
+            .into();
+        }
+        Some(Ok(Some(entry))) => entry,
+    };
+
+    let root = entry.get_root();
+    let mut column = Column::new().spacing(10).padding(10);
+    column = column.push(text(format!("Undo organizing {}", root.display())).size(25));
+    let time = DateTime::parse_from_rfc3339(entry.get_time())
+        .map(|time| {
+            time.with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_else(|_| String::from(entry.get_time()));
+    column = column.push(
+        text(format!(
+            "Organized on {}, {} changes will be reverted",
+            time,
+            entry.get_actions().len()
+        ))
+        .size(18),
+    );
+
+    let mut details = Column::new().spacing(5);
+    for action in entry.get_actions() {
+        let (kind, from, to) = match action {
+            JournalAction::Move {
+                source,
+                destination,
+            } => (
+                String::from("Move back"),
+                get_relative_path(root, destination),
+                get_relative_path(root, source),
+            ),
+            JournalAction::Archive { archive, members } => (
+                String::from("Extract"),
+                get_relative_path(root, archive),
+                format!("{} files", members.len()),
+            ),
+        };
+        details = details.push(
+            row![
+                text(kind).size(15).width(90),
+                text(from).size(15).width(iced::FillPortion(1)),
+                text(to).size(15).width(iced::FillPortion(1)),
+            ]
+            .spacing(5),
+        );
+    }
+    column = column.push(scrollable(details).height(400));
+    column = column.push(
+        row![
+            button(text("Undo these changes").size(15)).on_press(Message::ConfirmUndo),
+            button(text("Back").size(15)).on_press(Message::CloseUndo),
+        ]
+        .spacing(5),
+    );
+
+    container(column).center(iced::Fill).into()
+}
+
+pub fn type_report_layout<'a>(app: &'a App) -> Element<'a, Message> {
+    let Some(type_report) = app.get_type_report() else {
+        return container(text("Scanning...").size(20))
+            .center(iced::Fill)
+            .into();
+    };
+
+    let mut rows = Column::new().spacing(5);
+    rows = rows.push(row![
+        text("File").size(15).width(iced::FillPortion(3)),
+        text("Content").size(15).width(iced::FillPortion(1)),
+    ]);
+    for mismatch in type_report {
+        rows = rows.push(row![
+            text(mismatch.get_path().display().to_string())
+                .size(15)
+                .width(iced::FillPortion(3)),
+            text(mismatch.get_content_type().get_mime())
+                .size(15)
+                .width(iced::FillPortion(1)),
+        ]);
+    }
+
+    container(
+        column![
+            text(format!(
+                "{} files with an extension that does not match their content",
+                type_report.len()
+            ))
+            .size(20),
+            scrollable(rows).height(500),
+            button(text("Back").size(15)).on_press(Message::BackToBrowser),
+        ]
+        .spacing(10)
+        .padding(10),
     )
     .center(iced::Fill)
     .into()
 }
 
-fn error_text(app: &App) -> Container<Message> {
+pub fn retention_layout<'a>(app: &'a App) -> Element<'a, Message> {
+    let mut column = Column::new().spacing(10).padding(10);
+    column = column.push(text("Retention policies").size(25));
+    if app.get_policies().is_empty() {
+        column = column.push(text("No retention policies, add them to config.toml").size(15));
+    }
+    let busy = app.is_previewing_retention() || app.is_applying_retention();
+    for (index, policy) in app.get_policies().iter().enumerate() {
+        column = column.push(
+            row![
+                text(policy.get_name()).size(15).width(iced::FillPortion(1)),
+                text(policy.get_path().display().to_string())
+                    .size(15)
+                    .width(iced::FillPortion(2)),
+                text(policy.describe()).size(15).width(iced::FillPortion(2)),
+                button(text("Preview").size(15))
+                    .on_press_maybe((!busy).then_some(Message::PreviewPolicy(index))),
+            ]
+            .spacing(5),
+        );
+    }
+
+    if app.is_previewing_retention() {
+        column = column.push(text("Scanning...").size(18));
+    }
+    if let Some(report) = app.get_retention_report() {
+        let policy = report.get_policy();
+        column = column.push(
+            text(format!(
+                "{}: {} files, {} to remove, {} kept",
+                policy.get_name(),
+                report.get_candidates().len(),
+                util::format_size(report.get_total_size()),
+                report.get_kept()
+            ))
+            .size(18),
+        );
+        let mut details = Column::new().spacing(5);
+        for candidate in report.get_candidates() {
+            details = details.push(
+                row![
+                    text(get_relative_path(policy.get_path(), candidate.get_path()))
+                        .size(15)
+                        .width(iced::FillPortion(3)),
+                    text(util::format_size(candidate.get_size()))
+                        .size(15)
+                        .width(iced::FillPortion(1)),
+                    text(candidate.get_reason())
+                        .size(15)
+                        .width(iced::FillPortion(2)),
+                ]
+                .spacing(5),
+            );
+        }
+        if !report.get_protected().is_empty() {
+            details = details.push(text("Protected, left untouched").size(18));
+        }
+        for protected in report.get_protected() {
+            details = details.push(
+                text(format!(
+                    "{}: {}",
+                    protected.get_path().display(),
+                    protected.get_reason()
+                ))
+                .size(15),
+            );
+        }
+        for error in report.get_errors() {
+            details = details.push(text(error.clone()).size(15));
+        }
+        column = column.push(scrollable(details).height(350));
+
+        if let Some(result) = app.get_retention_result() {
+            column = column.push(
+                text(format!(
+                    "Removed {} files, freed {}",
+                    result.get_removed(),
+                    util::format_size(result.get_freed())
+                ))
+                .size(18),
+            );
+            for error in result.get_errors() {
+                column = column.push(text(error.clone()).size(15));
+            }
+        } else if app.is_applying_retention() {
+            column = column.push(text("Removing...").size(18));
+        }
+
+        let verb = match policy.get_action() {
+            PolicyAction::Trash => "Trash",
+            PolicyAction::Delete => "Delete",
+        };
+        let can_apply =
+            !report.get_candidates().is_empty() && app.get_retention_result().is_none() && !busy;
+        column = column.push(
+            button(text(format!("{} {} files", verb, report.get_candidates().len())).size(15))
+                .on_press_maybe(can_apply.then_some(Message::ApplyRetention)),
+        );
+    }
+    column = column.push(button(text("Back").size(15)).on_press(Message::BackToBrowser));
+
+    container(column).center(iced::Fill).into()
+}
+
+pub fn search_layout<'a>(app: &'a App) -> Element<'a, Message> {
+    let query = app.get_search_query();
+    let field = |placeholder, field| {
+        text_input(placeholder, query.get(field))
+            .on_input(move |value| Message::SearchQueryChanged(field, value))
+            .on_submit(Message::StartSearch)
+            .size(15)
+    };
+
+    let mut column = Column::new().spacing(10).padding(10);
+    column = column.push(
+        text(format!(
+            "Search {}",
+            util::display_path(app.get_current_path())
+        ))
+        .size(25),
+    );
+    column = column.push(
+        row![
+            field("Name, e.g. *.pdf", SearchField::Name).width(iced::FillPortion(3)),
+            checkbox("Regex", query.get_use_regex()).on_toggle(Message::ToggleSearchRegex),
+            checkbox("Use index", query.get_use_index()).on_toggle(Message::ToggleSearchIndex),
+        ]
+        .spacing(5)
+        .align_y(Vertical::Center),
+    );
+    column = column.push(
+        row![
+            field("Min size, e.g. 10MB", SearchField::MinSize),
+            field("Max size", SearchField::MaxSize),
+            field("Modified from YYYY-MM-DD", SearchField::ModifiedFrom),
+            field("Modified to YYYY-MM-DD", SearchField::ModifiedTo),
+        ]
+        .spacing(5),
+    );
+    column = column.push(
+        row![
+            button(text("Search").size(15)).on_press(Message::StartSearch),
+            button(text("Stop").size(15))
+                .on_press_maybe(app.is_searching().then_some(Message::StopSearch)),
+            button(text("Update index").size(15))
+                .on_press_maybe((!app.is_updating_index()).then_some(Message::UpdateIndex)),
+            button(text("Back").size(15)).on_press(Message::BackToBrowser),
+        ]
+        .spacing(5),
+    );
+    if app.is_updating_index() {
+        column = column.push(text("Updating index...").size(15));
+    } else if let Some(index_update) = app.get_index_update() {
+        column = column.push(text(format_index_update(index_update)).size(15));
+        for error in index_update.get_errors().iter().take(5) {
+            column = column.push(text(error.clone()).size(15));
+        }
+    }
+
+    let results = app.get_search_results();
+    let status = if app.is_searching() {
+        format!("Searching... {} found", results.len())
+    } else {
+        format!("{} found", results.len())
+    };
+    column = column.push(text(status).size(18));
+    for error in app.get_search_errors() {
+        column = column.push(text(error.clone()).size(15));
+    }
+
+    let mut rows = Column::new().spacing(5);
+    for result in results.iter().take(MAX_SHOWN_SEARCH_RESULTS) {
+        let metadata = result.get_metadata();
+        let size = if result.is_dir() {
+            String::new()
+        } else {
+            util::format_size(metadata.get_size())
+        };
+        rows = rows.push(
+            button(row![
+                text(get_relative_path(app.get_search_root(), result.get_path()))
+                    .size(15)
+                    .width(iced::FillPortion(4)),
+                text(size).size(15).width(iced::FillPortion(1)),
+                text(get_formatted_metadata(metadata).modified)
+                    .size(15)
+                    .width(iced::FillPortion(1)),
+            ])
+            .style(button::text)
+            .on_press(Message::OpenSearchResult(PathBuf::from(result.get_path()))),
+        );
+    }
+    if results.len() > MAX_SHOWN_SEARCH_RESULTS {
+        rows = rows.push(
+            text(format!(
+                "Showing the first {}, narrow the search to see the rest",
+                MAX_SHOWN_SEARCH_RESULTS
+            ))
+            .size(15),
+        );
+    }
+    column = column.push(scrollable(rows).height(400));
+
+    container(column).center(iced::Fill).into()
+}
+
+pub fn content_search_layout<'a>(app: &'a App) -> Element<'a, Message> {
+    let query = app.get_content_query();
+    let mut column = Column::new().spacing(10).padding(10);
+    column = column.push(
+        text(format!(
+            "Search contents of {}",
+            util::display_path(app.get_current_path())
+        ))
+        .size(25),
+    );
+    column = column.push(
+        row![
+            text_input("Text to find", query.get_pattern())
+                .on_input(Message::ContentPatternChanged)
+                .on_submit(Message::StartContentSearch)
+                .size(15)
+                .width(iced::FillPortion(3)),
+            checkbox("Regex", query.get_use_regex()).on_toggle(Message::ToggleContentRegex),
+            pick_list(
+                CaseMode::ALL,
+                Some(query.get_case_mode()),
+                Message::CaseModeSelected
+            )
+            .text_size(15),
+            text("Context lines").size(15),
+            pick_list(
+                CONTEXT_LINE_CHOICES,
+                Some(query.get_context_lines()),
+                Message::ContextLinesSelected
+            )
+            .text_size(15),
+        ]
+        .spacing(5)
+        .align_y(Vertical::Center),
+    );
+    let searching = app.is_content_searching();
+    column = column.push(
+        row![
+            button(text("Search").size(15)).on_press(Message::StartContentSearch),
+            button(text("Stop").size(15))
+                .on_press_maybe(searching.then_some(Message::StopContentSearch)),
+            button(text("Back").size(15)).on_press(Message::BackToBrowser),
+        ]
+        .spacing(5),
+    );
+
+    let results = app.get_content_results();
+    let status = if searching {
+        format!("Searching... {} files match", results.len())
+    } else {
+        format!(
+            "{} of {} files match",
+            results.len(),
+            app.get_content_searched()
+        )
+    };
+    column = column.push(text(status).size(18));
+    let skipped = app.get_content_skipped();
+    if !skipped.is_empty() {
+        column = column.push(
+            text(format!(
+                "{} files larger than {} were not searched: {}",
+                skipped.len(),
+                util::format_size(content_search::MAX_FILE_SIZE),
+                skipped
+                    .iter()
+                    .take(5)
+                    .map(|path| get_relative_path(app.get_content_root(), path))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+            .size(15),
+        );
+    }
+    for error in app.get_content_errors().iter().take(20) {
+        column = column.push(text(error.clone()).size(15));
+    }
+
+    let mut rows = Column::new().spacing(5);
+    for content_match in results.iter().take(MAX_SHOWN_CONTENT_MATCHES) {
+        rows = rows.push(
+            button(
+                text(format!(
+                    "{} ({} matches)",
+                    get_relative_path(app.get_content_root(), content_match.get_path()),
+                    content_match.get_match_count()
+                ))
+                .size(15),
+            )
+            .style(button::text)
+            .on_press(Message::OpenSearchResult(PathBuf::from(
+                content_match.get_path(),
+            ))),
+        );
+        // Gaps between context groups are marked like grep does
+        let mut previous_number = None;
+        for line in content_match.get_lines() {
+            if previous_number.is_some_and(|number| number + 1 != line.get_number()) {
+                rows = rows.push(text("--").font(Font::MONOSPACE).size(13));
+            }
+            previous_number = Some(line.get_number());
+            let separator = if line.is_match() { ':' } else { '-' };
+            rows = rows.push(
+                text(format!(
+                    "{:>6}{} {}",
+                    line.get_number(),
+                    separator,
+                    line.get_text()
+                ))
+                .font(Font::MONOSPACE)
+                .size(13),
+            );
+        }
+    }
+    if results.len() > MAX_SHOWN_CONTENT_MATCHES {
+        rows = rows.push(
+            text(format!(
+                "Showing the first {} files, all {} can still be organized",
+                MAX_SHOWN_CONTENT_MATCHES,
+                results.len()
+            ))
+            .size(15),
+        );
+    }
+    column = column.push(scrollable(rows).height(350));
+
+    let can_plan = !searching && !results.is_empty();
+    column = column.push(
+        row![
+            button(text("Organize matched files").size(15))
+                .on_press_maybe(can_plan.then_some(Message::OrganizeMatchedFiles)),
+            text_input("Folder, e.g. Matches/{ext}", app.get_bulk_destination())
+                .on_input(Message::BulkDestinationChanged)
+                .size(15)
+                .width(300),
+            button(text("Move matched files").size(15)).on_press_maybe(
+                (can_plan && !app.get_bulk_destination().trim().is_empty())
+                    .then_some(Message::MoveMatchedFiles)
+            ),
+        ]
+        .spacing(5)
+        .align_y(Vertical::Center),
+    );
+
+    container(column).center(iced::Fill).into()
+}
+
+pub fn duplicates_layout<'a>(app: &'a App) -> Element<'a, Message> {
+    let Some(duplicate_report) = app.get_duplicate_report() else {
+        return container(text("Looking for duplicates...").size(20))
+            .center(iced::Fill)
+            .into();
+    };
+    let root = duplicate_report.get_update().get_root();
+
+    let mut column = Column::new().spacing(10).padding(10);
+    column = column.push(text(format!("Duplicates in {}", root.display())).size(25));
+    column = column.push(
+        text(format!(
+            "{} groups of identical files, {} can be freed",
+            duplicate_report.get_groups().len(),
+            util::format_size(duplicate_report.get_wasted_size())
+        ))
+        .size(18),
+    );
+    column = column.push(
+        text(format!(
+            "{}, {} files hashed",
+            format_index_update(duplicate_report.get_update()),
+            duplicate_report.get_hashed()
+        ))
+        .size(15),
+    );
+
+    let mut groups = Column::new().spacing(5);
+    for group in duplicate_report.get_groups() {
+        groups = groups.push(
+            text(format!(
+                "{} files of {} ({})",
+                group.get_paths().len(),
+                util::format_size(group.get_size()),
+                &group.get_hash()[..12]
+            ))
+            .size(18),
+        );
+        for path in group.get_paths() {
+            groups = groups.push(
+                button(text(get_relative_path(root, path)).size(15))
+                    .style(button::text)
+                    .on_press(Message::OpenSearchResult(path.clone())),
+            );
+        }
+    }
+    let errors = duplicate_report
+        .get_update()
+        .get_errors()
+        .iter()
+        .chain(duplicate_report.get_errors());
+    for error in errors {
+        groups = groups.push(text(error.clone()).size(15));
+    }
+    column = column.push(scrollable(groups).height(400));
+    column = column.push(button(text("Back").size(15)).on_press(Message::BackToBrowser));
+
+    container(column).center(iced::Fill).into()
+}
+
+pub fn snapshots_layout<'a>(app: &'a App) -> Element<'a, Message> {
+    let mut column = Column::new().spacing(10).padding(10);
+    column = column.push(text("Snapshots").size(25));
+    column = column.push(
+        row![
+            button(
+                text(format!(
+                    "Save snapshot of {}",
+                    util::display_path(app.get_current_path())
+                ))
+                .size(15)
+            )
+            .on_press_maybe((!app.is_saving_snapshot()).then_some(Message::SaveSnapshot)),
+            checkbox("Hash contents", app.get_hash_snapshot())
+                .on_toggle(Message::ToggleSnapshotHash),
+        ]
+        .spacing(5)
+        .align_y(Vertical::Center),
+    );
+    if app.is_saving_snapshot() {
+        column = column.push(text("Saving snapshot...").size(15));
+    } else if let Some(snapshot_result) = app.get_snapshot_result() {
+        if let Some(info) = snapshot_result.get_info() {
+            column = column.push(
+                text(format!(
+                    "Saved {} files of {}",
+                    info.get_files(),
+                    info.get_root().display()
+                ))
+                .size(15),
+            );
+        }
+        for error in snapshot_result.get_errors().iter().take(20) {
+            column = column.push(text(error.clone()).size(15));
+        }
+    }
+
+    let mut saved = Column::new().spacing(5);
+    for info in app.get_snapshots() {
+        saved = saved.push(
+            row![
+                text(info.get_created().format("%Y-%m-%d %H:%M").to_string())
+                    .size(15)
+                    .width(iced::FillPortion(1)),
+                text(info.get_root().display().to_string())
+                    .size(15)
+                    .width(iced::FillPortion(3)),
+                text(format!(
+                    "{} files{}",
+                    info.get_files(),
+                    if info.is_hashed() { ", hashed" } else { "" }
+                ))
+                .size(15)
+                .width(iced::FillPortion(1)),
+            ]
+            .spacing(5),
+        );
+    }
+    for error in app.get_snapshot_errors() {
+        saved = saved.push(text(error.clone()).size(15));
+    }
+    column = column.push(scrollable(saved).height(120));
+
+    let mut choices = vec![SnapshotChoice::Live];
+    choices.extend(
+        app.get_snapshots()
+            .iter()
+            .cloned()
+            .map(SnapshotChoice::Saved),
+    );
+    let can_compare = app.get_old_snapshot().is_some()
+        && app.get_new_snapshot().is_some()
+        && !app.is_comparing_snapshots();
+    column = column.push(
+        row![
+            text("Compare").size(15),
+            pick_list(
+                choices.clone(),
+                app.get_old_snapshot().clone(),
+                Message::OldSnapshotSelected
+            )
+            .placeholder("Older snapshot")
+            .text_size(15),
+            text("with").size(15),
+            pick_list(
+                choices,
+                app.get_new_snapshot().clone(),
+                Message::NewSnapshotSelected
+            )
+            .placeholder("Newer snapshot")
+            .text_size(15),
+            button(text("Compare").size(15))
+                .on_press_maybe(can_compare.then_some(Message::CompareSnapshots)),
+        ]
+        .spacing(5)
+        .align_y(Vertical::Center),
+    );
+
+    if app.is_comparing_snapshots() {
+        column = column.push(text("Comparing...").size(18));
+    } else if let Some(snapshot_diff) = app.get_snapshot_diff() {
+        let counts: Vec<String> = ChangeKind::ALL
+            .iter()
+            .map(|kind| format!("{} {}", snapshot_diff.get_count(*kind), kind))
+            .collect();
+        column = column.push(
+            text(format!(
+                "{} to {}: {}",
+                snapshot_diff.get_old(),
+                snapshot_diff.get_new(),
+                counts.join(", ")
+            ))
+            .size(18),
+        );
+        let mut changes = Column::new().spacing(5);
+        for error in snapshot_diff.get_errors().iter().take(20) {
+            changes = changes.push(text(error.clone()).size(15));
+        }
+        for change in snapshot_diff.get_changes().iter().take(MAX_SHOWN_CHANGES) {
+            let size = |size: Option<u64>| size.map(util::format_size).unwrap_or_default();
+            let line = match change.get_kind() {
+                ChangeKind::Added => {
+                    format!("+ {} ({})", change.get_path(), size(change.get_new_size()))
+                }
+                ChangeKind::Removed => {
+                    format!("- {} ({})", change.get_path(), size(change.get_old_size()))
+                }
+                ChangeKind::Modified => format!(
+                    "~ {} ({} to {})",
+                    change.get_path(),
+                    size(change.get_old_size()),
+                    size(change.get_new_size())
+                ),
+                ChangeKind::Moved => format!(
+                    "> {} to {}",
+                    change.get_previous_path().unwrap_or_default(),
+                    change.get_path()
+                ),
+            };
+            changes = changes.push(text(line).font(Font::MONOSPACE).size(13));
+        }
+        if snapshot_diff.get_changes().len() > MAX_SHOWN_CHANGES {
+            changes = changes.push(
+                text(format!(
+                    "Showing the first {}, export to see all changes",
+                    MAX_SHOWN_CHANGES
+                ))
+                .size(15),
+            );
+        }
+        column = column.push(scrollable(changes).height(300));
+
+        column = column.push(export_buttons(app, Message::ExportSnapshotDiff));
+    }
+    column = column.push(button(text("Back").size(15)).on_press(Message::BackToBrowser));
+
+    container(column).center(iced::Fill).into()
+}
+
+pub fn usage_layout<'a>(app: &'a App) -> Element<'a, Message> {
+    let (Some(usage), Some(node)) = (app.get_usage(), app.get_usage_node(app.get_usage_path()))
+    else {
+        return container(text("Measuring...").size(20))
+            .center(iced::Fill)
+            .into();
+    };
+
+    let mut column = Column::new().spacing(10).padding(10);
+    column = column.push(text(format!("Disk usage of {}", node.get_path().display())).size(25));
+    column = column.push(
+        text(format!(
+            "{} in {} files",
+            util::format_size(node.get_size()),
+            node.get_files()
+        ))
+        .size(18),
+    );
+    let parent = node
+        .get_path()
+        .parent()
+        .filter(|_| node.get_path() != usage.get_root().get_path());
+    column = column.push(
+        row![
+            button(text("Up").size(15))
+                .on_press_maybe(parent.map(|parent| Message::UsageNavigate(PathBuf::from(parent)))),
+            button(text("Open in browser").size(15)).on_press(Message::BrowseUsage),
+            button(
+                text(if app.is_measuring_usage() {
+                    "Measuring..."
+                } else {
+                    "Measure again"
+                })
+                .size(15)
+            )
+            .on_press_maybe((!app.is_measuring_usage()).then_some(Message::MeasureUsage)),
+        ]
+        .spacing(5),
+    );
+    column = column.push(
+        container(usage_treemap(&get_usage_blocks(node), 0, true))
+            .width(iced::Fill)
+            .height(300),
+    );
+
+    let mut header = Row::new().spacing(5).padding(5);
+    for (usage_sort, portion) in [
+        (UsageSort::Name, 3),
+        (UsageSort::Size, 1),
+        (UsageSort::Files, 1),
+    ] {
+        let marker = match (
+            app.get_usage_sort() == usage_sort,
+            app.is_usage_descending(),
+        ) {
+            (true, true) => " ▼",
+            (true, false) => " ▲",
+            _ => "",
+        };
+        let label = button(text(format!("{}{}", usage_sort, marker)).size(15))
+            .style(button::text)
+            .padding(0)
+            .on_press(Message::SortUsage(usage_sort))
+            .width(iced::FillPortion(portion));
+        header = header.push(label);
+        if usage_sort == UsageSort::Size {
+            header = header.push(text("Share").size(15).width(iced::FillPortion(2)));
+        }
+    }
+    column = column.push(header);
+
+    let mut directories = Column::new().spacing(5);
+    for directory in node.get_sorted_directories(app.get_usage_sort(), app.is_usage_descending()) {
+        directories = directories.push(
+            button(usage_row(
+                directory.get_name().to_string_lossy().to_string(),
+                directory.get_size(),
+                directory.get_files(),
+                node.get_size(),
+            ))
+            .style(button::text)
+            .on_press(Message::UsageNavigate(PathBuf::from(directory.get_path()))),
+        );
+    }
+    if node.get_file_count() > 0 {
+        directories = directories.push(
+            container(usage_row(
+                String::from("(files in this folder)"),
+                node.get_file_size(),
+                node.get_file_count(),
+                node.get_size(),
+            ))
+            .padding(5),
+        );
+    }
+    for error in usage.get_errors() {
+        directories = directories.push(text(error.clone()).size(15));
+    }
+    column = column.push(scrollable(directories).height(300));
+    column = column.push(button(text("Back").size(15)).on_press(Message::BackToBrowser));
+
+    container(column).center(iced::Fill).into()
+}
+
+pub fn import_layout<'a>(app: &'a App) -> Element<'a, Message> {
+    let mut column = Column::new().spacing(10).padding(10);
+    column =
+        column.push(text(format!("Import from {}", app.get_import_volume().display())).size(25));
+    let can_change = !app.is_planning_import() && !app.is_importing();
+    column = column.push(
+        row![
+            text_input("Import to", app.get_import_root())
+                .on_input_maybe(can_change.then_some(Message::ImportRootChanged))
+                .size(15),
+            text_input("Folders", app.get_import_pattern())
+                .on_input_maybe(can_change.then_some(Message::ImportPatternChanged))
+                .size(15),
+            button(text("Look again").size(15))
+                .on_press_maybe(can_change.then_some(Message::PlanImport)),
+        ]
+        .spacing(5),
+    );
+
+    let Some(import_plan) = app.get_import_plan() else {
+        column = column.push(text("Looking for new files...").size(18));
+        column = column.push(button(text("Back").size(15)).on_press(Message::BackToBrowser));
+        return container(column).center(iced::Fill).into();
+    };
+    column = column.push(
+        text(format!(
+            "{} new files, {}, {} imported before",
+            import_plan.get_items().len(),
+            util::format_size(import_plan.get_size()),
+            import_plan.get_already_imported()
+        ))
+        .size(18),
+    );
+    column = column.push(display_space_shortages(import_plan.get_space_shortages()));
+
+    let mut items = Column::new().spacing(5);
+    for item in import_plan.get_items().iter().take(MAX_SHOWN_IMPORT_ITEMS) {
+        items = items.push(
+            row![
+                text(get_relative_path(
+                    import_plan.get_volume(),
+                    item.get_source()
+                ))
+                .size(15)
+                .width(iced::FillPortion(1)),
+                text(get_relative_path(
+                    import_plan.get_root(),
+                    item.get_destination()
+                ))
+                .size(15)
+                .width(iced::FillPortion(1)),
+            ]
+            .spacing(5),
+        );
+    }
+    if import_plan.get_items().len() > MAX_SHOWN_IMPORT_ITEMS {
+        items = items.push(
+            text(format!(
+                "and {} more",
+                import_plan.get_items().len() - MAX_SHOWN_IMPORT_ITEMS
+            ))
+            .size(15),
+        );
+    }
+    for error in import_plan.get_errors() {
+        items = items.push(text(error.clone()).size(15));
+    }
+    column = column.push(scrollable(items).height(300));
+
+    if let Some(import_result) = app.get_import_result() {
+        let mut summary = format!("Imported {} files", import_result.get_imported());
+        if app.get_delete_after_import() {
+            summary.push_str(&format!(
+                ", {} removed from the card",
+                import_result.get_deleted()
+            ));
+        }
+        column = column.push(text(summary).size(18));
+        for error in import_result.get_errors() {
+            column = column.push(text(error.clone()).size(15));
+        }
+    } else if let Some(progress) = app.get_import_progress() {
+        column = column.push(display_transfer_progress(
+            "Importing",
+            import_plan.get_volume(),
+            progress,
+        ));
+    } else if app.is_importing() {
+        column = column.push(text("Importing...").size(18));
+    }
+
+    let can_import = !import_plan.get_items().is_empty()
+        && app.get_import_result().is_none()
+        && !app.is_importing();
+    column = column.push(
+        row![
+            button(text(format!("Import {} files", import_plan.get_items().len())).size(15))
+                .on_press_maybe(can_import.then_some(Message::StartImport)),
+            checkbox(
+                "Remove from the card once copied",
+                app.get_delete_after_import()
+            )
+            .on_toggle_maybe((!app.is_importing()).then_some(Message::ToggleDeleteAfterImport))
+            .size(15),
+            button(text("Back").size(15)).on_press(Message::BackToBrowser),
+        ]
+        .spacing(5)
+        .align_y(Vertical::Center),
+    );
+
+    container(column).center(iced::Fill).into()
+}
+
+pub fn backup_layout<'a>(app: &'a App) -> Element<'a, Message> {
+    let mut column = Column::new().spacing(10).padding(10);
+    column = column.push(text(format!("Back up {}", app.get_backup_source().display())).size(25));
+    let can_change = !app.is_planning_backup() && !app.is_backing_up();
+    let selected_device = app
+        .get_backup_devices()
+        .iter()
+        .find(|device| Path::new(app.get_backup_target()).starts_with(device))
+        .cloned();
+    column = column.push(
+        row![
+            pick_list(
+                app.get_backup_devices().clone(),
+                selected_device,
+                Message::BackupDeviceSelected
+            )
+            .placeholder("Device")
+            .text_size(15),
+            text_input("Back up to", app.get_backup_target())
+                .on_input_maybe(can_change.then_some(Message::BackupTargetChanged))
+                .size(15),
+        ]
+        .spacing(5),
+    );
+    column = column.push(
+        row![
+            pick_list(
+                BackupCompare::ALL,
+                Some(app.get_backup_compare()),
+                Message::BackupCompareSelected
+            )
+            .text_size(15),
+            checkbox(
+                format!(
+                    "Move files removed here into {} in the backup",
+                    DELETED_DIRECTORY
+                ),
+                app.get_keep_removed_in_backup()
+            )
+            .on_toggle_maybe(can_change.then_some(Message::ToggleKeepRemovedInBackup))
+            .size(15),
+            button(text("Preview").size(15)).on_press_maybe(
+                (can_change && !app.get_backup_target().trim().is_empty())
+                    .then_some(Message::PlanBackup)
+            ),
+        ]
+        .spacing(5)
+        .align_y(Vertical::Center),
+    );
+
+    let Some(backup_plan) = app.get_backup_plan() else {
+        if app.is_planning_backup() {
+            column = column.push(text("Comparing...").size(18));
+        }
+        column = column.push(button(text("Back").size(15)).on_press(Message::BackToBrowser));
+        return container(column).center(iced::Fill).into();
+    };
+    let mut summary: Vec<String> = BackupActionKind::ALL
+        .iter()
+        .map(|kind| {
+            format!(
+                "{} {}, {}",
+                backup_plan.get_count(*kind),
+                kind.to_string().to_lowercase(),
+                util::format_size(backup_plan.get_size(*kind))
+            )
+        })
+        .collect();
+    summary.push(format!("{} unchanged", backup_plan.get_unchanged()));
+    if backup_plan.get_kept() > 0 {
+        summary.push(format!("{} only in the backup", backup_plan.get_kept()));
+    }
+    column = column.push(text(summary.join(" / ")).size(18));
+    column = column.push(display_space_shortages(backup_plan.get_space_shortages()));
+
+    let mut actions = Column::new().spacing(5);
+    for action in backup_plan
+        .get_actions()
+        .iter()
+        .take(MAX_SHOWN_BACKUP_ACTIONS)
+    {
+        actions = actions.push(
+            row![
+                text(action.get_kind().to_string()).size(15).width(80),
+                text(action.get_path().display().to_string()).size(15),
+            ]
+            .spacing(5),
+        );
+    }
+    if backup_plan.get_actions().len() > MAX_SHOWN_BACKUP_ACTIONS {
+        actions = actions.push(
+            text(format!(
+                "and {} more",
+                backup_plan.get_actions().len() - MAX_SHOWN_BACKUP_ACTIONS
+            ))
+            .size(15),
+        );
+    }
+    for error in backup_plan.get_errors() {
+        actions = actions.push(text(error.clone()).size(15));
+    }
+    column = column.push(scrollable(actions).height(300));
+
+    if let Some(backup_result) = app.get_backup_result() {
+        column = column.push(
+            text(format!(
+                "Copied {} files, moved {} into {}",
+                backup_result.get_copied(),
+                backup_result.get_removed(),
+                DELETED_DIRECTORY
+            ))
+            .size(18),
+        );
+        for error in backup_result.get_errors() {
+            column = column.push(text(error.clone()).size(15));
+        }
+    } else if let Some(progress) = app.get_backup_progress() {
+        column = column.push(display_transfer_progress(
+            "Backing up",
+            backup_plan.get_source(),
+            progress,
+        ));
+    } else if app.is_backing_up() {
+        column = column.push(text("Backing up...").size(18));
+    }
+
+    let can_back_up = !backup_plan.get_actions().is_empty()
+        && backup_plan.get_errors().is_empty()
+        && app.get_backup_result().is_none()
+        && !app.is_backing_up();
+    column = column.push(
+        row![
+            button(text(format!("Back up to {}", backup_plan.get_target().display())).size(15))
+                .on_press_maybe(can_back_up.then_some(Message::StartBackup)),
+            button(text("Back").size(15)).on_press(Message::BackToBrowser),
+        ]
+        .spacing(5),
+    );
+
+    container(column).center(iced::Fill).into()
+}
+
+pub fn cleanup_layout<'a>(app: &'a App) -> Element<'a, Message> {
+    let Some(cleanup_plan) = app.get_cleanup_plan() else {
+        return container(text("Scanning...").size(20))
+            .center(iced::Fill)
+            .into();
+    };
+    let root = cleanup_plan.get_root();
+
+    let mut column = Column::new().spacing(10).padding(10);
+    column = column.push(text(format!("Empty folders in {}", root.display())).size(25));
+    column = column.push(
+        text(format!(
+            "{} folders will be removed",
+            cleanup_plan.get_directories().len()
+        ))
+        .size(18),
+    );
+
+    let mut details = Column::new().spacing(5);
+    for directory in cleanup_plan.get_directories() {
+        let junk: Vec<String> = directory
+            .get_junk()
+            .iter()
+            .filter_map(|path| path.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .collect();
+        details = details.push(
+            row![
+                text(get_relative_path(root, directory.get_path()))
+                    .size(15)
+                    .width(iced::FillPortion(3)),
+                text(junk.join(", ")).size(15).width(iced::FillPortion(1)),
+            ]
+            .spacing(5),
+        );
+    }
+    if !cleanup_plan.get_protected().is_empty() {
+        details = details.push(text("Protected, left untouched").size(18));
+    }
+    for protected in cleanup_plan.get_protected() {
+        details = details.push(
+            text(format!(
+                "{}: {}",
+                protected.get_path().display(),
+                protected.get_reason()
+            ))
+            .size(15),
+        );
+    }
+    for error in cleanup_plan.get_errors() {
+        details = details.push(text(error.clone()).size(15));
+    }
+    column = column.push(scrollable(details).height(400));
+
+    if let Some(cleanup_result) = app.get_cleanup_result() {
+        column =
+            column.push(text(format!("Removed {} folders", cleanup_result.get_removed())).size(18));
+        for error in cleanup_result.get_errors() {
+            column = column.push(text(error.clone()).size(15));
+        }
+    } else if app.is_removing_empty_directories() {
+        column = column.push(text("Removing...").size(18));
+    }
+
+    let can_remove = !cleanup_plan.get_directories().is_empty()
+        && app.get_cleanup_result().is_none()
+        && !app.is_removing_empty_directories();
+    column = column.push(
+        row![
+            button(text("Remove").size(15))
+                .on_press_maybe(can_remove.then_some(Message::RemoveEmptyDirectories)),
+            button(text("Back").size(15)).on_press(Message::BackToBrowser),
+        ]
+        .spacing(5),
+    );
+
+    container(column).center(iced::Fill).into()
+}
+
+fn format_index_update(index_update: &IndexUpdate) -> String {
+    format!(
+        "Index of {}: {} added, {} changed, {} removed, {} unchanged",
+        index_update.get_root().display(),
+        index_update.get_added(),
+        index_update.get_changed(),
+        index_update.get_removed(),
+        index_update.get_unchanged()
+    )
+}
+
+fn usage_row<'a>(name: String, size: u64, files: usize, total: u64) -> Row<'a, Message> {
+    let share = if total == 0 {
+        0.0
+    } else {
+        size as f32 / total as f32 * 100.0
+    };
+    row![
+        text(name).size(15).width(iced::FillPortion(3)),
+        text(util::format_size(size))
+            .size(15)
+            .width(iced::FillPortion(1)),
+        progress_bar(0.0..=100.0, share)
+            .height(12)
+            .width(iced::FillPortion(1)),
+        text(format!("{:.1}%", share))
+            .size(15)
+            .width(iced::FillPortion(1)),
+        text(files.to_string()).size(15).width(iced::FillPortion(1)),
+    ]
+    .spacing(5)
+    .align_y(Vertical::Center)
+}
+
+struct UsageBlock<'a> {
+    label: String,
+    size: u64,
+    path: Option<&'a Path>,
+}
+
+// The largest directories get their own block, the rest are lumped together
+fn get_usage_blocks(node: &UsageNode) -> Vec<UsageBlock<'_>> {
+    let mut blocks: Vec<UsageBlock> = node
+        .get_directories()
+        .iter()
+        .filter(|directory| directory.get_size() > 0)
+        .take(MAX_TREEMAP_BLOCKS)
+        .map(|directory| UsageBlock {
+            label: directory.get_name().to_string_lossy().to_string(),
+            size: directory.get_size(),
+            path: Some(directory.get_path()),
+        })
+        .collect();
+    let other: u64 = node
+        .get_directories()
+        .iter()
+        .skip(MAX_TREEMAP_BLOCKS)
+        .map(UsageNode::get_size)
+        .sum();
+    for (label, size) in [("Files", node.get_file_size()), ("Other", other)] {
+        if size > 0 {
+            blocks.push(UsageBlock {
+                label: String::from(label),
+                size,
+                path: None,
+            });
+        }
+    }
+    blocks.sort_by_key(|block| Reverse(block.size));
+    blocks
+}
+
+// Splits the blocks in two halves of about the same size, side by side, and
+// lays out each half the same way across the other direction
+fn usage_treemap<'a>(
+    blocks: &[UsageBlock<'a>],
+    offset: usize,
+    horizontal: bool,
+) -> Element<'a, Message> {
+    let total: u64 = blocks.iter().map(|block| block.size).sum();
+    if total == 0 {
+        return Space::new(iced::Fill, iced::Fill).into();
+    }
+    if let [block] = blocks {
+        let (r, g, b) = TREEMAP_COLORS[offset % TREEMAP_COLORS.len()];
+        return button(
+            text(format!(
+                "{}\n{}",
+                block.label,
+                util::format_size(block.size)
+            ))
+            .size(12)
+            .color(Color::BLACK),
+        )
+        .width(iced::Fill)
+        .height(iced::Fill)
+        .padding(4)
+        .clip(true)
+        .style(move |_, _| button::Style::default().with_background(Color::from_rgb8(r, g, b)))
+        .on_press_maybe(
+            block
+                .path
+                .map(|path| Message::UsageNavigate(PathBuf::from(path))),
+        )
+        .into();
+    }
+
+    let mut split = 1;
+    let mut first_size = blocks[0].size;
+    while split < blocks.len() - 1 && first_size * 2 < total {
+        first_size += blocks[split].size;
+        split += 1;
+    }
+    let first_portion = ((first_size as f64 / total as f64 * 1000.0).round() as u16).clamp(1, 999);
+    let first = usage_treemap(&blocks[..split], offset, !horizontal);
+    let second = usage_treemap(&blocks[split..], offset + split, !horizontal);
+    if horizontal {
+        row![
+            container(first).width(iced::FillPortion(first_portion)),
+            container(second).width(iced::FillPortion(1000 - first_portion)),
+        ]
+        .spacing(1)
+        .into()
+    } else {
+        column![
+            container(first).height(iced::FillPortion(first_portion)),
+            container(second).height(iced::FillPortion(1000 - first_portion)),
+        ]
+        .spacing(1)
+        .into()
+    }
+}
+
+fn get_relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+fn error_text(app: &App) -> Container<'_, Message> {
     if let Some(error) = app.get_error() {
         return container(text(error.to_string().clone()));
     }
     container(text(""))
 }
 
-fn get_directory_buttons(app: &App) -> Container<Message> {
+fn undo_text(app: &App) -> Container<'_, Message> {
+    let Some(undo_result) = app.get_undo_result() else {
+        return container(text(""));
+    };
+    let mut status = format!("Restored {} files", undo_result.get_restored());
+    if let Some(error) = undo_result.get_errors().first() {
+        status.push_str(&format!(
+            ", {} errors: {}",
+            undo_result.get_errors().len(),
+            error
+        ));
+    }
+    container(text(status).size(15))
+}
+
+fn get_directory_buttons(app: &App) -> Container<'_, Message> {
     let mut column = Column::new();
     let mut root = app.get_root();
     let mut position = app.get_id_stack().iter();
 
     // Select current directory to display
     root = find_current_directory(&mut position, root);
-    column = display_head(column);
-    column = display_directories(column, root);
-    column = display_files(column, root);
+    if root.is_unread_archive() && app.get_reading_archive().is_some() {
+        return Container::new(text("Reading archive...").size(15));
+    }
+    column = display_head(column, app.get_show_thumbnails());
+    column = display_directories(column, root, app);
+    column = display_files(column, root, app);
     column = column.spacing(5);
 
-    let container = Container::new(column);
-    container
+    Container::new(column)
+}
+
+// A card mounted anywhere, such as below /media, is found by its DCIM folder
+fn has_camera_folder(directory: &Directory) -> bool {
+    directory
+        .get_directories()
+        .iter()
+        .any(|subdirectory| subdirectory.get_name() == import::CAMERA_DIRECTORY)
 }
 
 fn find_current_directory<'a>(
     position: &mut impl Iterator<Item = &'a usize>,
     mut root: &'a Directory,
 ) -> &'a Directory {
-    while let Some(id) = position.next() {
+    for id in position.by_ref() {
         for directory in root.get_directories() {
             if *id == directory.get_directory_id() {
                 root = directory;
@@ -132,30 +1683,411 @@ fn get_formatted_metadata(metadata: &FileMetadata) -> FormattedDates {
     if let Some(accessed) = metadata.get_accessed() {
         formatted_accessed_date = format_datetime(accessed);
     }
-    let formatted_dates = FormattedDates {
+    FormattedDates {
         created: formatted_creation_date,
         modified: formatted_modified_date,
         accessed: formatted_accessed_date,
-    };
-    formatted_dates
+    }
 }
 
 fn display_external_storage_devices<'a>(app: &'a App) -> Row<'a, Message> {
     let mut row = Row::new();
     for storage_device in app.get_external_storage_devices() {
         if let Some(name) = storage_device.get_name().to_str() {
-            row = row
-                .push(button(name).on_press(Message::InExternal(storage_device.get_directory_id())))
+            let id = storage_device.get_directory_id();
+            let mut label = Column::new().push(text(name));
+            if let Some(space) = app.get_device_space(id) {
+                label = label
+                    .push(text(space.to_string()).size(12))
+                    .push(
+                        progress_bar(0.0..=1.0, space.get_used_fraction())
+                            .height(4)
+                            .width(iced::Fill),
+                    )
+                    .spacing(2)
+                    .width(180);
+            }
+            row = row.push(button(label).on_press(Message::InExternal(id)));
+            if let Some(volume) = app.get_camera_volume(id) {
+                row = row.push(
+                    button(text("Import").size(15)).on_press(Message::ImportLayout(volume.clone())),
+                );
+            }
         }
     }
     row = row.spacing(5);
     row
 }
 
-fn display_head<'a>(mut column: Column<'a, Message>) -> Column<'a, Message> {
+fn display_sidebar<'a>(app: &'a App) -> Container<'a, Message> {
+    let mut column = Column::new();
+    let config = app.get_config();
+
+    column = column.push(text("Bookmarks").size(18));
+    let bookmarks = config.get_bookmarks();
+    for (index, bookmark) in bookmarks.iter().enumerate() {
+        column = column.push(
+            row![
+                sidebar_button(get_path_label(bookmark), bookmark),
+                button(text("↑").size(12)).on_press_maybe(
+                    (index > 0).then_some(Message::MoveBookmark(index, index.saturating_sub(1)))
+                ),
+                button(text("↓").size(12)).on_press_maybe(
+                    (index + 1 < bookmarks.len())
+                        .then_some(Message::MoveBookmark(index, index + 1))
+                ),
+                button(text("x").size(12)).on_press(Message::RemoveBookmark(index)),
+            ]
+            .spacing(2),
+        );
+    }
+
+    column = column.push(text("Places").size(18));
+    for (name, path) in app.get_user_directories() {
+        column = column.push(sidebar_button(name.clone(), path));
+    }
+
+    column = column.push(text("Recent").size(18));
+    for location in config.get_recent_locations() {
+        column = column.push(sidebar_button(get_path_label(location), location));
+    }
+
+    container(scrollable(column.spacing(5).padding(10))).width(250)
+}
+
+fn sidebar_button<'a>(label: String, path: &Path) -> Element<'a, Message> {
+    button(text(label).size(15))
+        .style(button::text)
+        .width(iced::Fill)
+        .on_press(Message::NavigateTo(OsString::from(path)))
+        .into()
+}
+
+fn get_path_label(path: &Path) -> String {
+    match path.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => path.display().to_string(),
+    }
+}
+
+fn display_history_buttons<'a>(app: &'a App) -> Row<'a, Message> {
+    let history = app.get_history();
+    row![
+        button(text("<").size(15)).on_press_maybe(history.can_go_back().then_some(Message::Back)),
+        button(text(">").size(15))
+            .on_press_maybe(history.can_go_forward().then_some(Message::Forward)),
+    ]
+    .spacing(5)
+}
+
+fn display_address_bar<'a>(app: &'a App) -> Row<'a, Message> {
+    row![
+        text_input("Path", app.get_address())
+            .id(text_input::Id::new(ADDRESS_BAR_ID))
+            .on_input(Message::AddressChanged)
+            .on_submit(Message::AddressSubmitted)
+            .size(15),
+        button(text("Go").size(15)).on_press(Message::AddressSubmitted),
+        button(text("Bookmark").size(15)).on_press(Message::AddBookmark),
+        button(
+            text(if app.get_entry_filter().get_show_hidden() {
+                "Hide hidden files"
+            } else {
+                "Show hidden files"
+            })
+            .size(15)
+        )
+        .on_press(Message::ToggleHidden),
+        button(
+            text(if app.get_show_thumbnails() {
+                "Hide thumbnails"
+            } else {
+                "Show thumbnails"
+            })
+            .size(15)
+        )
+        .on_press(Message::ToggleThumbnails),
+    ]
+    .spacing(5)
+}
+
+fn display_breadcrumbs<'a>(app: &'a App) -> Row<'a, Message> {
+    let mut row = Row::new();
+    for (index, (name, path)) in util::get_path_breadcrumbs(app.get_current_path())
+        .into_iter()
+        .enumerate()
+    {
+        if index > 1 {
+            row = row.push(text("/").size(15));
+        }
+        row = row.push(
+            button(text(name).size(15))
+                .style(button::text)
+                .on_press(Message::NavigateTo(path)),
+        );
+    }
+    row = row.spacing(2).align_y(Vertical::Center);
+    row
+}
+
+fn display_transfer_progress<'a>(
+    action: &str,
+    root: &Path,
+    progress: &TransferProgress,
+) -> Column<'a, Message> {
+    let mut column = Column::new().spacing(5).push(
+        text(format!(
+            "{} {} of {}: {}",
+            action,
+            progress.get_done() + 1,
+            progress.get_count(),
+            get_relative_path(root, progress.get_path())
+        ))
+        .size(18),
+    );
+    // Renames within one drive have no bytes to report
+    if progress.get_size() > 0 {
+        column = column.push(
+            row![
+                progress_bar(
+                    0.0..=progress.get_size() as f32,
+                    progress.get_copied() as f32
+                )
+                .height(12),
+                text(format!(
+                    "{} of {}",
+                    util::format_size(progress.get_copied()),
+                    util::format_size(progress.get_size())
+                ))
+                .size(15),
+            ]
+            .spacing(5)
+            .align_y(Vertical::Center),
+        );
+    }
+    column
+}
+
+fn display_space_shortages<'a>(shortages: &[SpaceShortage]) -> Column<'a, Message> {
+    let mut column = Column::new().spacing(5);
+    for shortage in shortages {
+        let space = shortage.get_info();
+        let warning = if space.is_read_only() {
+            format!("{} is read-only", space.get_path().display())
+        } else {
+            format!(
+                "Not enough space on {}: {} needed, {} available",
+                space.get_path().display(),
+                util::format_size(shortage.get_needed()),
+                util::format_size(space.get_available())
+            )
+        };
+        column = column.push(text(warning).size(15).color(Color::from_rgb8(200, 0, 0)));
+    }
+    column
+}
+
+fn display_current_space<'a>(app: &'a App) -> Element<'a, Message> {
+    let Some(space) = app.get_current_space() else {
+        return Column::new().into();
+    };
+    let mount_point = space
+        .get_mount_point()
+        .as_ref()
+        .map(|mount_point| format!("{}: ", mount_point.display()))
+        .unwrap_or_default();
+    text(format!(
+        "{}{} used, {}",
+        mount_point,
+        util::format_size(space.get_used()),
+        space
+    ))
+    .size(15)
+    .into()
+}
+
+fn display_export_actions<'a>(
+    app: &'a App,
+    archive: &'a Option<ArchiveLocation>,
+) -> Element<'a, Message> {
+    if archive.is_some() {
+        return Column::new().into();
+    }
+    row![
+        text("Export listing").size(15),
+        checkbox("All subfolders", app.get_export_recursive())
+            .on_toggle(Message::ToggleExportRecursive)
+            .size(15),
+        checkbox("Hashes", app.get_export_hashes())
+            .on_toggle(Message::ToggleExportHashes)
+            .size(15),
+        export_buttons(app, Message::ExportListing),
+    ]
+    .spacing(5)
+    .align_y(Vertical::Center)
+    .into()
+}
+
+fn export_buttons<'a>(
+    app: &'a App,
+    on_export: impl Fn(ExportFormat) -> Message,
+) -> Element<'a, Message> {
+    let mut buttons = Row::new().spacing(5).align_y(Vertical::Center);
+    for format in ExportFormat::ALL {
+        buttons = buttons.push(
+            button(text(format!("Export {}", format)).size(15))
+                .on_press_maybe((!app.is_exporting()).then(|| on_export(format))),
+        );
+    }
+    let status = if app.is_exporting() {
+        String::from("Exporting...")
+    } else {
+        match app.get_export_result() {
+            Some(Ok(path)) => format!("Exported to {}", path.display()),
+            Some(Err(error)) => error.clone(),
+            None => String::new(),
+        }
+    };
+    buttons.push(text(status).size(15)).into()
+}
+
+fn display_archive_actions<'a>(
+    app: &'a App,
+    archive: &'a Option<ArchiveLocation>,
+) -> Element<'a, Message> {
+    let Some(location) = archive else {
+        return Column::new().into();
+    };
+    let destination = app.get_extract_destination().display().to_string();
+    let mut actions = row![
+        text_input(&destination, app.get_extract_destination_input())
+            .on_input(Message::ExtractDestinationChanged)
+            .size(15),
+        button(text("Extract selected").size(15)).on_press_maybe(
+            app.get_selected()
+                .clone()
+                .filter(|_| !app.is_extracting())
+                .map(|selected| Message::Extract(Some(selected)))
+        ),
+    ]
+    .spacing(5);
+    let extract_all = if location.is_archive_root() {
+        "Extract archive"
+    } else {
+        "Extract this folder"
+    };
+    actions = actions.push(
+        button(text(extract_all).size(15))
+            .on_press_maybe((!app.is_extracting()).then_some(Message::Extract(None))),
+    );
+
+    let status = match app.get_extract_result() {
+        _ if app.is_extracting() => String::from("Extracting..."),
+        Some(extract_result) => {
+            let mut status = format!("Extracted {} files", extract_result.get_extracted());
+            if let Some(error) = extract_result.get_errors().first() {
+                status.push_str(&format!(
+                    ", {} errors: {}",
+                    extract_result.get_errors().len(),
+                    error
+                ));
+            }
+            status
+        }
+        None => String::new(),
+    };
+    column![actions, text(status).size(15)].spacing(5).into()
+}
+
+fn display_info_panel<'a>(app: &'a App) -> Element<'a, Message> {
+    let Some(selected) = app.get_selected() else {
+        return Column::new().into();
+    };
+    let mut column = Column::new().spacing(5).padding(10);
+    if let Some(handle) = app.get_selected_thumbnail() {
+        column = column.push(image(handle.clone()).width(THUMBNAIL_PREVIEW_SIZE));
+    }
+    column = column.push(text(selected.to_string_lossy().to_string()).size(18));
+
+    let properties = app
+        .get_selected_metadata()
+        .map(|document_metadata| document_metadata.get_properties())
+        .unwrap_or_default();
+    if properties.is_empty() {
+        column = column.push(text("No document properties").size(15));
+    }
+    for (name, value) in properties {
+        column = column.push(text(name).size(13));
+        column = column.push(text(value).size(15));
+    }
+    column = column.push(display_preview(app));
+    scrollable(column).into()
+}
+
+fn display_preview<'a>(app: &'a App) -> Column<'a, Message> {
+    let mut column = Column::new().spacing(5);
+    let Some(preview) = app.get_preview() else {
+        return column;
+    };
+    let mode = app.get_preview_mode();
+    let mode_button = |label, button_mode| {
+        button(text(label).size(13)).on_press_maybe(
+            (mode != button_mode).then_some(Message::PreviewModeChanged(button_mode)),
+        )
+    };
+    column = column.push(
+        row![
+            mode_button("Text", PreviewMode::Text),
+            mode_button("Hex", PreviewMode::Hex),
+        ]
+        .spacing(5),
+    );
+    let mut details = Vec::new();
+    if let (PreviewMode::Text, Some(text_preview)) = (mode, preview.get_text()) {
+        details.push(String::from(text_preview.get_encoding()));
+        if let Some(syntax) = text_preview.get_syntax() {
+            details.push(String::from(syntax));
+        }
+    }
+    let shown_length = preview.get_shown_length(mode);
+    if shown_length < preview.get_size() {
+        details.push(format!(
+            "first {} of {}",
+            util::format_size(shown_length),
+            util::format_size(preview.get_size())
+        ));
+    }
+    column = column.push(text(details.join(", ")).size(13));
+
+    match (mode, preview.get_text()) {
+        (PreviewMode::Text, Some(text_preview)) => {
+            for line in text_preview.get_lines() {
+                let spans: Vec<_> = line
+                    .iter()
+                    .map(|line_span| {
+                        let [r, g, b] = line_span.get_color();
+                        span::<Message, Font>(line_span.get_text()).color(Color::from_rgb8(r, g, b))
+                    })
+                    .collect();
+                column = column.push(rich_text(spans).font(Font::MONOSPACE).size(13));
+            }
+        }
+        _ => {
+            for line in preview.get_hex() {
+                column = column.push(text(line).font(Font::MONOSPACE).size(13));
+            }
+        }
+    }
+    column
+}
+
+fn display_head<'a>(mut column: Column<'a, Message>, show_thumbnails: bool) -> Column<'a, Message> {
     column = column.push(
         row![
+            thumbnail_cell(show_thumbnails, None),
             text("Name").size(15).width(iced::FillPortion(1)),
+            text("Type").size(15).width(iced::FillPortion(1)),
+            text("Size").size(15).width(iced::FillPortion(1)),
             text("Created").size(15).width(iced::FillPortion(1)),
             text("Modified").size(15).width(iced::FillPortion(1)),
             text("Accessed").size(15).width(iced::FillPortion(1)),
@@ -169,19 +2101,46 @@ fn display_head<'a>(mut column: Column<'a, Message>) -> Column<'a, Message> {
 fn display_directories<'a>(
     mut column: Column<'a, Message>,
     root: &'a Directory,
+    app: &'a App,
 ) -> Column<'a, Message> {
-    for dir in root.get_directories() {
+    let show_thumbnails = app.get_show_thumbnails();
+    let current_path = util::current_path_buf(app.get_current_path());
+    for dir in root
+        .get_directories()
+        .iter()
+        .filter(|dir| app.matches_filter(dir.get_name()))
+    {
         let dir_name = dir.get_name().to_str();
         let directory_metadata = dir.get_metadata();
         let formatted_dates = get_formatted_metadata(directory_metadata);
+        let kind = match dir.get_archive() {
+            Some(location) if location.is_archive_root() => "archive",
+            _ => "directory",
+        };
+        // Directories have a size once disk usage was measured for them
+        let size = app
+            .get_usage_node(&current_path.join(dir.get_name()))
+            .map(|usage| util::format_size(usage.get_size()))
+            .unwrap_or_default();
         if let Some(name) = dir_name {
             column = column.push(
                 button(row![
+                    thumbnail_cell(show_thumbnails, None),
                     text(name)
                         .center()
                         .size(15)
                         .align_x(Horizontal::Left)
                         .width(iced::FillPortion(1)),
+                    text(kind)
+                        .center()
+                        .size(15)
+                        .align_x(Horizontal::Left)
+                        .width(iced::FillPortion(1)),
+                    text(size)
+                        .center()
+                        .size(15)
+                        .align_x(Horizontal::Left)
+                        .width(iced::FillPortion(1)),
                     text(formatted_dates.created)
                         .center()
                         .size(15)
@@ -205,17 +2164,42 @@ fn display_directories<'a>(
     column
 }
 
-fn display_files<'a>(mut column: Column<'a, Message>, root: &'a Directory) -> Column<'a, Message> {
-    for file in root.get_files() {
+fn display_files<'a>(
+    mut column: Column<'a, Message>,
+    root: &'a Directory,
+    app: &'a App,
+) -> Column<'a, Message> {
+    let selected = app.get_selected();
+    for file in root
+        .get_files()
+        .iter()
+        .filter(|file| app.matches_filter(file.get_name()))
+    {
         let file_name = file.get_name();
         let file_metadata = file.get_metadata();
 
         let formatted_dates = get_formatted_metadata(file_metadata);
+        let mime = app
+            .get_content_type(file)
+            .map(|content_type| content_type.get_mime())
+            .unwrap_or("");
 
         if let Some(name) = file_name.to_str() {
+            let is_selected = selected.as_deref() == Some(file_name);
             column = column.push(
-                row![
+                button(row![
+                    thumbnail_cell(app.get_show_thumbnails(), app.get_thumbnail(file)),
                     text(name).size(15).width(iced::FillPortion(1)),
+                    text(mime)
+                        .center()
+                        .align_x(Horizontal::Left)
+                        .size(15)
+                        .width(iced::FillPortion(1)),
+                    text(util::format_size(file_metadata.get_size()))
+                        .center()
+                        .align_x(Horizontal::Left)
+                        .size(15)
+                        .width(iced::FillPortion(1)),
                     text(formatted_dates.created)
                         .center()
                         .align_x(Horizontal::Left)
@@ -231,11 +2215,36 @@ fn display_files<'a>(mut column: Column<'a, Message>, root: &'a Directory) -> Co
                         .align_x(Horizontal::Left)
                         .size(15)
                         .width(iced::FillPortion(1))
-                ]
-                .spacing(5)
-                .padding(5),
+                ])
+                .style(move |theme: &Theme, status| {
+                    if is_selected {
+                        button::primary(theme, status)
+                    } else {
+                        button::text(theme, status)
+                    }
+                })
+                .on_press(Message::Select(OsString::from(file_name))),
             );
         }
     }
     column
 }
+
+// Takes no space when thumbnails are hidden, so the columns stay aligned
+fn thumbnail_cell<'a>(
+    show_thumbnails: bool,
+    handle: Option<&image::Handle>,
+) -> Element<'a, Message> {
+    if !show_thumbnails {
+        return Space::with_width(0).into();
+    }
+    let cell = match handle {
+        Some(handle) => container(
+            image(handle.clone())
+                .width(THUMBNAIL_CELL_SIZE)
+                .height(THUMBNAIL_CELL_SIZE),
+        ),
+        None => container(Space::with_width(THUMBNAIL_CELL_SIZE)),
+    };
+    cell.width(THUMBNAIL_CELL_SIZE + 8.0).into()
+}
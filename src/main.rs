@@ -1,11 +1,41 @@
+mod archive;
+mod audio;
+mod backup;
+mod cleanup;
+mod config;
+mod content_search;
+mod content_type;
 mod directory;
+mod document;
+mod export;
 mod file;
 mod fileorder;
+mod filter;
+mod history;
+mod import;
+mod index;
+mod journal;
 mod layouts;
+mod pattern;
+mod photo;
+mod plan;
+mod preview;
+mod protection;
+mod retention;
+mod rules;
+mod scan;
+mod search;
+mod snapshot;
+mod storage;
+mod thumbnail;
+mod transfer;
+mod usage;
 mod util;
 
 use fileorder::App;
 
 fn main() -> iced::Result {
-    iced::application("File Order", App::update, App::view).run()
+    iced::application("File Order", App::update, App::view)
+        .subscription(App::subscription)
+        .run()
 }
@@ -0,0 +1,342 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    path::Path,
+};
+
+use chrono::{DateTime, Datelike, Local};
+
+use crate::{
+    audio::{self, AudioTags},
+    content_type::ContentType,
+    document::{self, DocumentMetadata},
+    file::FileMetadata,
+    photo::{self, PhotoMetadata},
+};
+
+// Most filesystems allow 255 bytes per name, the rest is left for the
+// counter of a taken name and the suffix of a partial copy
+const MAX_COMPONENT_BYTES: usize = 200;
+const MAX_EXTENSION_BYTES: usize = 16;
+const CONTENT_TOKEN_NAMES: [&str; 2] = ["kind", "mime"];
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+#[derive(Debug, Clone)]
+pub enum TokenValue {
+    Text(String),
+    Number(u64),
+    Date(DateTime<Local>),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Tokens {
+    values: HashMap<String, TokenValue>,
+}
+
+impl Tokens {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Only the readers that provide one of the needed tokens open the file,
+    // the name and dates are always there
+    pub fn from_file(path: &Path, metadata: &FileMetadata, needed: &HashSet<String>) -> Self {
+        let needs = |names: &[&str]| names.iter().any(|name| needed.contains(*name));
+        let mut tokens = Self::new();
+        if let Some(stem) = path.file_stem() {
+            tokens.insert("name", TokenValue::Text(stem.to_string_lossy().to_string()));
+        }
+        if let Some(extension) = path.extension() {
+            tokens.insert(
+                "ext",
+                TokenValue::Text(extension.to_string_lossy().to_lowercase()),
+            );
+        }
+        if let Some(created) = metadata.get_created() {
+            tokens.insert("created", TokenValue::Date(created));
+        }
+        if let Some(modified) = metadata.get_modified() {
+            tokens.insert("modified", TokenValue::Date(modified));
+            tokens.insert("year", TokenValue::Number(modified.year() as u64));
+            tokens.insert("month", TokenValue::Text(modified.format("%m").to_string()));
+            tokens.insert("day", TokenValue::Text(modified.format("%d").to_string()));
+        }
+
+        if needs(&photo::TOKEN_NAMES) {
+            if let Some(photo_metadata) = PhotoMetadata::read(path) {
+                photo_metadata.insert_tokens(&mut tokens);
+            }
+            // Photos without a capture date fall back to the earliest
+            // filesystem date, as the creation date is usually when the file
+            // was copied
+            if tokens.get("exif").is_none() && photo::is_photo(path) {
+                let earliest = match (metadata.get_created(), metadata.get_modified()) {
+                    (Some(created), Some(modified)) => Some(created.min(modified)),
+                    (created, modified) => created.or(modified),
+                };
+                if let Some(earliest) = earliest {
+                    tokens.insert("exif", TokenValue::Date(earliest));
+                }
+            }
+        }
+        if needs(&audio::TOKEN_NAMES) {
+            if let Some(audio_tags) = AudioTags::read(path) {
+                audio_tags.insert_tokens(&mut tokens);
+            }
+        }
+        if needs(&document::TOKEN_NAMES) {
+            if let Some(document_metadata) = DocumentMetadata::read(path) {
+                document_metadata.insert_tokens(&mut tokens);
+            }
+        }
+        if !needs(&CONTENT_TOKEN_NAMES) {
+            return tokens;
+        }
+        if let Ok(Some(content_type)) = ContentType::detect(path) {
+            tokens.insert(
+                "kind",
+                TokenValue::Text(String::from(content_type.get_kind())),
+            );
+            tokens.insert(
+                "mime",
+                TokenValue::Text(String::from(content_type.get_mime())),
+            );
+        }
+        tokens
+    }
+
+    pub fn insert(&mut self, name: &str, value: TokenValue) {
+        self.values.insert(String::from(name), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TokenValue> {
+        self.values.get(name)
+    }
+}
+
+// Expands a destination pattern such as `Photos/{year}/{month}` with the
+// values of the tokens. Every token may carry a format after a colon, e.g.
+// `{track:02}` for zero padded numbers or `{modified:%Y-%m}` for dates.
+pub fn expand(pattern: &str, tokens: &Tokens) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let Some(length) = rest[start..].find('}') else {
+            return Err(format!("Unclosed token in pattern {}", pattern));
+        };
+        let token = &rest[start + 1..start + length];
+        expanded.push_str(&sanitize_path_component(&format_token(token, tokens)?));
+        rest = &rest[start + length + 1..];
+    }
+    expanded.push_str(rest);
+    // Several tokens in one component can still add up to a long name
+    Ok(expanded
+        .split('/')
+        .map(truncate_component)
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+// The names of the tokens in a pattern without their formats, e.g. `exif`
+// and `camera` for `Photos/{exif:%Y}/{camera}`
+pub fn get_token_names(pattern: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        let Some(length) = rest[start..].find('}') else {
+            break;
+        };
+        names.push(split_token(&rest[start + 1..start + length]).0);
+        rest = &rest[start + length + 1..];
+    }
+    names
+}
+
+pub fn format_token(token: &str, tokens: &Tokens) -> Result<String, String> {
+    let (name, format) = split_token(token);
+    let Some(value) = tokens.get(name) else {
+        return Err(format!("No value for {{{}}}", token));
+    };
+    let formatted = match value {
+        TokenValue::Text(text) => text.clone(),
+        TokenValue::Number(number) => match format {
+            Some(format) => {
+                let width: usize = format.trim_start_matches('0').parse().unwrap_or(0);
+                format!("{:0width$}", number, width = width)
+            }
+            None => number.to_string(),
+        },
+        TokenValue::Date(date) => {
+            let format = format.unwrap_or("%Y-%m-%d");
+            let mut formatted = String::new();
+            write!(formatted, "{}", date.format(format))
+                .map_err(|_| format!("Invalid date format {} in {{{}}}", format, token))?;
+            formatted
+        }
+    };
+    Ok(formatted)
+}
+
+pub fn split_token(token: &str) -> (&str, Option<&str>) {
+    match token.split_once(':') {
+        Some((name, format)) => (name, Some(format)),
+        None => (token, None),
+    }
+}
+
+// Makes a token value such as an album title safe to use as a single path
+// component on every supported operating system
+pub fn sanitize_path_component(value: &str) -> String {
+    let sanitized: String = value
+        .chars()
+        .map(|character| match character {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            character if character.is_control() => '_',
+            character => character,
+        })
+        .collect();
+    let collapsed = sanitized.split_whitespace().collect::<Vec<_>>().join(" ");
+    let truncated = truncate_component(&collapsed);
+    let trimmed = truncated
+        .trim_start_matches('.')
+        .trim_end_matches('.')
+        .trim();
+    if trimmed.is_empty() {
+        return String::from("_");
+    }
+    let stem = trimmed.split('.').next().unwrap_or(trimmed);
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return format!("_{}", trimmed);
+    }
+    String::from(trimmed)
+}
+
+// Shortens a name to the byte budget at a character boundary, keeping its
+// extension
+fn truncate_component(value: &str) -> String {
+    if value.len() <= MAX_COMPONENT_BYTES {
+        return String::from(value);
+    }
+    let (stem, extension) = match value.rfind('.') {
+        Some(dot) if dot > 0 && value.len() - dot <= MAX_EXTENSION_BYTES => value.split_at(dot),
+        _ => (value, ""),
+    };
+    let mut end = MAX_COMPONENT_BYTES - extension.len();
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+    let stem = stem[..end].trim_end_matches(|c: char| c == '.' || c.is_whitespace());
+    format!("{}{}", stem, extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::fs;
+
+    fn music_tokens() -> Tokens {
+        let mut tokens = Tokens::new();
+        tokens.insert("artist", TokenValue::Text(String::from("AC/DC")));
+        tokens.insert("title", TokenValue::Text(String::from("Back  in Black")));
+        tokens.insert("ext", TokenValue::Text(String::from("mp3")));
+        tokens.insert("track", TokenValue::Number(7));
+        tokens.insert(
+            "modified",
+            TokenValue::Date(Local.with_ymd_and_hms(2024, 3, 9, 12, 0, 0).unwrap()),
+        );
+        tokens
+    }
+
+    #[test]
+    fn expand_formats_and_sanitizes_tokens() {
+        let tokens = music_tokens();
+        assert_eq!(
+            expand("Music/{artist}/{track:02} - {title}.{ext}", &tokens).unwrap(),
+            "Music/AC_DC/07 - Back in Black.mp3"
+        );
+        assert_eq!(
+            expand("Archive/{modified:%Y-%m}", &tokens).unwrap(),
+            "Archive/2024-03"
+        );
+        assert_eq!(expand("{modified}", &tokens).unwrap(), "2024-03-09");
+        assert!(expand("Music/{album}", &tokens).is_err());
+        assert!(expand("Music/{artist", &tokens).is_err());
+    }
+
+    #[test]
+    fn expand_keeps_the_extension_of_long_names() {
+        let mut tokens = music_tokens();
+        tokens.insert("title", TokenValue::Text("é".repeat(150)));
+        let expanded = expand("Music/{title}.{ext}", &tokens).unwrap();
+        let file_name = expanded.rsplit('/').next().unwrap();
+        assert!(file_name.len() <= MAX_COMPONENT_BYTES);
+        assert!(file_name.ends_with("é.mp3"));
+    }
+
+    #[test]
+    fn sanitize_replaces_separators_and_reserved_names() {
+        assert_eq!(sanitize_path_component("a/b\\c:d*e?"), "a_b_c_d_e_");
+        assert_eq!(sanitize_path_component("  spaced    out  "), "spaced out");
+        assert_eq!(sanitize_path_component("tab\tbed"), "tab_bed");
+        assert_eq!(sanitize_path_component("..hidden."), "hidden");
+        assert_eq!(sanitize_path_component("..."), "_");
+        assert_eq!(sanitize_path_component("con.txt"), "_con.txt");
+        assert_eq!(sanitize_path_component("Console"), "Console");
+    }
+
+    #[test]
+    fn sanitize_truncates_to_bytes_at_a_character_boundary() {
+        let long = format!("{}.flac", "ü".repeat(200));
+        let sanitized = sanitize_path_component(&long);
+        assert!(sanitized.len() <= MAX_COMPONENT_BYTES);
+        assert!(sanitized.ends_with("ü.flac"));
+
+        let without_extension = "日本".repeat(100);
+        let sanitized = sanitize_path_component(&without_extension);
+        assert!(sanitized.len() <= MAX_COMPONENT_BYTES);
+        assert!(without_extension.starts_with(&sanitized));
+    }
+
+    #[test]
+    fn token_names_drop_their_formats() {
+        assert_eq!(
+            get_token_names("Photos/{exif:%Y}/{camera} - {track:02}.{ext}"),
+            vec!["exif", "camera", "track", "ext"]
+        );
+        assert!(get_token_names("Documents/{unclosed").is_empty());
+    }
+
+    #[test]
+    fn exif_date_falls_back_only_for_photos() {
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-pattern-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let photo = directory.join("scan.jpg");
+        let text = directory.join("notes.txt");
+        fs::write(&photo, "").unwrap();
+        fs::write(&text, "").unwrap();
+        let needed = HashSet::from([String::from("exif")]);
+
+        let metadata = FileMetadata::read(&fs::metadata(&photo).unwrap());
+        assert!(Tokens::from_file(&photo, &metadata, &needed)
+            .get("exif")
+            .is_some());
+        let metadata = FileMetadata::read(&fs::metadata(&text).unwrap());
+        assert!(Tokens::from_file(&text, &metadata, &needed)
+            .get("exif")
+            .is_none());
+        assert!(Tokens::from_file(&photo, &metadata, &HashSet::new())
+            .get("exif")
+            .is_none());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, TimeZone};
+use exif::{Exif, In, Reader, Tag, Value};
+
+use crate::pattern::{TokenValue, Tokens};
+
+const PHOTO_EXTENSIONS: [&str; 17] = [
+    "jpg", "jpeg", "heic", "heif", "avif", "tif", "tiff", "png", "webp", "dng", "cr2", "nef",
+    "nrw", "arw", "orf", "rw2", "pef",
+];
+pub const TOKEN_NAMES: [&str; 6] = [
+    "exif",
+    "camera_make",
+    "camera_model",
+    "camera",
+    "lens",
+    "gps",
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct PhotoMetadata {
+    taken: Option<DateTime<Local>>,
+    camera_make: Option<String>,
+    camera_model: Option<String>,
+    lens: Option<String>,
+    has_gps: bool,
+}
+
+impl PhotoMetadata {
+    pub fn read(path: &Path) -> Option<Self> {
+        if !is_photo(path) {
+            return None;
+        }
+        let file = File::open(path).ok()?;
+        let exif = Reader::new()
+            .read_from_container(&mut BufReader::new(file))
+            .ok()?;
+
+        let taken = [Tag::DateTimeOriginal, Tag::DateTimeDigitized, Tag::DateTime]
+            .into_iter()
+            .find_map(|tag| read_date(&exif, tag));
+        Some(Self {
+            taken,
+            camera_make: read_text(&exif, Tag::Make),
+            camera_model: read_text(&exif, Tag::Model),
+            lens: read_text(&exif, Tag::LensModel),
+            has_gps: exif.get_field(Tag::GPSLatitude, In::PRIMARY).is_some(),
+        })
+    }
+
+    pub fn get_camera(&self) -> Option<String> {
+        match (&self.camera_make, &self.camera_model) {
+            (Some(make), Some(model)) if model.starts_with(make.as_str()) => Some(model.clone()),
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (make, model) => make.clone().or(model.clone()),
+        }
+    }
+
+    pub fn insert_tokens(&self, tokens: &mut Tokens) {
+        if let Some(taken) = self.taken {
+            tokens.insert("exif", TokenValue::Date(taken));
+        }
+        if let Some(make) = &self.camera_make {
+            tokens.insert("camera_make", TokenValue::Text(make.clone()));
+        }
+        if let Some(model) = &self.camera_model {
+            tokens.insert("camera_model", TokenValue::Text(model.clone()));
+        }
+        if let Some(camera) = self.get_camera() {
+            tokens.insert("camera", TokenValue::Text(camera));
+        }
+        if let Some(lens) = &self.lens {
+            tokens.insert("lens", TokenValue::Text(lens.clone()));
+        }
+        if self.has_gps {
+            tokens.insert("gps", TokenValue::Text(String::from("yes")));
+        }
+    }
+}
+
+pub fn is_photo(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            PHOTO_EXTENSIONS
+                .iter()
+                .any(|photo| photo.eq_ignore_ascii_case(extension))
+        })
+}
+
+fn read_text(exif: &Exif, tag: Tag) -> Option<String> {
+    let field = exif.get_field(tag, In::PRIMARY)?;
+    let Value::Ascii(values) = &field.value else {
+        return None;
+    };
+    let text = String::from_utf8_lossy(values.first()?);
+    let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    if text.is_empty() {
+        return None;
+    }
+    Some(String::from(text))
+}
+
+fn read_date(exif: &Exif, tag: Tag) -> Option<DateTime<Local>> {
+    let field = exif.get_field(tag, In::PRIMARY)?;
+    let Value::Ascii(values) = &field.value else {
+        return None;
+    };
+    let mut date = exif::DateTime::from_ascii(values.first()?).ok()?;
+    let offset_tag = match tag {
+        Tag::DateTimeOriginal => Tag::OffsetTimeOriginal,
+        Tag::DateTimeDigitized => Tag::OffsetTimeDigitized,
+        _ => Tag::OffsetTime,
+    };
+    if let Some(Value::Ascii(offset)) = exif
+        .get_field(offset_tag, In::PRIMARY)
+        .map(|field| &field.value)
+    {
+        if let Some(offset) = offset.first() {
+            let _ = date.parse_offset(offset);
+        }
+    }
+
+    let naive = NaiveDate::from_ymd_opt(date.year.into(), date.month.into(), date.day.into())?
+        .and_hms_opt(date.hour.into(), date.minute.into(), date.second.into())?;
+    match date.offset {
+        Some(offset) => FixedOffset::east_opt(i32::from(offset) * 60)?
+            .from_local_datetime(&naive)
+            .single()
+            .map(|date| date.with_timezone(&Local)),
+        // Without an offset the camera clock is assumed to be in local time
+        None => Local.from_local_datetime(&naive).earliest(),
+    }
+}
@@ -0,0 +1,714 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    fs,
+    path::{Component, Path, PathBuf},
+    thread,
+};
+
+use iced::futures::channel::mpsc::{self as async_mpsc, UnboundedReceiver};
+use serde::Serialize;
+
+use crate::{
+    archive,
+    export::{self, ExportFormat},
+    filter::EntryFilter,
+    index::Index,
+    journal,
+    pattern::{self, TokenValue, Tokens},
+    protection::{self, ProtectedPath, Protection},
+    rules::{self, Rule, RuleAction, RuleSet},
+    scan::{self, ScanResult},
+    storage::{self, SpaceShortage},
+    transfer::{self, ProgressThrottle, TransferProgress},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OperationKind {
+    Move,
+    Archive,
+}
+
+#[derive(Debug, Clone)]
+pub struct Operation {
+    kind: OperationKind,
+    source: PathBuf,
+    destination: PathBuf,
+    rule: String,
+}
+
+impl Operation {
+    pub fn get_kind(&self) -> &OperationKind {
+        &self.kind
+    }
+
+    pub fn get_source(&self) -> &Path {
+        &self.source
+    }
+
+    pub fn get_destination(&self) -> &Path {
+        &self.destination
+    }
+
+    pub fn get_rule(&self) -> &str {
+        &self.rule
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Plan {
+    root: PathBuf,
+    operations: Vec<Operation>,
+    protected: Vec<ProtectedPath>,
+    refused: Option<String>,
+    space_shortages: Vec<SpaceShortage>,
+    errors: Vec<String>,
+}
+
+impl Plan {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: PathBuf::from(root),
+            operations: Vec::new(),
+            protected: Vec::new(),
+            refused: None,
+            space_shortages: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn get_root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn get_operations(&self) -> &Vec<Operation> {
+        &self.operations
+    }
+
+    pub fn get_protected(&self) -> &Vec<ProtectedPath> {
+        &self.protected
+    }
+
+    pub fn get_refused(&self) -> &Option<String> {
+        &self.refused
+    }
+
+    pub fn get_space_shortages(&self) -> &Vec<SpaceShortage> {
+        &self.space_shortages
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+
+    pub fn can_apply(&self) -> bool {
+        self.refused.is_none() && !self.operations.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PlanResult {
+    completed: Vec<Operation>,
+    errors: Vec<String>,
+}
+
+impl PlanResult {
+    pub fn get_completed(&self) -> &Vec<Operation> {
+        &self.completed
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ApplyEvent {
+    Progress(TransferProgress),
+    Finished(PlanResult),
+}
+
+pub fn build_plan(
+    root: &Path,
+    rules: &[Rule],
+    entry_filter: &EntryFilter,
+    protection: &Protection,
+) -> Plan {
+    let mut plan = Plan::new(root);
+    if refuse_protected_root(&mut plan, protection) {
+        return plan;
+    }
+    // The index rereads the directories it last read with another filter,
+    // so an indexed root is scanned through it
+    let scan_result = match Index::open_indexed(root) {
+        Some(mut index) => scan::scan_indexed(root, entry_filter, Some(protection), &mut index),
+        None => scan::scan(root, entry_filter, Some(protection)),
+    };
+    plan_files(&mut plan, &scan_result, rules, protection);
+    plan
+}
+
+// Plans only the given files, such as the results of a search, with paths
+// and destinations relative to root as usual
+pub fn build_plan_for_files(
+    root: &Path,
+    files: &[PathBuf],
+    rules: &[Rule],
+    protection: &Protection,
+) -> Plan {
+    let mut plan = Plan::new(root);
+    if refuse_protected_root(&mut plan, protection) {
+        return plan;
+    }
+    let scan_result = scan::read_files(files, protection);
+    plan_files(&mut plan, &scan_result, rules, protection);
+    plan
+}
+
+fn refuse_protected_root(plan: &mut Plan, protection: &Protection) -> bool {
+    let root = plan.root.as_path();
+    if let Some(protected) = protection.check_path(root) {
+        plan.refused = Some(if protected.get_path() == root {
+            format!(
+                "Refusing to organize {}: {}",
+                root.display(),
+                protected.get_reason()
+            )
+        } else {
+            format!(
+                "Refusing to organize {}, it is inside {}: {}",
+                root.display(),
+                protected.get_path().display(),
+                protected.get_reason()
+            )
+        });
+        return true;
+    }
+    false
+}
+
+fn plan_files(plan: &mut Plan, scan_result: &ScanResult, rules: &[Rule], protection: &Protection) {
+    let root = plan.root.clone();
+    let root = root.as_path();
+    plan.protected
+        .extend(scan_result.get_protected().iter().cloned());
+    plan.errors.extend(scan_result.get_errors().iter().cloned());
+
+    let mut planned_destinations: HashSet<PathBuf> = HashSet::new();
+    let mut archive_destinations: HashMap<PathBuf, PathBuf> = HashMap::new();
+    let token_names = rules::get_token_names(rules);
+    let rule_set = RuleSet::build(rules, &mut plan.errors);
+    // Software projects are planned like files, so that rules can move them
+    // as a whole, and are matched with the project token
+    let files = scan_result
+        .get_files()
+        .iter()
+        .map(|file| (file, None))
+        .chain(
+            scan_result
+                .get_projects()
+                .iter()
+                .map(|project| (project, protection::find_project_marker(project.get_path()))),
+        );
+    for (file, project_marker) in files {
+        let source = file.get_path();
+        let mut tokens = Tokens::from_file(source, file.get_metadata(), &token_names);
+        if let Some(marker) = project_marker {
+            tokens.insert("project", TokenValue::Text(String::from(marker)));
+        }
+        tokens.insert("path", TokenValue::Text(get_member_path(root, source)));
+        if let Some(folder) = source.parent().and_then(|parent| parent.file_name()) {
+            tokens.insert(
+                "folder",
+                TokenValue::Text(folder.to_string_lossy().to_string()),
+            );
+        }
+        let Some(rule) = rule_set.find_matching_rule(source, &tokens) else {
+            continue;
+        };
+        let expanded_destination = match pattern::expand(rule.get_destination(), &tokens) {
+            Ok(expanded) if is_relative_inside(Path::new(&expanded)) => root.join(expanded),
+            Ok(expanded) => {
+                plan.errors.push(format!(
+                    "Rule {} points outside of {}: {}",
+                    rule.get_name(),
+                    root.display(),
+                    expanded
+                ));
+                continue;
+            }
+            Err(error) => {
+                plan.errors.push(format!("{}: {}", source.display(), error));
+                continue;
+            }
+        };
+        if rule.get_action() == RuleAction::Archive {
+            if project_marker.is_some() {
+                plan.protected.push(ProtectedPath::build(
+                    source,
+                    format!(
+                        "Software project, only moved as a whole, not archived by rule {}",
+                        rule.get_name()
+                    ),
+                ));
+                continue;
+            }
+            let is_archive_name = expanded_destination
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(archive::split_archive_name)
+                .is_some();
+            if !is_archive_name {
+                plan.errors.push(format!(
+                    "Rule {} must name an archive ending in one of {}: {}",
+                    rule.get_name(),
+                    archive::get_archive_suffixes(),
+                    expanded_destination.display()
+                ));
+                continue;
+            }
+            if let Some(protected) = expanded_destination
+                .parent()
+                .and_then(|parent| protection.check_path(parent))
+            {
+                plan.protected.push(ProtectedPath::build(
+                    source,
+                    format!(
+                        "Archive {} is inside {}: {}",
+                        expanded_destination.display(),
+                        protected.get_path().display(),
+                        protected.get_reason()
+                    ),
+                ));
+                continue;
+            }
+            // Every file of the same archive shares one destination, which
+            // only has to avoid existing files
+            let destination = archive_destinations
+                .entry(expanded_destination.clone())
+                .or_insert_with(|| {
+                    unique_archive_destination(&expanded_destination, &planned_destinations)
+                })
+                .clone();
+            planned_destinations.insert(destination.clone());
+            plan.operations.push(Operation {
+                kind: OperationKind::Archive,
+                source: PathBuf::from(source),
+                destination,
+                rule: String::from(rule.get_name()),
+            });
+            continue;
+        }
+
+        let (destination_directory, file_name) = if rule.renames_file() {
+            match (
+                expanded_destination.parent(),
+                expanded_destination.file_name(),
+            ) {
+                (Some(parent), Some(file_name)) => {
+                    (PathBuf::from(parent), OsString::from(file_name))
+                }
+                _ => continue,
+            }
+        } else {
+            match source.file_name() {
+                Some(file_name) => (expanded_destination, OsString::from(file_name)),
+                None => continue,
+            }
+        };
+        if destination_directory.join(&file_name) == source {
+            continue;
+        }
+        if let Some(protected) = protection.check_path(&destination_directory) {
+            plan.protected.push(ProtectedPath::build(
+                source,
+                format!(
+                    "Destination {} is inside {}: {}",
+                    destination_directory.display(),
+                    protected.get_path().display(),
+                    protected.get_reason()
+                ),
+            ));
+            continue;
+        }
+        // Projects are renamed in one step and never copied file by file
+        if project_marker.is_some() && !storage::is_same_device(source, &destination_directory) {
+            plan.protected.push(ProtectedPath::build(
+                source,
+                format!(
+                    "Software project, only moved within the same drive, not to {}",
+                    destination_directory.display()
+                ),
+            ));
+            continue;
+        }
+
+        let destination =
+            unique_destination(&destination_directory, &file_name, &planned_destinations);
+        planned_destinations.insert(destination.clone());
+        plan.operations.push(Operation {
+            kind: OperationKind::Move,
+            source: PathBuf::from(source),
+            destination,
+            rule: String::from(rule.get_name()),
+        });
+    }
+    check_space(plan);
+}
+
+// Archives are written next to the originals before those are removed, and
+// moves to another filesystem are copies
+fn check_space(plan: &mut Plan) {
+    let writes: Vec<(&Path, u64)> = plan
+        .operations
+        .iter()
+        .filter(|operation| {
+            operation.kind == OperationKind::Archive
+                || !storage::is_same_device(&operation.source, &operation.destination)
+        })
+        .filter_map(|operation| {
+            let size = fs::metadata(&operation.source).ok()?.len();
+            Some((operation.destination.as_path(), size))
+        })
+        .collect();
+    plan.space_shortages = storage::find_space_shortages(&writes);
+}
+
+// One line of an exported plan. Protected paths and errors are listed with
+// their reason in the rule column.
+#[derive(Debug, Serialize)]
+struct PlanRecord {
+    status: &'static str,
+    kind: String,
+    source: String,
+    destination: String,
+    rule: String,
+}
+
+// Operations are marked done or not done once the plan was applied
+pub fn export_plan(
+    plan: &Plan,
+    plan_result: Option<&PlanResult>,
+    format: ExportFormat,
+) -> Result<PathBuf, String> {
+    let completed: HashSet<&Path> = plan_result
+        .map(|plan_result| {
+            plan_result
+                .completed
+                .iter()
+                .map(|operation| operation.source.as_path())
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut records: Vec<PlanRecord> = plan
+        .operations
+        .iter()
+        .map(|operation| PlanRecord {
+            status: match plan_result {
+                None => "planned",
+                Some(_) if completed.contains(operation.source.as_path()) => "done",
+                Some(_) => "not done",
+            },
+            kind: format!("{:?}", operation.kind),
+            source: operation.source.display().to_string(),
+            destination: operation.destination.display().to_string(),
+            rule: operation.rule.clone(),
+        })
+        .collect();
+    for protected in &plan.protected {
+        records.push(PlanRecord {
+            status: "protected",
+            kind: String::new(),
+            source: protected.get_path().display().to_string(),
+            destination: String::new(),
+            rule: protected.get_reason().to_string(),
+        });
+    }
+    let errors = plan.errors.iter().chain(
+        plan_result
+            .iter()
+            .flat_map(|plan_result| &plan_result.errors),
+    );
+    for error in errors {
+        records.push(PlanRecord {
+            status: "error",
+            kind: String::new(),
+            source: String::new(),
+            destination: String::new(),
+            rule: error.clone(),
+        });
+    }
+
+    let path = export::get_export_path("plan", format)?;
+    let title = format!("Organizing {}", plan.root.display());
+    export::write_records(&path, format, &title, &records)
+        .map(|_| path)
+        .map_err(|error| error.to_string())
+}
+
+// Applies the plan on its own thread and reports the progress a few times a
+// second, which matters for large files copied to another drive
+pub fn start_apply(plan: Plan, verify: bool) -> UnboundedReceiver<ApplyEvent> {
+    let (sender, receiver) = async_mpsc::unbounded();
+    thread::spawn(move || {
+        let mut throttle = ProgressThrottle::new();
+        let result = apply_plan(&plan, verify, |progress| {
+            if throttle.is_due() {
+                let _ = sender.unbounded_send(ApplyEvent::Progress(progress));
+            }
+        });
+        let _ = sender.unbounded_send(ApplyEvent::Finished(result));
+    });
+    receiver
+}
+
+pub fn apply_plan(
+    plan: &Plan,
+    verify: bool,
+    mut on_progress: impl FnMut(TransferProgress),
+) -> PlanResult {
+    let mut result = PlanResult::default();
+    if let Some(refused) = plan.get_refused() {
+        result.errors.push(refused.clone());
+        return result;
+    }
+    let operations = plan.get_operations().len();
+    let mut archives: BTreeMap<&Path, Vec<&Operation>> = BTreeMap::new();
+    for (done, operation) in plan.get_operations().iter().enumerate() {
+        if operation.kind == OperationKind::Archive {
+            archives
+                .entry(&operation.destination)
+                .or_default()
+                .push(operation);
+            continue;
+        }
+        let mut progress = |copied, size| {
+            on_progress(TransferProgress::build(
+                done,
+                operations,
+                &operation.source,
+                copied,
+                size,
+            ))
+        };
+        match apply_move(operation, verify, &mut progress) {
+            Ok(()) => result.completed.push(operation.clone()),
+            Err(error) => result.errors.push(format!(
+                "{} -> {}: {}",
+                operation.source.display(),
+                operation.destination.display(),
+                error
+            )),
+        }
+    }
+    let mut done = operations - archives.values().map(Vec::len).sum::<usize>();
+    for (destination, archived) in archives {
+        on_progress(TransferProgress::build(done, operations, destination, 0, 0));
+        apply_archive(&plan.root, destination, &archived, &mut result);
+        done += archived.len();
+    }
+
+    if !result.completed.is_empty() {
+        if let Err(error) = journal::record(&plan.root, &result.completed) {
+            result
+                .errors
+                .push(format!("Could not write the journal: {}", error));
+        }
+    }
+    result
+}
+
+// Archive member paths are relative to the organized directory
+pub fn get_member_path(root: &Path, source: &Path) -> String {
+    source
+        .strip_prefix(root)
+        .unwrap_or(source)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn apply_move(
+    operation: &Operation,
+    verify: bool,
+    progress: &mut impl FnMut(u64, u64),
+) -> std::io::Result<()> {
+    if operation.destination.exists() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            "Destination already exists",
+        ));
+    }
+    if let Some(parent) = operation.destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    transfer::move_file(&operation.source, &operation.destination, verify, progress)
+}
+
+// The originals are only moved to the trash once the archive has been read
+// back and matches them
+fn apply_archive(
+    root: &Path,
+    destination: &Path,
+    operations: &[&Operation],
+    result: &mut PlanResult,
+) {
+    let members: Vec<(String, PathBuf)> = operations
+        .iter()
+        .map(|operation| {
+            (
+                get_member_path(root, &operation.source),
+                operation.source.clone(),
+            )
+        })
+        .collect();
+    if let Err(error) = archive::create(destination, &members) {
+        if error.kind() != std::io::ErrorKind::AlreadyExists {
+            let _ = fs::remove_file(destination);
+        }
+        result
+            .errors
+            .push(format!("{}: {}", destination.display(), error));
+        return;
+    }
+    if let Err(error) = archive::verify(destination, &members) {
+        let _ = fs::remove_file(destination);
+        result.errors.push(format!(
+            "{}: Verification failed, originals kept: {}",
+            destination.display(),
+            error
+        ));
+        return;
+    }
+    for operation in operations {
+        match trash::delete(&operation.source) {
+            Ok(()) => result.completed.push((*operation).clone()),
+            Err(error) => result.errors.push(format!(
+                "{}: Archived but not moved to the trash: {}",
+                operation.source.display(),
+                error
+            )),
+        }
+    }
+}
+
+pub fn is_relative_inside(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+pub fn unique_destination(
+    directory: &Path,
+    file_name: &OsStr,
+    planned_destinations: &HashSet<PathBuf>,
+) -> PathBuf {
+    let mut destination = directory.join(file_name);
+    let file_path = Path::new(file_name);
+    let mut counter = 1;
+    while destination.exists() || planned_destinations.contains(&destination) {
+        let mut name = OsString::from(file_path.file_stem().unwrap_or(file_name));
+        name.push(format!(" ({})", counter));
+        if let Some(extension) = file_path.extension() {
+            name.push(".");
+            name.push(extension);
+        }
+        destination = directory.join(name);
+        counter += 1;
+    }
+    destination
+}
+
+fn unique_archive_destination(path: &Path, planned_destinations: &HashSet<PathBuf>) -> PathBuf {
+    let Some((stem, suffix)) = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(archive::split_archive_name)
+        .map(|(stem, suffix, _)| (String::from(stem), String::from(suffix)))
+    else {
+        return PathBuf::from(path);
+    };
+    let mut destination = PathBuf::from(path);
+    let mut counter = 1;
+    while destination.exists() || planned_destinations.contains(&destination) {
+        destination.set_file_name(format!("{} ({}){}", stem, counter, suffix));
+        counter += 1;
+    }
+    destination
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Condition;
+
+    fn temp_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-plan-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn relative_paths_must_stay_inside() {
+        assert!(is_relative_inside(Path::new("Images/2024")));
+        assert!(is_relative_inside(Path::new("./Music")));
+        assert!(!is_relative_inside(Path::new("../Images")));
+        assert!(!is_relative_inside(Path::new("Images/../../etc")));
+        assert!(!is_relative_inside(Path::new("/etc")));
+    }
+
+    #[test]
+    fn unique_destination_numbers_taken_names() {
+        let directory = temp_directory("unique");
+        fs::write(directory.join("photo.jpg"), "").unwrap();
+        let mut planned = HashSet::new();
+        planned.insert(directory.join("photo (1).jpg"));
+
+        assert_eq!(
+            unique_destination(&directory, OsStr::new("photo.jpg"), &planned),
+            directory.join("photo (2).jpg")
+        );
+        assert_eq!(
+            unique_destination(&directory, OsStr::new("notes"), &planned),
+            directory.join("notes")
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn projects_are_moved_as_a_whole() {
+        let directory = temp_directory("project");
+        fs::create_dir_all(directory.join("tool/src")).unwrap();
+        fs::write(directory.join("tool/Cargo.toml"), "").unwrap();
+        fs::write(directory.join("tool/src/main.rs"), "").unwrap();
+        let rules = [
+            Rule::build("Projects", &[], "Projects")
+                .with_condition(Condition::build("project", None)),
+            Rule::build("Code", &["rs", "toml"], "Code"),
+        ];
+
+        let plan = build_plan(
+            &directory,
+            &rules,
+            &EntryFilter::new(),
+            &Protection::build(&[]),
+        );
+        let operations: Vec<(&Path, &Path)> = plan
+            .get_operations()
+            .iter()
+            .map(|operation| (operation.get_source(), operation.get_destination()))
+            .collect();
+        assert_eq!(
+            operations,
+            vec![(
+                directory.join("tool").as_path(),
+                directory.join("Projects/tool").as_path()
+            )]
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
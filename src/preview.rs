@@ -0,0 +1,310 @@
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    parsing::SyntaxSet,
+};
+
+const TEXT_PREVIEW_LENGTH: u64 = 64 * 1024;
+const HEX_PREVIEW_LENGTH: usize = 8 * 1024;
+const HEX_LINE_LENGTH: usize = 16;
+const MAX_HIGHLIGHTED_LINES: usize = 2000;
+const HIGHLIGHT_THEME: &str = "InspiredGitHub";
+
+static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PreviewMode {
+    Text,
+    Hex,
+}
+
+// A run of text drawn in one color
+#[derive(Debug, Clone)]
+pub struct Span {
+    text: String,
+    color: [u8; 3],
+}
+
+impl Span {
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn get_color(&self) -> [u8; 3] {
+        self.color
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TextPreview {
+    encoding: &'static str,
+    syntax: Option<String>,
+    lines: Vec<Vec<Span>>,
+}
+
+impl TextPreview {
+    pub fn get_encoding(&self) -> &str {
+        self.encoding
+    }
+
+    pub fn get_syntax(&self) -> Option<&str> {
+        self.syntax.as_deref()
+    }
+
+    pub fn get_lines(&self) -> &Vec<Vec<Span>> {
+        &self.lines
+    }
+}
+
+// The first bytes of a file as text, when they decode as text, and as a hex dump
+#[derive(Debug, Clone)]
+pub struct Preview {
+    path: PathBuf,
+    text: Option<TextPreview>,
+    hex: Vec<String>,
+    size: u64,
+}
+
+impl Preview {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|error| error.to_string())?;
+        let size = file.metadata().map_err(|error| error.to_string())?.len();
+        let mut buffer = Vec::new();
+        file.take(TEXT_PREVIEW_LENGTH + 1)
+            .read_to_end(&mut buffer)
+            .map_err(|error| error.to_string())?;
+        let truncated = buffer.len() as u64 > TEXT_PREVIEW_LENGTH;
+        buffer.truncate(TEXT_PREVIEW_LENGTH as usize);
+
+        let text = decode(&buffer, truncated).map(|(content, encoding)| {
+            let (syntax, lines) = highlight(path, &content);
+            TextPreview {
+                encoding: encoding.name(),
+                syntax,
+                lines,
+            }
+        });
+        Ok(Self {
+            path: PathBuf::from(path),
+            hex: hex_dump(&buffer[..buffer.len().min(HEX_PREVIEW_LENGTH)]),
+            text,
+            size,
+        })
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_text(&self) -> Option<&TextPreview> {
+        self.text.as_ref()
+    }
+
+    pub fn get_hex(&self) -> &Vec<String> {
+        &self.hex
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn get_shown_length(&self, mode: PreviewMode) -> u64 {
+        match mode {
+            PreviewMode::Text => self.size.min(TEXT_PREVIEW_LENGTH),
+            PreviewMode::Hex => self.size.min(HEX_PREVIEW_LENGTH as u64),
+        }
+    }
+}
+
+// Byte order marks are trusted, then UTF-8 is tried before guessing a legacy
+// encoding. Content with NUL bytes or other control characters is binary.
+fn decode(buffer: &[u8], truncated: bool) -> Option<(String, &'static Encoding)> {
+    if let Some((encoding, _)) = Encoding::for_bom(buffer) {
+        let (content, _, had_errors) = encoding.decode(buffer);
+        return (!had_errors || truncated).then(|| (content.into_owned(), encoding));
+    }
+    if buffer.contains(&0) {
+        return None;
+    }
+    let (content, encoding) = match std::str::from_utf8(buffer) {
+        Ok(content) => (String::from(content), UTF_8),
+        // A multibyte character may be cut at the end of the preview
+        Err(error) if error.error_len().is_none() => (
+            String::from_utf8_lossy(&buffer[..error.valid_up_to()]).into_owned(),
+            UTF_8,
+        ),
+        Err(_) => {
+            let mut detector = EncodingDetector::new();
+            detector.feed(buffer, !truncated);
+            let encoding = detector.guess(None, true);
+            let (content, _, had_errors) = encoding.decode(buffer);
+            if had_errors {
+                return None;
+            }
+            (content.into_owned(), encoding)
+        }
+    };
+    let is_binary = content
+        .chars()
+        .any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t' | '\x0c'));
+    (!is_binary).then_some((content, encoding))
+}
+
+fn get_syntax_set() -> &'static SyntaxSet {
+    SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+// The syntax is found by file name or the first line. Lines past the
+// highlighting limit are shown in the default color.
+fn highlight(path: &Path, content: &str) -> (Option<String>, Vec<Vec<Span>>) {
+    let syntax_set = get_syntax_set();
+    let theme = THEME.get_or_init(|| {
+        ThemeSet::load_defaults()
+            .themes
+            .remove(HIGHLIGHT_THEME)
+            .unwrap_or_default()
+    });
+    let syntax = syntax_set
+        .find_syntax_for_file(path)
+        .ok()
+        .flatten()
+        .or_else(|| {
+            syntax_set.find_syntax_by_first_line(content.lines().next().unwrap_or_default())
+        })
+        .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+    let syntax_name =
+        (syntax.name != syntax_set.find_syntax_plain_text().name).then(|| syntax.name.clone());
+    let default_color = theme
+        .settings
+        .foreground
+        .map(|color| [color.r, color.g, color.b])
+        .unwrap_or_default();
+
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let mut lines = Vec::new();
+    for (index, line) in syntect::util::LinesWithEndings::from(content).enumerate() {
+        let ranges = if index < MAX_HIGHLIGHTED_LINES {
+            highlighter.highlight_line(line, syntax_set).ok()
+        } else {
+            None
+        };
+        let spans = match ranges {
+            Some(ranges) => ranges
+                .into_iter()
+                .map(|(style, text)| Span {
+                    text: String::from(text.trim_end_matches(['\n', '\r'])),
+                    color: [style.foreground.r, style.foreground.g, style.foreground.b],
+                })
+                .collect(),
+            None => vec![Span {
+                text: String::from(line.trim_end_matches(['\n', '\r'])),
+                color: default_color,
+            }],
+        };
+        lines.push(spans);
+    }
+    (syntax_name, lines)
+}
+
+// Lines like `00000010  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 00  |Hello, world!...|`
+fn hex_dump(buffer: &[u8]) -> Vec<String> {
+    buffer
+        .chunks(HEX_LINE_LENGTH)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut line = format!("{:08x} ", index * HEX_LINE_LENGTH);
+            for position in 0..HEX_LINE_LENGTH {
+                if position % 8 == 0 {
+                    line.push(' ');
+                }
+                match chunk.get(position) {
+                    Some(byte) => line.push_str(&format!("{:02x} ", byte)),
+                    None => line.push_str("   "),
+                }
+            }
+            let printable: String = chunk
+                .iter()
+                .map(|byte| {
+                    if byte.is_ascii_graphic() || *byte == b' ' {
+                        *byte as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            line.push_str(&format!(" |{}|", printable));
+            line
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{UTF_16LE, WINDOWS_1252};
+
+    #[test]
+    fn decode_follows_byte_order_marks() {
+        assert_eq!(
+            decode(b"\xff\xfeh\0i\0", false),
+            Some((String::from("hi"), UTF_16LE))
+        );
+        assert_eq!(
+            decode(b"\xef\xbb\xbfcaf\xc3\xa9", false),
+            Some((String::from("caf\u{e9}"), UTF_8))
+        );
+    }
+
+    #[test]
+    fn decode_drops_a_multibyte_character_cut_at_the_end() {
+        assert_eq!(decode(b"caf\xc3", true), Some((String::from("caf"), UTF_8)));
+    }
+
+    #[test]
+    fn decode_guesses_legacy_encodings() {
+        let buffer = b"Gr\xfc\xdfe aus M\xfcnchen, sch\xf6ne Gr\xfc\xdfe";
+        assert_eq!(
+            decode(buffer, false),
+            Some((
+                String::from("Gr\u{fc}\u{df}e aus M\u{fc}nchen, sch\u{f6}ne Gr\u{fc}\u{df}e"),
+                WINDOWS_1252
+            ))
+        );
+    }
+
+    #[test]
+    fn decode_rejects_binary_content() {
+        assert_eq!(decode(b"\x7fELF\x02\x01\x01\0", false), None);
+        assert_eq!(decode(b"text\x01with control", false), None);
+        assert_eq!(
+            decode(b"lines\r\n\tand tabs\x0c", false),
+            Some((String::from("lines\r\n\tand tabs\x0c"), UTF_8))
+        );
+    }
+
+    #[test]
+    fn hex_dump_shows_offsets_bytes_and_printable_characters() {
+        let lines = hex_dump(b"Hello, world!\n\0\xffABC");
+        assert_eq!(
+            lines,
+            vec![
+                String::from(
+                    "00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 ff  |Hello, world!...|"
+                ),
+                // Short lines are padded so the printable column stays aligned
+                format!("00000010  41 42 43 {}|ABC|", " ".repeat(41)),
+            ]
+        );
+        assert!(hex_dump(b"").is_empty());
+    }
+}
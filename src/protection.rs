@@ -0,0 +1,123 @@
+use std::path::{Path, PathBuf};
+
+const PROJECT_MARKERS: [&str; 14] = [
+    ".git",
+    ".hg",
+    ".svn",
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "setup.py",
+    "pyvenv.cfg",
+    "go.mod",
+    "pom.xml",
+    "build.gradle",
+    "CMakeLists.txt",
+    "composer.json",
+    "Gemfile",
+];
+
+pub fn default_protected_paths() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = [
+        "/bin",
+        "/boot",
+        "/dev",
+        "/etc",
+        "/lib",
+        "/lib64",
+        "/opt",
+        "/proc",
+        "/sbin",
+        "/sys",
+        "/usr",
+        "/var",
+        "/Applications",
+        "/Library",
+        "/System",
+        "C:/Program Files",
+        "C:/Program Files (x86)",
+        "C:/ProgramData",
+        "C:/Windows",
+    ]
+    .iter()
+    .map(PathBuf::from)
+    .collect();
+    if let Some(home) = dirs::home_dir() {
+        for directory in [".cache", ".config", ".local", ".ssh", ".gnupg", "Library"] {
+            paths.push(home.join(directory));
+        }
+    }
+    paths
+}
+
+#[derive(Debug, Clone)]
+pub struct ProtectedPath {
+    path: PathBuf,
+    reason: String,
+}
+
+impl ProtectedPath {
+    pub fn build(path: &Path, reason: String) -> Self {
+        Self {
+            path: PathBuf::from(path),
+            reason,
+        }
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Protection {
+    protected_paths: Vec<PathBuf>,
+}
+
+impl Protection {
+    pub fn build(protected_paths: &[PathBuf]) -> Self {
+        Self {
+            protected_paths: protected_paths.to_vec(),
+        }
+    }
+
+    // Returns why the directory must not be entered while organizing, if it
+    // is a protected system path or the root of a software project
+    pub fn check_directory(&self, directory: &Path) -> Option<ProtectedPath> {
+        if let Some(protected) = self.check_system_path(directory) {
+            return Some(protected);
+        }
+        find_project_marker(directory).map(|marker| {
+            ProtectedPath::build(directory, format!("Software project, contains {}", marker))
+        })
+    }
+
+    // Checks the path and all of its ancestors, so that nothing inside a
+    // protected path or inside a project is ever touched
+    pub fn check_path(&self, path: &Path) -> Option<ProtectedPath> {
+        path.ancestors()
+            .find_map(|ancestor| self.check_directory(ancestor))
+    }
+
+    pub fn check_system_path(&self, path: &Path) -> Option<ProtectedPath> {
+        self.protected_paths
+            .iter()
+            .find(|protected| path.starts_with(protected))
+            .map(|protected| {
+                ProtectedPath::build(
+                    protected,
+                    format!("Protected system path {}", protected.display()),
+                )
+            })
+    }
+}
+
+pub fn find_project_marker(directory: &Path) -> Option<&'static str> {
+    PROJECT_MARKERS
+        .into_iter()
+        .find(|marker| directory.join(marker).exists())
+}
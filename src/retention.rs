@@ -0,0 +1,383 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    file::FileMetadata,
+    filter::EntryFilter,
+    protection::{ProtectedPath, Protection},
+    rules, scan, util,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyAction {
+    #[default]
+    Trash,
+    Delete,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgeSource {
+    #[default]
+    Modified,
+    Accessed,
+}
+
+// A cleanup policy for one directory, e.g. `{ name = "Downloads", path =
+// "/home/me/Downloads", older_than = "90d", keep_newest = 10, max_size = "2GB",
+// exclude = ["*.keep"] }`. Files matching any of the limits are removed, except
+// for those excluded by the policy or the global ignore patterns, and nothing
+// inside a protected path or a software project is touched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Policy {
+    name: String,
+    path: PathBuf,
+    #[serde(default)]
+    action: PolicyAction,
+    #[serde(default)]
+    older_than: Option<String>,
+    #[serde(default)]
+    age_from: AgeSource,
+    #[serde(default)]
+    keep_newest: Option<usize>,
+    #[serde(default)]
+    max_size: Option<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+impl Policy {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_action(&self) -> PolicyAction {
+        self.action
+    }
+
+    pub fn describe(&self) -> String {
+        let mut limits = Vec::new();
+        if let Some(older_than) = &self.older_than {
+            let age_from = match self.age_from {
+                AgeSource::Modified => "modified",
+                AgeSource::Accessed => "accessed",
+            };
+            limits.push(format!("{} more than {} ago", age_from, older_than));
+        }
+        if let Some(keep_newest) = self.keep_newest {
+            limits.push(format!("beyond newest {} per folder", keep_newest));
+        }
+        if let Some(max_size) = &self.max_size {
+            limits.push(format!("over {} in total", max_size));
+        }
+        if limits.is_empty() {
+            return String::from("No limits");
+        }
+        limits.join(", ")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetentionCandidate {
+    path: PathBuf,
+    size: u64,
+    modified: Option<DateTime<Local>>,
+    reason: String,
+}
+
+impl RetentionCandidate {
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn get_reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+// The dry run of a policy. Applying only removes what the report lists.
+#[derive(Debug, Clone)]
+pub struct RetentionReport {
+    policy: Policy,
+    candidates: Vec<RetentionCandidate>,
+    kept: usize,
+    protected: Vec<ProtectedPath>,
+    errors: Vec<String>,
+}
+
+impl RetentionReport {
+    pub fn get_policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    pub fn get_candidates(&self) -> &Vec<RetentionCandidate> {
+        &self.candidates
+    }
+
+    pub fn get_kept(&self) -> usize {
+        self.kept
+    }
+
+    pub fn get_protected(&self) -> &Vec<ProtectedPath> {
+        &self.protected
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+
+    pub fn get_total_size(&self) -> u64 {
+        self.candidates.iter().map(|candidate| candidate.size).sum()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RetentionResult {
+    removed: usize,
+    freed: u64,
+    errors: Vec<String>,
+}
+
+impl RetentionResult {
+    pub fn get_removed(&self) -> usize {
+        self.removed
+    }
+
+    pub fn get_freed(&self) -> u64 {
+        self.freed
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+}
+
+pub fn create_report(
+    policy: &Policy,
+    ignore_patterns: &[String],
+    protection: &Protection,
+) -> RetentionReport {
+    let mut report = RetentionReport {
+        policy: policy.clone(),
+        candidates: Vec::new(),
+        kept: 0,
+        protected: Vec::new(),
+        errors: Vec::new(),
+    };
+    if !policy.path.is_absolute() || policy.path.parent().is_none() {
+        report.errors.push(format!(
+            "Policy {} needs an absolute directory other than the root: {}",
+            policy.name,
+            policy.path.display()
+        ));
+        return report;
+    }
+    if let Some(protected) = protection.check_path(&policy.path) {
+        report.protected.push(protected);
+        return report;
+    }
+    let age = match &policy.older_than {
+        Some(older_than) => match rules::parse_age(older_than) {
+            Some(age) => Some(age),
+            None => {
+                report.errors.push(format!("Invalid age {}", older_than));
+                return report;
+            }
+        },
+        None => None,
+    };
+    let max_size = match &policy.max_size {
+        Some(max_size) => match util::parse_size(max_size) {
+            Some(max_size) => Some(max_size),
+            None => {
+                report.errors.push(format!("Invalid size {}", max_size));
+                return report;
+            }
+        },
+        None => None,
+    };
+    let entry_filter = match EntryFilter::build(true, ignore_patterns)
+        .and_then(|entry_filter| entry_filter.with_rooted_patterns(&policy.path, &policy.exclude))
+    {
+        Ok(entry_filter) => entry_filter,
+        Err(error) => {
+            report
+                .errors
+                .push(format!("Invalid exclusion pattern: {}", error));
+            return report;
+        }
+    };
+
+    let scan_result = scan::scan(&policy.path, &entry_filter, Some(protection));
+    report
+        .errors
+        .extend(scan_result.get_errors().iter().cloned());
+    report
+        .protected
+        .extend(scan_result.get_protected().iter().cloned());
+    report.protected.extend(
+        scan_result
+            .get_projects()
+            .iter()
+            .filter_map(|project| protection.check_directory(project.get_path())),
+    );
+    // Newest first, so every limit keeps the most recent files
+    let mut files: Vec<(&Path, &FileMetadata)> = scan_result
+        .get_files()
+        .iter()
+        .map(|file| (file.get_path(), file.get_metadata()))
+        .collect();
+    files.sort_by_key(|(_, metadata)| Reverse(metadata.get_modified()));
+
+    let mut reasons: HashMap<&Path, String> = HashMap::new();
+    if let Some(age) = age {
+        let now = Local::now();
+        for (path, metadata) in &files {
+            let date = match policy.age_from {
+                AgeSource::Modified => metadata.get_modified(),
+                AgeSource::Accessed => metadata.get_accessed(),
+            };
+            if date.is_some_and(|date| now - date > age) {
+                reasons.insert(
+                    path,
+                    format!(
+                        "Older than {}",
+                        policy.older_than.as_deref().unwrap_or_default()
+                    ),
+                );
+            }
+        }
+    }
+    if let Some(keep_newest) = policy.keep_newest {
+        let mut counts: HashMap<&Path, usize> = HashMap::new();
+        for (path, _) in &files {
+            let count = counts.entry(path.parent().unwrap_or(path)).or_default();
+            *count += 1;
+            if *count > keep_newest {
+                reasons
+                    .entry(path)
+                    .or_insert_with(|| format!("Beyond newest {} in folder", keep_newest));
+            }
+        }
+    }
+    if let Some(max_size) = max_size {
+        let mut total: u64 = 0;
+        for (path, metadata) in &files {
+            if reasons.contains_key(path) {
+                continue;
+            }
+            total += metadata.get_size();
+            if total > max_size {
+                reasons.insert(
+                    path,
+                    format!("Over {} in total", util::format_size(max_size)),
+                );
+            }
+        }
+    }
+
+    for (path, metadata) in &files {
+        match reasons.remove(path) {
+            Some(reason) => report.candidates.push(RetentionCandidate {
+                path: PathBuf::from(path),
+                size: metadata.get_size(),
+                modified: metadata.get_modified(),
+                reason,
+            }),
+            None => report.kept += 1,
+        }
+    }
+    report
+}
+
+// Files that changed after the report was made are left alone
+pub fn apply_report(report: &RetentionReport) -> RetentionResult {
+    let mut result = RetentionResult::default();
+    for candidate in &report.candidates {
+        let unchanged = fs::symlink_metadata(&candidate.path).is_ok_and(|metadata| {
+            let metadata = FileMetadata::read(&metadata);
+            metadata.get_size() == candidate.size && metadata.get_modified() == candidate.modified
+        });
+        if !unchanged {
+            result.errors.push(format!(
+                "{}: Changed since the preview, kept",
+                candidate.path.display()
+            ));
+            continue;
+        }
+        let removed = match report.policy.action {
+            PolicyAction::Trash => {
+                trash::delete(&candidate.path).map_err(|error| error.to_string())
+            }
+            PolicyAction::Delete => {
+                fs::remove_file(&candidate.path).map_err(|error| error.to_string())
+            }
+        };
+        match removed {
+            Ok(()) => {
+                result.removed += 1;
+                result.freed += candidate.size;
+            }
+            Err(error) => result
+                .errors
+                .push(format!("{}: {}", candidate.path.display(), error)),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_skips_ignored_excluded_and_protected_files() {
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-retention-{}", std::process::id()));
+        fs::create_dir_all(directory.join("tool")).unwrap();
+        for name in ["old.log", "scratch.tmp", "notes.keep", "tool/Cargo.toml"] {
+            fs::write(directory.join(name), "").unwrap();
+        }
+        let policy: Policy = toml::from_str(&format!(
+            "name = \"Test\"\npath = {:?}\nkeep_newest = 0\nexclude = [\"*.keep\"]",
+            directory
+        ))
+        .unwrap();
+
+        let report = create_report(&policy, &[String::from("*.tmp")], &Protection::build(&[]));
+        let candidates: Vec<&Path> = report
+            .get_candidates()
+            .iter()
+            .map(RetentionCandidate::get_path)
+            .collect();
+        assert_eq!(candidates, vec![directory.join("old.log").as_path()]);
+        assert_eq!(report.get_protected().len(), 1);
+        assert_eq!(report.get_protected()[0].get_path(), directory.join("tool"));
+
+        let report = create_report(
+            &policy,
+            &[],
+            &Protection::build(std::slice::from_ref(&directory)),
+        );
+        assert!(report.get_candidates().is_empty());
+        assert_eq!(report.get_protected().len(), 1);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,331 @@
+use std::{collections::HashSet, path::Path};
+
+use chrono::{Local, TimeDelta};
+use globset::{GlobBuilder, GlobMatcher};
+use serde::{Deserialize, Serialize};
+
+use crate::pattern::{self, TokenValue, Tokens};
+
+// A condition on one pattern token, e.g. `{ token = "camera", matches = "*EOS*" }`
+// or `{ token = "modified", older_than = "2y" }`. Without `matches` or
+// `older_than` the token only has to be present.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Condition {
+    token: String,
+    #[serde(default)]
+    matches: Option<String>,
+    #[serde(default)]
+    older_than: Option<String>,
+}
+
+impl Condition {
+    pub fn build(token: &str, matches: Option<&str>) -> Self {
+        Self {
+            token: String::from(token),
+            matches: matches.map(String::from),
+            older_than: None,
+        }
+    }
+}
+
+// A condition with its age and pattern parsed, so they are read once per plan
+// instead of once per file
+struct CheckedCondition<'a> {
+    token: &'a str,
+    age: Option<TimeDelta>,
+    matcher: Option<GlobMatcher>,
+}
+
+impl<'a> CheckedCondition<'a> {
+    fn build(condition: &'a Condition) -> Result<Self, String> {
+        let age = match &condition.older_than {
+            Some(older_than) => {
+                Some(parse_age(older_than).ok_or_else(|| format!("Invalid age {}", older_than))?)
+            }
+            None => None,
+        };
+        let matcher = match &condition.matches {
+            Some(matches) => Some(
+                GlobBuilder::new(matches)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|error| format!("Invalid pattern {}: {}", matches, error))?
+                    .compile_matcher(),
+            ),
+            None => None,
+        };
+        Ok(Self {
+            token: &condition.token,
+            age,
+            matcher,
+        })
+    }
+
+    fn is_met(&self, tokens: &Tokens) -> bool {
+        let Ok(value) = pattern::format_token(self.token, tokens) else {
+            return false;
+        };
+        if let Some(age) = self.age {
+            match tokens.get(self.token) {
+                Some(TokenValue::Date(date)) if Local::now() - *date > age => {}
+                _ => return false,
+            }
+        }
+        self.matcher
+            .as_ref()
+            .is_none_or(|matcher| matcher.is_match(value))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    #[default]
+    Move,
+    Archive,
+}
+
+// The destination is a directory pattern, unless it ends with `.{ext}` in
+// which case its last component is the new name of the file. Archive rules
+// name the archive instead, e.g. `Archives/{modified:%Y-%m}.tar.zst`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    name: String,
+    #[serde(default)]
+    action: RuleAction,
+    #[serde(default)]
+    extensions: Vec<String>,
+    #[serde(default)]
+    conditions: Vec<Condition>,
+    destination: String,
+}
+
+impl Rule {
+    pub fn build(name: &str, extensions: &[&str], destination: &str) -> Self {
+        Self {
+            name: String::from(name),
+            action: RuleAction::Move,
+            extensions: extensions.iter().map(|e| String::from(*e)).collect(),
+            conditions: Vec::new(),
+            destination: String::from(destination),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_action(&self) -> RuleAction {
+        self.action
+    }
+
+    pub fn get_destination(&self) -> &str {
+        &self.destination
+    }
+
+    pub fn with_condition(mut self, condition: Condition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    pub fn renames_file(&self) -> bool {
+        self.destination.ends_with(".{ext}")
+    }
+
+    fn matches_extension(&self, path: &Path) -> bool {
+        if self.extensions.is_empty() {
+            return true;
+        }
+        let Some(extension) = path.extension().and_then(|e| e.to_str()) else {
+            return false;
+        };
+        self.extensions
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(extension))
+    }
+}
+
+pub fn default_rules() -> Vec<Rule> {
+    vec![
+        Rule::build(
+            "Images",
+            &[
+                "jpg", "jpeg", "png", "gif", "heic", "webp", "tif", "tiff", "bmp", "cr2", "nef",
+                "arw", "dng",
+            ],
+            "Images/{exif:%Y}",
+        ),
+        Rule::build(
+            "Videos",
+            &["mp4", "mov", "avi", "mkv", "webm", "m4v"],
+            "Videos/{year}",
+        ),
+        Rule::build(
+            "Tagged music",
+            &["mp3", "flac", "m4a", "ogg", "opus"],
+            "Music/{album_artist}/{album}/{track:02} - {title}.{ext}",
+        )
+        .with_condition(Condition::build("album_artist", None))
+        .with_condition(Condition::build("album", None))
+        .with_condition(Condition::build("track", None))
+        .with_condition(Condition::build("title", None)),
+        Rule::build(
+            "Music",
+            &["mp3", "flac", "m4a", "ogg", "opus", "wav"],
+            "Music",
+        ),
+        Rule::build(
+            "Documents",
+            &[
+                "pdf", "doc", "docx", "odt", "rtf", "txt", "md", "xls", "xlsx", "ods", "ppt",
+                "pptx", "odp",
+            ],
+            "Documents/{ext}",
+        ),
+        Rule::build(
+            "Archives",
+            &["zip", "tar", "gz", "tgz", "zst", "7z", "rar"],
+            "Archives",
+        ),
+    ]
+}
+
+// Ages such as `30d`, `12w`, `6m` or `2y`, where months and years are
+// counted as 30 and 365 days. Ages are positive.
+pub fn parse_age(age: &str) -> Option<TimeDelta> {
+    let age = age.trim();
+    let unit = age.chars().last()?;
+    let amount: i64 = age[..age.len() - unit.len_utf8()].trim().parse().ok()?;
+    if amount <= 0 {
+        return None;
+    }
+    let days = match unit {
+        'd' => amount,
+        'w' => amount.checked_mul(7)?,
+        'm' => amount.checked_mul(30)?,
+        'y' => amount.checked_mul(365)?,
+        _ => return None,
+    };
+    TimeDelta::try_days(days)
+}
+
+// Every token read by the conditions or destinations of the rules
+pub fn get_token_names(rules: &[Rule]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for rule in rules {
+        names.extend(
+            pattern::get_token_names(&rule.destination)
+                .into_iter()
+                .map(String::from),
+        );
+        for condition in &rule.conditions {
+            names.insert(String::from(pattern::split_token(&condition.token).0));
+        }
+    }
+    names
+}
+
+// The rules with their conditions checked, built once for all files of a plan
+pub struct RuleSet<'a> {
+    rules: Vec<(&'a Rule, Vec<CheckedCondition<'a>>)>,
+}
+
+impl<'a> RuleSet<'a> {
+    // Rules with an invalid condition never match and are reported in errors
+    pub fn build(rules: &'a [Rule], errors: &mut Vec<String>) -> Self {
+        let mut checked_rules = Vec::new();
+        for rule in rules {
+            let conditions: Result<Vec<CheckedCondition>, String> = rule
+                .conditions
+                .iter()
+                .map(CheckedCondition::build)
+                .collect();
+            match conditions {
+                Ok(conditions) => checked_rules.push((rule, conditions)),
+                Err(error) => errors.push(format!("Rule {}: {}", rule.name, error)),
+            }
+        }
+        Self {
+            rules: checked_rules,
+        }
+    }
+
+    pub fn find_matching_rule(&self, path: &Path, tokens: &Tokens) -> Option<&'a Rule> {
+        self.rules
+            .iter()
+            .find(|(rule, conditions)| {
+                rule.matches_extension(path)
+                    && conditions.iter().all(|condition| condition.is_met(tokens))
+            })
+            .map(|(rule, _)| *rule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ages_are_counted_in_days() {
+        assert_eq!(parse_age("30d"), TimeDelta::try_days(30));
+        assert_eq!(parse_age("12w"), TimeDelta::try_days(84));
+        assert_eq!(parse_age(" 6m "), TimeDelta::try_days(180));
+        assert_eq!(parse_age("2 y"), TimeDelta::try_days(730));
+    }
+
+    #[test]
+    fn invalid_ages_are_rejected() {
+        assert_eq!(parse_age(""), None);
+        assert_eq!(parse_age("d"), None);
+        assert_eq!(parse_age("30"), None);
+        assert_eq!(parse_age("30h"), None);
+        assert_eq!(parse_age("1.5y"), None);
+        assert_eq!(parse_age("30é"), None);
+        assert_eq!(parse_age("-1d"), None);
+        assert_eq!(parse_age("0w"), None);
+        assert_eq!(parse_age("99999999999999999y"), None);
+        assert_eq!(parse_age("9223372036854775807d"), None);
+    }
+
+    #[test]
+    fn token_names_come_from_destinations_and_conditions() {
+        let rules = [
+            Rule::build("Photos", &["jpg"], "Photos/{exif:%Y}/{camera}"),
+            Rule::build("Old", &[], "Old")
+                .with_condition(Condition::build("modified:%Y", Some("20*"))),
+        ];
+        let mut names: Vec<String> = get_token_names(&rules).into_iter().collect();
+        names.sort();
+        assert_eq!(names, vec!["camera", "exif", "modified"]);
+    }
+
+    #[test]
+    fn rules_with_invalid_conditions_are_reported_once() {
+        let mut old = Rule::build("Old", &[], "Old");
+        old.conditions.push(Condition {
+            token: String::from("modified"),
+            matches: None,
+            older_than: Some(String::from("-1d")),
+        });
+        let rules = [
+            Rule::build("Broken", &[], "Broken")
+                .with_condition(Condition::build("name", Some("[a"))),
+            old,
+            Rule::build("Notes", &["txt"], "Notes")
+                .with_condition(Condition::build("name", Some("NOTE*"))),
+        ];
+        let mut errors = Vec::new();
+        let rule_set = RuleSet::build(&rules, &mut errors);
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors[0].starts_with("Rule Broken: Invalid pattern [a"));
+        assert_eq!(errors[1], "Rule Old: Invalid age -1d");
+
+        let mut tokens = Tokens::new();
+        tokens.insert("name", TokenValue::Text(String::from("notes")));
+        let matching = rule_set.find_matching_rule(Path::new("notes.txt"), &tokens);
+        assert_eq!(matching.map(Rule::get_name), Some("Notes"));
+        assert!(rule_set
+            .find_matching_rule(Path::new("notes.md"), &tokens)
+            .is_none());
+    }
+}
@@ -0,0 +1,279 @@
+use std::{
+    collections::HashSet,
+    fs::{self, Metadata},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    file::FileMetadata,
+    filter::EntryFilter,
+    index::Index,
+    protection::{self, ProtectedPath, Protection},
+};
+
+#[derive(Debug, Clone)]
+pub struct ScannedFile {
+    path: PathBuf,
+    metadata: FileMetadata,
+}
+
+impl ScannedFile {
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_metadata(&self) -> &FileMetadata {
+        &self.metadata
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ScanResult {
+    files: Vec<ScannedFile>,
+    projects: Vec<ScannedFile>,
+    protected: Vec<ProtectedPath>,
+    errors: Vec<String>,
+}
+
+impl ScanResult {
+    pub fn get_files(&self) -> &Vec<ScannedFile> {
+        &self.files
+    }
+
+    // Roots of software projects, which are not entered but can be moved
+    // as a whole
+    pub fn get_projects(&self) -> &Vec<ScannedFile> {
+        &self.projects
+    }
+
+    pub fn get_protected(&self) -> &Vec<ProtectedPath> {
+        &self.protected
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+}
+
+// Walks the tree under root depth first. Ignored entries are skipped and, when
+// protection is given, protected system paths and software projects are
+// reported instead of entered.
+pub fn scan(
+    root: &Path,
+    entry_filter: &EntryFilter,
+    protection: Option<&Protection>,
+) -> ScanResult {
+    let mut result = ScanResult::default();
+    let mut stack: Vec<PathBuf> = vec![PathBuf::from(root)];
+
+    while let Some(directory) = stack.pop() {
+        let entries = match fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(error) => {
+                result
+                    .errors
+                    .push(format!("{}: {}", directory.display(), error));
+                continue;
+            }
+        };
+        let directory_filter = entry_filter.for_directory(&directory);
+
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            if directory_filter.is_ignored(&path, file_type.is_dir()) {
+                continue;
+            }
+
+            if file_type.is_dir() {
+                let Some(protection) = protection else {
+                    stack.push(path);
+                    continue;
+                };
+                if let Some(protected) = protection.check_system_path(&path) {
+                    result.protected.push(protected);
+                } else if protection::find_project_marker(&path).is_some() {
+                    match entry.metadata() {
+                        Ok(metadata) => result.projects.push(ScannedFile {
+                            path,
+                            metadata: FileMetadata::read(&metadata),
+                        }),
+                        Err(error) => result.errors.push(format!("{}: {}", path.display(), error)),
+                    }
+                } else {
+                    stack.push(path);
+                }
+            } else if file_type.is_file() {
+                match entry.metadata() {
+                    Ok(metadata) => result.files.push(ScannedFile {
+                        path,
+                        metadata: FileMetadata::read(&metadata),
+                    }),
+                    Err(error) => result.errors.push(format!("{}: {}", path.display(), error)),
+                }
+            }
+        }
+    }
+    result
+}
+
+// Scans root like `scan` while bringing its index up to date, so directories
+// that did not change since they were last read with entry_filter are not
+// read again. Protected paths and projects are still walked by the index but
+// left out of the result.
+pub fn scan_indexed(
+    root: &Path,
+    entry_filter: &EntryFilter,
+    protection: Option<&Protection>,
+    index: &mut Index,
+) -> ScanResult {
+    let mut result = ScanResult::default();
+    let mut skipped: HashSet<PathBuf> = HashSet::new();
+    let update = index.update_visiting(root, entry_filter, |path, metadata| {
+        if path
+            .ancestors()
+            .skip(1)
+            .any(|ancestor| skipped.contains(ancestor))
+        {
+            return;
+        }
+        if metadata.is_dir() {
+            let Some(protection) = protection else {
+                return;
+            };
+            if let Some(protected) = protection.check_system_path(path) {
+                result.protected.push(protected);
+                skipped.insert(PathBuf::from(path));
+            } else if protection::find_project_marker(path).is_some() {
+                result.projects.push(ScannedFile {
+                    path: PathBuf::from(path),
+                    metadata: FileMetadata::read(metadata),
+                });
+                skipped.insert(PathBuf::from(path));
+            }
+        } else {
+            result.files.push(ScannedFile {
+                path: PathBuf::from(path),
+                metadata: FileMetadata::read(metadata),
+            });
+        }
+    });
+    result.errors.extend(update.get_errors().iter().cloned());
+    result
+}
+
+// Reads the given files as if they had been scanned. Files inside protected
+// paths are reported instead.
+pub fn read_files(paths: &[PathBuf], protection: &Protection) -> ScanResult {
+    let mut result = ScanResult::default();
+    for path in paths {
+        if let Some(protected) = protection.check_path(path) {
+            result.protected.push(protected);
+            continue;
+        }
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_file() => result.files.push(ScannedFile {
+                path: PathBuf::from(path),
+                metadata: FileMetadata::read(&metadata),
+            }),
+            Ok(_) => result
+                .errors
+                .push(format!("{}: Not a file", path.display())),
+            Err(error) => result.errors.push(format!("{}: {}", path.display(), error)),
+        }
+    }
+    result
+}
+
+// Walks the tree under root depth first like `scan`, handing every directory
+// and file to visit instead of collecting them. The walk stops when visit
+// returns false.
+pub fn walk(
+    root: &Path,
+    entry_filter: &EntryFilter,
+    errors: &mut Vec<String>,
+    mut visit: impl FnMut(&Path, &Metadata) -> bool,
+) {
+    let mut stack: Vec<PathBuf> = vec![PathBuf::from(root)];
+
+    while let Some(directory) = stack.pop() {
+        let entries = match fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(error) => {
+                errors.push(format!("{}: {}", directory.display(), error));
+                continue;
+            }
+        };
+        let directory_filter = entry_filter.for_directory(&directory);
+
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let path = entry.path();
+            if !metadata.is_dir() && !metadata.is_file() {
+                continue;
+            }
+            if directory_filter.is_ignored(&path, metadata.is_dir()) {
+                continue;
+            }
+            if !visit(&path, &metadata) {
+                return;
+            }
+            if metadata.is_dir() {
+                stack.push(path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_paths(files: &[ScannedFile], root: &Path) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = files
+            .iter()
+            .map(|file| PathBuf::from(file.get_path().strip_prefix(root).unwrap()))
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn indexed_scan_matches_scan() {
+        let directory = std::env::temp_dir().join(format!("fileorder-scan-{}", std::process::id()));
+        let root = directory.join("tree");
+        fs::create_dir_all(root.join("photos")).unwrap();
+        fs::create_dir_all(root.join("tool/src")).unwrap();
+        for name in [
+            "notes.txt",
+            "photos/a.jpg",
+            "tool/Cargo.toml",
+            "tool/src/main.rs",
+        ] {
+            fs::write(root.join(name), "").unwrap();
+        }
+        let mut index = Index::open_path(&directory.join("index.sqlite")).unwrap();
+        let entry_filter = EntryFilter::new();
+        let protection = Protection::build(&[]);
+
+        let scanned = scan(&root, &entry_filter, Some(&protection));
+        for _ in 0..2 {
+            let indexed = scan_indexed(&root, &entry_filter, Some(&protection), &mut index);
+            assert!(indexed.get_errors().is_empty());
+            assert_eq!(
+                get_paths(indexed.get_files(), &root),
+                get_paths(scanned.get_files(), &root)
+            );
+            assert_eq!(
+                get_paths(indexed.get_projects(), &root),
+                vec![PathBuf::from("tool")]
+            );
+        }
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
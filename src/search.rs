@@ -0,0 +1,287 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use globset::{GlobBuilder, GlobMatcher};
+use iced::futures::channel::mpsc::{self, UnboundedReceiver};
+use regex::{Regex, RegexBuilder};
+
+use crate::{file::FileMetadata, filter::EntryFilter, index::Index, scan, util};
+
+const BATCH_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_RESULTS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchField {
+    Name,
+    MinSize,
+    MaxSize,
+    ModifiedFrom,
+    ModifiedTo,
+}
+
+// The search form as typed. Every field is optional.
+#[derive(Debug, Clone, Default)]
+pub struct SearchQuery {
+    name: String,
+    use_regex: bool,
+    use_index: bool,
+    min_size: String,
+    max_size: String,
+    modified_from: String,
+    modified_to: String,
+}
+
+impl SearchQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, field: SearchField) -> &str {
+        match field {
+            SearchField::Name => &self.name,
+            SearchField::MinSize => &self.min_size,
+            SearchField::MaxSize => &self.max_size,
+            SearchField::ModifiedFrom => &self.modified_from,
+            SearchField::ModifiedTo => &self.modified_to,
+        }
+    }
+
+    pub fn set(&mut self, field: SearchField, value: String) {
+        match field {
+            SearchField::Name => self.name = value,
+            SearchField::MinSize => self.min_size = value,
+            SearchField::MaxSize => self.max_size = value,
+            SearchField::ModifiedFrom => self.modified_from = value,
+            SearchField::ModifiedTo => self.modified_to = value,
+        }
+    }
+
+    pub fn get_use_regex(&self) -> bool {
+        self.use_regex
+    }
+
+    pub fn set_use_regex(&mut self, use_regex: bool) {
+        self.use_regex = use_regex;
+    }
+
+    pub fn get_use_index(&self) -> bool {
+        self.use_index
+    }
+
+    pub fn set_use_index(&mut self, use_index: bool) {
+        self.use_index = use_index;
+    }
+}
+
+#[derive(Debug, Clone)]
+enum NameMatcher {
+    Any,
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchCriteria {
+    name: NameMatcher,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    modified_from: Option<DateTime<Local>>,
+    modified_to: Option<DateTime<Local>>,
+    use_index: bool,
+}
+
+impl SearchCriteria {
+    // Names are matched case-insensitively. A name without glob characters
+    // matches anywhere in the file name.
+    pub fn build(query: &SearchQuery) -> Result<Self, String> {
+        let name = query.name.trim();
+        let name = if name.is_empty() {
+            NameMatcher::Any
+        } else if query.use_regex {
+            let regex = RegexBuilder::new(name)
+                .case_insensitive(true)
+                .build()
+                .map_err(|error| error.to_string())?;
+            NameMatcher::Regex(regex)
+        } else {
+            let pattern = if name.contains(['*', '?', '[', '{']) {
+                String::from(name)
+            } else {
+                format!("*{}*", name)
+            };
+            let glob = GlobBuilder::new(&pattern)
+                .case_insensitive(true)
+                .build()
+                .map_err(|error| error.to_string())?;
+            NameMatcher::Glob(glob.compile_matcher())
+        };
+        Ok(Self {
+            name,
+            min_size: parse_optional(&query.min_size, util::parse_size, "size")?,
+            max_size: parse_optional(&query.max_size, util::parse_size, "size")?,
+            modified_from: parse_optional(
+                &query.modified_from,
+                |date| parse_date(date, 0),
+                "date",
+            )?,
+            modified_to: parse_optional(&query.modified_to, |date| parse_date(date, 1), "date")?,
+            use_index: query.use_index,
+        })
+    }
+
+    // Size limits only apply to files, so setting one leaves directories out
+    fn matches(&self, name: &str, metadata: &FileMetadata, is_dir: bool) -> bool {
+        let name_matches = match &self.name {
+            NameMatcher::Any => true,
+            NameMatcher::Glob(glob) => glob.is_match(name),
+            NameMatcher::Regex(regex) => regex.is_match(name),
+        };
+        if !name_matches {
+            return false;
+        }
+        if is_dir && (self.min_size.is_some() || self.max_size.is_some()) {
+            return false;
+        }
+        let size = metadata.get_size();
+        if self.min_size.is_some_and(|min_size| size < min_size)
+            || self.max_size.is_some_and(|max_size| size > max_size)
+        {
+            return false;
+        }
+        let modified = metadata.get_modified();
+        if let Some(modified_from) = self.modified_from {
+            if modified.is_none_or(|modified| modified < modified_from) {
+                return false;
+            }
+        }
+        if let Some(modified_to) = self.modified_to {
+            if modified.is_none_or(|modified| modified >= modified_to) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    path: PathBuf,
+    is_dir: bool,
+    metadata: FileMetadata,
+}
+
+impl SearchResult {
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    pub fn get_metadata(&self) -> &FileMetadata {
+        &self.metadata
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SearchEvent {
+    Found(Vec<SearchResult>),
+    Finished(Vec<String>),
+}
+
+// Searches on its own thread and sends the results in batches. Setting
+// cancelled stops the search. With the index, entries are read from it
+// instead of the disk.
+pub fn start(
+    root: PathBuf,
+    criteria: SearchCriteria,
+    entry_filter: EntryFilter,
+    cancelled: Arc<AtomicBool>,
+) -> UnboundedReceiver<SearchEvent> {
+    let (sender, receiver) = mpsc::unbounded();
+    thread::spawn(move || {
+        let mut errors = Vec::new();
+        let mut batch = Vec::new();
+        let mut last_sent = Instant::now();
+        let mut found = 0;
+        let mut visit = |path: &Path, is_dir: bool, file_metadata: FileMetadata| {
+            if cancelled.load(Ordering::Relaxed) {
+                return false;
+            }
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy())
+                .unwrap_or_default();
+            if criteria.matches(&name, &file_metadata, is_dir) {
+                batch.push(SearchResult {
+                    path: PathBuf::from(path),
+                    is_dir,
+                    metadata: file_metadata,
+                });
+                found += 1;
+            }
+            if !batch.is_empty() && last_sent.elapsed() >= BATCH_INTERVAL {
+                last_sent = Instant::now();
+                if sender
+                    .unbounded_send(SearchEvent::Found(std::mem::take(&mut batch)))
+                    .is_err()
+                {
+                    return false;
+                }
+            }
+            found < MAX_RESULTS
+        };
+        if criteria.use_index {
+            if let Err(error) =
+                Index::open().and_then(|index| index.visit(&root, &entry_filter, &mut visit))
+            {
+                errors.push(error);
+            }
+        } else {
+            scan::walk(&root, &entry_filter, &mut errors, |path, metadata| {
+                visit(path, metadata.is_dir(), FileMetadata::read(metadata))
+            });
+        }
+        if found >= MAX_RESULTS {
+            errors.push(format!("Stopped after {} results", MAX_RESULTS));
+        }
+        if !batch.is_empty() {
+            let _ = sender.unbounded_send(SearchEvent::Found(batch));
+        }
+        let _ = sender.unbounded_send(SearchEvent::Finished(errors));
+    });
+    receiver
+}
+
+fn parse_optional<T>(
+    value: &str,
+    parse: impl Fn(&str) -> Option<T>,
+    kind: &str,
+) -> Result<Option<T>, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    parse(value)
+        .map(Some)
+        .ok_or_else(|| format!("Invalid {}: {}", kind, value))
+}
+
+// Dates are whole days, so the end of a range is the start of the next day
+fn parse_date(date: &str, days_after: u64) -> Option<DateTime<Local>> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()?
+        .checked_add_days(chrono::Days::new(days_after))?;
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .earliest()
+}
@@ -0,0 +1,803 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    io::{BufRead, BufReader, BufWriter, Error, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    export::{self, ExportFormat},
+    filter::EntryFilter,
+    index, scan,
+};
+
+const SNAPSHOT_DIRECTORY: &str = "fileorder";
+const SNAPSHOTS: &str = "snapshots";
+const SNAPSHOT_EXTENSION: &str = "jsonl";
+
+// What was left out when a snapshot was taken. The live tree is read the same
+// way, so files hidden or ignored since then are not reported as removed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotFilter {
+    show_hidden: bool,
+    ignore_patterns: Vec<String>,
+}
+
+impl SnapshotFilter {
+    pub fn build(show_hidden: bool, ignore_patterns: &[String]) -> Self {
+        Self {
+            show_hidden,
+            ignore_patterns: ignore_patterns.to_vec(),
+        }
+    }
+
+    fn build_entry_filter(&self) -> Result<EntryFilter, Error> {
+        EntryFilter::build(self.show_hidden, &self.ignore_patterns)
+    }
+}
+
+// The first line of a snapshot file. Every following line is one file.
+// Snapshots from before the filter was recorded have none.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SnapshotHeader {
+    root: PathBuf,
+    created: String,
+    hashed: bool,
+    files: usize,
+    #[serde(default)]
+    filter: Option<SnapshotFilter>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SnapshotEntry {
+    path: String,
+    size: u64,
+    modified: Option<i64>,
+    device: Option<u64>,
+    inode: Option<u64>,
+    hash: Option<String>,
+}
+
+impl SnapshotEntry {
+    fn is_modified(&self, other: &SnapshotEntry) -> bool {
+        self.size != other.size
+            || self.modified != other.modified
+            || matches!((&self.hash, &other.hash), (Some(hash), Some(other)) if hash != other)
+    }
+
+    // A file keeps its inode when it is moved on the same device. Otherwise
+    // it is recognized by its content.
+    fn is_same_file(&self, other: &SnapshotEntry) -> bool {
+        if self.size != other.size {
+            return false;
+        }
+        let same_inode =
+            self.inode.is_some() && self.inode == other.inode && self.device == other.device;
+        let same_hash = self.hash.is_some() && self.hash == other.hash;
+        same_inode || same_hash
+    }
+}
+
+struct Snapshot {
+    root: PathBuf,
+    hashed: bool,
+    filter: Option<SnapshotFilter>,
+    entries: Vec<SnapshotEntry>,
+}
+
+// A saved snapshot as listed, without its entries
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotInfo {
+    path: PathBuf,
+    root: PathBuf,
+    created: DateTime<Local>,
+    hashed: bool,
+    files: usize,
+    filter: Option<SnapshotFilter>,
+}
+
+impl SnapshotInfo {
+    pub fn get_root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn get_created(&self) -> DateTime<Local> {
+        self.created
+    }
+
+    pub fn is_hashed(&self) -> bool {
+        self.hashed
+    }
+
+    pub fn get_files(&self) -> usize {
+        self.files
+    }
+}
+
+impl fmt::Display for SnapshotInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            self.created.format("%Y-%m-%d %H:%M"),
+            self.root.display()
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotChoice {
+    Live,
+    Saved(SnapshotInfo),
+}
+
+impl fmt::Display for SnapshotChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotChoice::Live => write!(f, "Live tree"),
+            SnapshotChoice::Saved(info) => info.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotResult {
+    info: Option<SnapshotInfo>,
+    errors: Vec<String>,
+}
+
+impl SnapshotResult {
+    pub fn get_info(&self) -> &Option<SnapshotInfo> {
+        &self.info
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+    Moved,
+}
+
+impl ChangeKind {
+    pub const ALL: [ChangeKind; 4] = [
+        ChangeKind::Added,
+        ChangeKind::Removed,
+        ChangeKind::Modified,
+        ChangeKind::Moved,
+    ];
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Moved => "moved",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+// Paths are relative to the roots of the compared snapshots
+#[derive(Debug, Clone, Serialize)]
+pub struct Change {
+    kind: ChangeKind,
+    path: String,
+    previous_path: Option<String>,
+    old_size: Option<u64>,
+    new_size: Option<u64>,
+}
+
+impl Change {
+    pub fn get_kind(&self) -> ChangeKind {
+        self.kind
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn get_previous_path(&self) -> Option<&str> {
+        self.previous_path.as_deref()
+    }
+
+    pub fn get_old_size(&self) -> Option<u64> {
+        self.old_size
+    }
+
+    pub fn get_new_size(&self) -> Option<u64> {
+        self.new_size
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SnapshotDiff {
+    old: String,
+    new: String,
+    changes: Vec<Change>,
+    #[serde(skip)]
+    errors: Vec<String>,
+}
+
+impl SnapshotDiff {
+    pub fn get_old(&self) -> &str {
+        &self.old
+    }
+
+    pub fn get_new(&self) -> &str {
+        &self.new
+    }
+
+    pub fn get_changes(&self) -> &Vec<Change> {
+        &self.changes
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+
+    pub fn get_count(&self, kind: ChangeKind) -> usize {
+        self.changes
+            .iter()
+            .filter(|change| change.kind == kind)
+            .count()
+    }
+}
+
+// Records every file under root. Hashing reads every file, but finds moves
+// across devices and changes that kept the size and modification time.
+pub fn save_snapshot(root: &Path, filter: SnapshotFilter, hashed: bool) -> SnapshotResult {
+    let mut result = SnapshotResult::default();
+    let entry_filter = match filter.build_entry_filter() {
+        Ok(entry_filter) => entry_filter,
+        Err(error) => {
+            result.errors.push(error.to_string());
+            return result;
+        }
+    };
+    let mut snapshot = capture(root, &entry_filter, hashed, &mut result.errors);
+    snapshot.filter = Some(filter);
+    match write_snapshot(&snapshot) {
+        Ok(info) => result.info = Some(info),
+        Err(error) => result.errors.push(error.to_string()),
+    }
+    result
+}
+
+// Newest first. Only the header line of each file is read.
+pub fn list_snapshots() -> (Vec<SnapshotInfo>, Vec<String>) {
+    let mut snapshots = Vec::new();
+    let mut errors = Vec::new();
+    let Some(directory) = get_snapshot_directory() else {
+        return (snapshots, errors);
+    };
+    let entries = match fs::read_dir(&directory) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return (snapshots, errors),
+        Err(error) => {
+            errors.push(format!("{}: {}", directory.display(), error));
+            return (snapshots, errors);
+        }
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path
+            .extension()
+            .is_none_or(|extension| extension != SNAPSHOT_EXTENSION)
+        {
+            continue;
+        }
+        match read_info(&path) {
+            Ok(info) => snapshots.push(info),
+            Err(error) => errors.push(format!("{}: {}", path.display(), error)),
+        }
+    }
+    snapshots.sort_by_key(|snapshot| Reverse(snapshot.created));
+    (snapshots, errors)
+}
+
+// The live tree is read from the root of the saved snapshot it is compared
+// with, hashed when that snapshot is and with the same filter. The given
+// filter is only used for snapshots that did not record theirs.
+pub fn compare(
+    old: &SnapshotChoice,
+    new: &SnapshotChoice,
+    entry_filter: &EntryFilter,
+) -> SnapshotDiff {
+    let mut diff = SnapshotDiff {
+        old: old.to_string(),
+        new: new.to_string(),
+        ..SnapshotDiff::default()
+    };
+    let saved = match (old, new) {
+        (SnapshotChoice::Saved(info), _) | (_, SnapshotChoice::Saved(info)) => info,
+        _ => {
+            diff.errors
+                .push(String::from("Choose a saved snapshot to compare"));
+            return diff;
+        }
+    };
+    if let (SnapshotChoice::Saved(old_info), SnapshotChoice::Saved(new_info)) = (old, new) {
+        if old_info.root != new_info.root {
+            diff.errors.push(format!(
+                "The snapshots are of different folders, {} and {}",
+                old_info.root.display(),
+                new_info.root.display()
+            ));
+            return diff;
+        }
+        if old_info.filter != new_info.filter {
+            diff.errors.push(String::from(
+                "The snapshots were taken with different hidden files or ignore patterns, \
+                 so some files may show as added or removed",
+            ));
+        }
+    }
+    let saved_filter = match saved
+        .filter
+        .as_ref()
+        .map(SnapshotFilter::build_entry_filter)
+    {
+        Some(Ok(entry_filter)) => Some(entry_filter),
+        Some(Err(error)) => {
+            diff.errors.push(error.to_string());
+            return diff;
+        }
+        None => None,
+    };
+    let live_filter = saved_filter.as_ref().unwrap_or(entry_filter);
+    let mut load = |choice: &SnapshotChoice| match choice {
+        SnapshotChoice::Live => Some(capture(
+            &saved.root,
+            live_filter,
+            saved.hashed,
+            &mut diff.errors,
+        )),
+        SnapshotChoice::Saved(info) => read_snapshot(&info.path)
+            .inspect_err(|error| {
+                diff.errors
+                    .push(format!("{}: {}", info.path.display(), error))
+            })
+            .ok(),
+    };
+    let (Some(old), Some(new)) = (load(old), load(new)) else {
+        return diff;
+    };
+    diff.changes = find_changes(&old, &new);
+    diff
+}
+
+// JSON keeps the compared snapshots, the other formats list the changes
+pub fn export_diff(diff: &SnapshotDiff, format: ExportFormat) -> Result<PathBuf, String> {
+    let path = export::get_export_path("changes", format)?;
+    let result = match format {
+        ExportFormat::Json => export::write_json(&path, diff),
+        _ => {
+            let title = format!("Changes from {} to {}", diff.old, diff.new);
+            export::write_records(&path, format, &title, &diff.changes)
+        }
+    };
+    result.map(|_| path).map_err(|error| error.to_string())
+}
+
+fn capture(
+    root: &Path,
+    entry_filter: &EntryFilter,
+    hashed: bool,
+    errors: &mut Vec<String>,
+) -> Snapshot {
+    let mut entries = Vec::new();
+    let mut hash_errors = Vec::new();
+    scan::walk(root, entry_filter, errors, |path, metadata| {
+        if !metadata.is_file() {
+            return true;
+        }
+        let hash = if hashed {
+            match index::hash_file(path) {
+                Ok(hash) => Some(hash),
+                Err(error) => {
+                    hash_errors.push(format!("{}: {}", path.display(), error));
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let (device, inode) = index::get_file_id(metadata);
+        entries.push(SnapshotEntry {
+            path: path
+                .strip_prefix(root)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .to_string(),
+            size: metadata.len(),
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_nanos() as i64),
+            device,
+            inode,
+            hash,
+        });
+        true
+    });
+    errors.extend(hash_errors);
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Snapshot {
+        root: PathBuf::from(root),
+        hashed,
+        filter: None,
+        entries,
+    }
+}
+
+// Files only in the new snapshot that match a file only in the old one were
+// moved. The removed files are looked up by inode and by content.
+fn find_changes(old: &Snapshot, new: &Snapshot) -> Vec<Change> {
+    let old_entries: HashMap<&str, &SnapshotEntry> = old
+        .entries
+        .iter()
+        .map(|entry| (entry.path.as_str(), entry))
+        .collect();
+    let new_paths: HashSet<&str> = new
+        .entries
+        .iter()
+        .map(|entry| entry.path.as_str())
+        .collect();
+    let removed: Vec<&SnapshotEntry> = old
+        .entries
+        .iter()
+        .filter(|entry| !new_paths.contains(entry.path.as_str()))
+        .collect();
+    let mut is_moved = vec![false; removed.len()];
+    let mut by_inode: HashMap<(u64, u64), Vec<usize>> = HashMap::new();
+    let mut by_hash: HashMap<(u64, &str), Vec<usize>> = HashMap::new();
+    for (position, entry) in removed.iter().enumerate() {
+        if let Some(file_id) = entry.device.zip(entry.inode) {
+            by_inode.entry(file_id).or_default().push(position);
+        }
+        if let Some(hash) = &entry.hash {
+            by_hash
+                .entry((entry.size, hash.as_str()))
+                .or_default()
+                .push(position);
+        }
+    }
+
+    let mut changes = Vec::new();
+    for entry in &new.entries {
+        let change = match old_entries.get(entry.path.as_str()) {
+            Some(old_entry) if old_entry.is_modified(entry) => Change {
+                kind: ChangeKind::Modified,
+                path: entry.path.clone(),
+                previous_path: None,
+                old_size: Some(old_entry.size),
+                new_size: Some(entry.size),
+            },
+            Some(_) => continue,
+            None => {
+                let same_inode = entry
+                    .device
+                    .zip(entry.inode)
+                    .and_then(|file_id| by_inode.get(&file_id));
+                let same_hash = entry
+                    .hash
+                    .as_deref()
+                    .and_then(|hash| by_hash.get(&(entry.size, hash)));
+                let position = same_inode
+                    .into_iter()
+                    .chain(same_hash)
+                    .flatten()
+                    .copied()
+                    .find(|&position| !is_moved[position] && removed[position].is_same_file(entry));
+                match position {
+                    Some(position) => {
+                        is_moved[position] = true;
+                        let old_entry = removed[position];
+                        Change {
+                            kind: ChangeKind::Moved,
+                            path: entry.path.clone(),
+                            previous_path: Some(old_entry.path.clone()),
+                            old_size: Some(old_entry.size),
+                            new_size: Some(entry.size),
+                        }
+                    }
+                    None => Change {
+                        kind: ChangeKind::Added,
+                        path: entry.path.clone(),
+                        previous_path: None,
+                        old_size: None,
+                        new_size: Some(entry.size),
+                    },
+                }
+            }
+        };
+        changes.push(change);
+    }
+    for (old_entry, _) in removed
+        .iter()
+        .zip(&is_moved)
+        .filter(|(_, is_moved)| !**is_moved)
+    {
+        changes.push(Change {
+            kind: ChangeKind::Removed,
+            path: old_entry.path.clone(),
+            previous_path: None,
+            old_size: Some(old_entry.size),
+            new_size: None,
+        });
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    changes
+}
+
+fn write_snapshot(snapshot: &Snapshot) -> Result<SnapshotInfo, Error> {
+    let directory =
+        get_snapshot_directory().ok_or_else(|| Error::other("Could not find data directory"))?;
+    fs::create_dir_all(&directory)?;
+    let created = Local::now();
+    let path = directory.join(format!(
+        "{}.{}",
+        created.format("%Y%m%d-%H%M%S%3f"),
+        SNAPSHOT_EXTENSION
+    ));
+    let header = SnapshotHeader {
+        root: snapshot.root.clone(),
+        created: created.to_rfc3339(),
+        hashed: snapshot.hashed,
+        files: snapshot.entries.len(),
+        filter: snapshot.filter.clone(),
+    };
+    // Written under a name that is not listed until it is complete
+    let temporary_path = path.with_extension(format!("{}.tmp", std::process::id()));
+    let written = write_entries(&temporary_path, &header, &snapshot.entries)
+        .and_then(|_| fs::rename(&temporary_path, &path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary_path);
+    }
+    written?;
+    Ok(SnapshotInfo {
+        path,
+        root: header.root,
+        created,
+        hashed: header.hashed,
+        files: header.files,
+        filter: header.filter,
+    })
+}
+
+fn write_entries(
+    path: &Path,
+    header: &SnapshotHeader,
+    entries: &[SnapshotEntry],
+) -> Result<(), Error> {
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    serde_json::to_writer(&mut writer, header)?;
+    writeln!(writer)?;
+    for entry in entries {
+        serde_json::to_writer(&mut writer, entry)?;
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+    writer.get_ref().sync_all()
+}
+
+fn read_info(path: &Path) -> Result<SnapshotInfo, Error> {
+    let mut line = String::new();
+    BufReader::new(fs::File::open(path)?).read_line(&mut line)?;
+    parse_header(path, &line)
+}
+
+fn parse_header(path: &Path, line: &str) -> Result<SnapshotInfo, Error> {
+    let header: SnapshotHeader = serde_json::from_str(line)?;
+    let created = DateTime::parse_from_rfc3339(&header.created)
+        .map_err(Error::other)?
+        .with_timezone(&Local);
+    Ok(SnapshotInfo {
+        path: PathBuf::from(path),
+        root: header.root,
+        created,
+        hashed: header.hashed,
+        files: header.files,
+        filter: header.filter,
+    })
+}
+
+fn read_snapshot(path: &Path) -> Result<Snapshot, Error> {
+    let mut lines = BufReader::new(fs::File::open(path)?).lines();
+    let header = lines
+        .next()
+        .ok_or_else(|| Error::other("Empty snapshot"))??;
+    let info = parse_header(path, &header)?;
+    let mut entries = Vec::new();
+    for line in lines {
+        entries.push(serde_json::from_str(&line?)?);
+    }
+    if entries.len() != info.files {
+        return Err(Error::other(format!(
+            "Incomplete snapshot, {} of {} files",
+            entries.len(),
+            info.files
+        )));
+    }
+    Ok(Snapshot {
+        root: info.root,
+        hashed: info.hashed,
+        filter: info.filter,
+        entries,
+    })
+}
+
+fn get_snapshot_directory() -> Option<PathBuf> {
+    dirs::data_dir().map(|directory| directory.join(SNAPSHOT_DIRECTORY).join(SNAPSHOTS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, size: u64, inode: Option<u64>, hash: Option<&str>) -> SnapshotEntry {
+        SnapshotEntry {
+            path: String::from(path),
+            size,
+            modified: Some(1),
+            device: inode.map(|_| 1),
+            inode,
+            hash: hash.map(String::from),
+        }
+    }
+
+    fn snapshot(entries: Vec<SnapshotEntry>) -> Snapshot {
+        Snapshot {
+            root: PathBuf::from("/data"),
+            hashed: true,
+            filter: None,
+            entries,
+        }
+    }
+
+    fn describe(changes: &[Change]) -> Vec<(ChangeKind, &str, Option<&str>)> {
+        changes
+            .iter()
+            .map(|change| {
+                (
+                    change.get_kind(),
+                    change.get_path(),
+                    change.get_previous_path(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn find_changes_detects_every_kind() {
+        let old = snapshot(vec![
+            entry("kept.txt", 1, Some(1), Some("a")),
+            entry("edited.txt", 2, Some(2), Some("b")),
+            entry("renamed.txt", 3, Some(3), Some("c")),
+            entry("copied.txt", 4, None, Some("d")),
+            entry("deleted.txt", 5, Some(5), Some("e")),
+        ]);
+        let new = snapshot(vec![
+            entry("kept.txt", 1, Some(1), Some("a")),
+            entry("edited.txt", 2, Some(2), Some("x")),
+            entry("moved/renamed.txt", 3, Some(3), Some("c")),
+            entry("other/copied.txt", 4, Some(40), Some("d")),
+            entry("created.txt", 6, Some(6), Some("f")),
+        ]);
+        assert_eq!(
+            describe(&find_changes(&old, &new)),
+            vec![
+                (ChangeKind::Added, "created.txt", None),
+                (ChangeKind::Removed, "deleted.txt", None),
+                (ChangeKind::Modified, "edited.txt", None),
+                (ChangeKind::Moved, "moved/renamed.txt", Some("renamed.txt")),
+                (ChangeKind::Moved, "other/copied.txt", Some("copied.txt")),
+            ]
+        );
+    }
+
+    #[test]
+    fn find_changes_matches_each_removed_file_once() {
+        let old = snapshot(vec![
+            entry("a.txt", 1, None, Some("same")),
+            entry("b.txt", 1, None, Some("same")),
+        ]);
+        let new = snapshot(vec![
+            entry("c.txt", 1, None, Some("same")),
+            entry("d.txt", 1, None, Some("same")),
+            entry("e.txt", 1, None, Some("same")),
+            entry("f.txt", 2, None, Some("same")),
+        ]);
+        let changes = find_changes(&old, &new);
+        let kinds: Vec<ChangeKind> = changes.iter().map(Change::get_kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ChangeKind::Moved,
+                ChangeKind::Moved,
+                ChangeKind::Added,
+                ChangeKind::Added
+            ]
+        );
+        let mut previous: Vec<&str> = changes
+            .iter()
+            .filter_map(Change::get_previous_path)
+            .collect();
+        previous.sort();
+        assert_eq!(previous, vec!["a.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn compare_refuses_snapshots_of_different_folders() {
+        let info = |root: &str| SnapshotInfo {
+            path: PathBuf::from("/nonexistent.jsonl"),
+            root: PathBuf::from(root),
+            created: Local::now(),
+            hashed: false,
+            files: 0,
+            filter: None,
+        };
+        let diff = compare(
+            &SnapshotChoice::Saved(info("/data")),
+            &SnapshotChoice::Saved(info("/other")),
+            &EntryFilter::new(),
+        );
+        assert!(diff.get_changes().is_empty());
+        assert_eq!(diff.get_errors().len(), 1);
+        assert!(diff.get_errors()[0].contains("different folders"));
+    }
+
+    #[test]
+    fn live_tree_is_read_with_the_filter_of_the_snapshot() {
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-snapshot-{}", std::process::id()));
+        let root = directory.join("tree");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join(".hidden"), "").unwrap();
+        fs::write(root.join("shown.txt"), "").unwrap();
+        let filter = SnapshotFilter::build(true, &[]);
+        let mut errors = Vec::new();
+        let mut snapshot = capture(
+            &root,
+            &filter.build_entry_filter().unwrap(),
+            false,
+            &mut errors,
+        );
+        snapshot.filter = Some(filter);
+        assert_eq!(snapshot.entries.len(), 2);
+        let header = SnapshotHeader {
+            root: root.clone(),
+            created: Local::now().to_rfc3339(),
+            hashed: false,
+            files: snapshot.entries.len(),
+            filter: snapshot.filter.clone(),
+        };
+        let path = directory.join("snapshot.jsonl");
+        write_entries(&path, &header, &snapshot.entries).unwrap();
+
+        let saved = SnapshotChoice::Saved(read_info(&path).unwrap());
+        let diff = compare(&saved, &SnapshotChoice::Live, &EntryFilter::new());
+        assert!(diff.get_errors().is_empty(), "{:?}", diff.get_errors());
+        assert!(diff.get_changes().is_empty());
+
+        write_entries(&path, &header, &snapshot.entries[..1]).unwrap();
+        assert!(read_snapshot(&path).is_err());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
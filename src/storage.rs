@@ -0,0 +1,254 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{index, util};
+
+// Space of the filesystem a path is on. Available is what the current user
+// can still write, which may be less than what is free.
+#[derive(Debug, Clone)]
+pub struct SpaceInfo {
+    path: PathBuf,
+    mount_point: Option<PathBuf>,
+    filesystem: Option<String>,
+    total: u64,
+    used: u64,
+    available: u64,
+    read_only: bool,
+}
+
+impl SpaceInfo {
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_mount_point(&self) -> &Option<PathBuf> {
+        &self.mount_point
+    }
+
+    pub fn get_used(&self) -> u64 {
+        self.used
+    }
+
+    pub fn get_available(&self) -> u64 {
+        self.available
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn get_used_fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.used as f32 / self.total as f32
+    }
+}
+
+impl fmt::Display for SpaceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} free of {}",
+            util::format_size(self.available),
+            util::format_size(self.total)
+        )?;
+        if let Some(filesystem) = &self.filesystem {
+            write!(f, ", {}", filesystem)?;
+        }
+        if self.read_only {
+            write!(f, ", read-only")?;
+        }
+        Ok(())
+    }
+}
+
+// A filesystem that cannot hold everything planned to be written to it
+#[derive(Debug, Clone)]
+pub struct SpaceShortage {
+    info: SpaceInfo,
+    needed: u64,
+}
+
+impl SpaceShortage {
+    pub fn get_info(&self) -> &SpaceInfo {
+        &self.info
+    }
+
+    pub fn get_needed(&self) -> u64 {
+        self.needed
+    }
+}
+
+#[cfg(unix)]
+pub fn get_space_info(path: &Path) -> Result<SpaceInfo, String> {
+    use nix::sys::statvfs::{statvfs, FsFlags};
+
+    let stat = statvfs(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    let fragment_size = stat.fragment_size() as u64;
+    let (mount_point, filesystem) = get_mount(path);
+    Ok(SpaceInfo {
+        path: PathBuf::from(path),
+        mount_point,
+        filesystem,
+        total: stat.blocks() as u64 * fragment_size,
+        used: stat.blocks().saturating_sub(stat.blocks_free()) as u64 * fragment_size,
+        available: stat.blocks_available() as u64 * fragment_size,
+        read_only: stat.flags().contains(FsFlags::ST_RDONLY),
+    })
+}
+
+#[cfg(not(unix))]
+pub fn get_space_info(path: &Path) -> Result<SpaceInfo, String> {
+    Err(format!(
+        "{}: Free space is not available on this system",
+        path.display()
+    ))
+}
+
+// Moves within one filesystem are renames that need no space. When either
+// side cannot be read the move is assumed to copy.
+pub fn is_same_device(source: &Path, destination: &Path) -> bool {
+    let device = |path: &Path| {
+        get_existing_ancestor(path)
+            .and_then(|existing| fs::metadata(existing).ok())
+            .and_then(|metadata| index::get_file_id(&metadata).0)
+    };
+    match (device(source), device(destination)) {
+        (Some(source), Some(destination)) => source == destination,
+        _ => false,
+    }
+}
+
+// Adds up the bytes written below each destination per filesystem. Targets
+// that do not exist yet are checked on their closest existing parent.
+pub fn find_space_shortages(writes: &[(&Path, u64)]) -> Vec<SpaceShortage> {
+    let mut needed: HashMap<Option<u64>, (PathBuf, u64)> = HashMap::new();
+    for (destination, size) in writes {
+        let Some(existing) = get_existing_ancestor(destination) else {
+            continue;
+        };
+        let Ok(metadata) = fs::metadata(existing) else {
+            continue;
+        };
+        needed
+            .entry(index::get_file_id(&metadata).0)
+            .or_insert_with(|| (PathBuf::from(existing), 0))
+            .1 += size;
+    }
+    let mut shortages: Vec<SpaceShortage> = needed
+        .into_values()
+        .filter_map(|(path, needed)| {
+            let info = get_space_info(&path).ok()?;
+            (info.read_only || needed > info.available).then_some(SpaceShortage { info, needed })
+        })
+        .collect();
+    shortages.sort_by(|a, b| a.info.path.cmp(&b.info.path));
+    shortages
+}
+
+fn get_existing_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|ancestor| ancestor.exists())
+}
+
+// The mount table lists every mount point, the one closest to the path is
+// the filesystem it is on
+#[cfg(target_os = "linux")]
+fn get_mount(path: &Path) -> (Option<PathBuf>, Option<String>) {
+    // Mount points are bytes like any path and need not be valid UTF-8
+    let Ok(mounts) = fs::read("/proc/self/mounts") else {
+        return (None, None);
+    };
+    let path = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    let mut closest: Option<(PathBuf, String)> = None;
+    for line in mounts.split(|byte| *byte == b'\n') {
+        let mut fields = line
+            .split(|byte| byte.is_ascii_whitespace())
+            .filter(|field| !field.is_empty())
+            .skip(1);
+        let (Some(mount_point), Some(filesystem)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let mount_point = PathBuf::from(unescape_mount_field(mount_point));
+        let filesystem = String::from_utf8_lossy(filesystem);
+        let is_closer = closest
+            .as_ref()
+            .is_none_or(|(closest, _)| mount_point.as_os_str().len() >= closest.as_os_str().len());
+        if path.starts_with(&mount_point) && is_closer {
+            closest = Some((mount_point, filesystem.into_owned()));
+        }
+    }
+    match closest {
+        Some((mount_point, filesystem)) => (Some(mount_point), Some(filesystem)),
+        None => (None, None),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn get_mount(path: &Path) -> (Option<PathBuf>, Option<String>) {
+    match nix::sys::statfs::statfs(path) {
+        Ok(stat) => (None, Some(String::from(stat.filesystem_type_name()))),
+        Err(_) => (None, None),
+    }
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "macos"))))]
+fn get_mount(_: &Path) -> (Option<PathBuf>, Option<String>) {
+    (None, None)
+}
+
+// Spaces and other special characters are written as octal escapes of the
+// bytes of the path
+#[cfg(target_os = "linux")]
+fn unescape_mount_field(bytes: &[u8]) -> std::ffi::OsString {
+    use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut position = 0;
+    while position < bytes.len() {
+        if bytes[position] == b'\\' {
+            let code = bytes
+                .get(position + 1..position + 4)
+                .filter(|digits| digits.iter().all(|digit| (b'0'..=b'7').contains(digit)))
+                .and_then(|digits| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 8).ok());
+            if let Some(code) = code {
+                unescaped.push(code);
+                position += 4;
+                continue;
+            }
+        }
+        unescaped.push(bytes[position]);
+        position += 1;
+    }
+    OsString::from_vec(unescaped)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescape_mount_field_decodes_octal_escapes() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let unescape = |field: &str| unescape_mount_field(field.as_bytes());
+        assert_eq!(unescape("/media/usb"), "/media/usb");
+        assert_eq!(unescape("/media/My\\040Drive"), "/media/My Drive");
+        assert_eq!(unescape("a\\011b\\012c"), "a\tb\nc");
+        assert_eq!(unescape("back\\134slash"), "back\\slash");
+        assert_eq!(unescape("caf\\303\\251"), "caf\u{e9}");
+        // Bytes that are not UTF-8 are kept, escaped or not
+        assert_eq!(unescape("/media/caf\\351").as_bytes(), b"/media/caf\xe9");
+        assert_eq!(
+            unescape_mount_field(b"/media/caf\xe9").as_bytes(),
+            b"/media/caf\xe9"
+        );
+        // Incomplete or invalid escapes are kept as they are
+        assert_eq!(unescape("end\\04"), "end\\04");
+        assert_eq!(unescape("end\\"), "end\\");
+        assert_eq!(unescape("x\\089"), "x\\089");
+        assert_eq!(unescape("x\\400"), "x\\400");
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    fs,
+    io::{Error, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use image::{DynamicImage, ImageFormat};
+
+const THUMBNAIL_DIRECTORY: &str = "thumbnails";
+const SOFTWARE: &str = "fileorder";
+
+// The sizes and cache layout of the freedesktop thumbnail specification
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThumbnailSize {
+    Normal,
+    Large,
+}
+
+impl ThumbnailSize {
+    pub fn get_pixels(&self) -> u32 {
+        match self {
+            ThumbnailSize::Normal => 128,
+            ThumbnailSize::Large => 256,
+        }
+    }
+
+    fn get_directory(&self) -> &'static str {
+        match self {
+            ThumbnailSize::Normal => "normal",
+            ThumbnailSize::Large => "large",
+        }
+    }
+}
+
+pub fn is_thumbnailable(path: &Path) -> bool {
+    ImageFormat::from_path(path).is_ok_and(|format| format.reading_enabled())
+}
+
+// Returns the PNG bytes of the cached thumbnail, creating it when it is
+// missing or older than the image
+pub fn load(path: &Path, size: ThumbnailSize) -> Result<Vec<u8>, String> {
+    let path = fs::canonicalize(path).map_err(|error| error.to_string())?;
+    let metadata = fs::metadata(&path).map_err(|error| error.to_string())?;
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .ok_or_else(|| String::from("No modification time"))?;
+    let uri = get_uri(&path);
+    let cache_directory = get_cache_directory()
+        .ok_or_else(|| String::from("Could not find cache directory"))?
+        .join(size.get_directory());
+    let thumbnail_path = cache_directory.join(format!("{:x}.png", md5::compute(&uri)));
+
+    if let Ok(bytes) = fs::read(&thumbnail_path) {
+        if is_current(&bytes, &uri, mtime) {
+            return Ok(bytes);
+        }
+    }
+    // Thumbnails of thumbnails are not cached
+    if path.starts_with(&cache_directory) {
+        return fs::read(&path).map_err(|error| error.to_string());
+    }
+
+    let image = image::open(&path).map_err(|error| error.to_string())?;
+    let pixels = size.get_pixels();
+    let image = if image.width() > pixels || image.height() > pixels {
+        image.thumbnail(pixels, pixels)
+    } else {
+        image
+    };
+    let bytes = encode(&image, &uri, mtime, metadata.len()).map_err(|error| error.to_string())?;
+    if let Err(error) = write_thumbnail(&cache_directory, &thumbnail_path, &bytes) {
+        eprintln!(
+            "Error writing thumbnail {}: {}",
+            thumbnail_path.display(),
+            error
+        );
+    }
+    Ok(bytes)
+}
+
+// A cached thumbnail is valid when it was made from the same file at the
+// same modification time
+fn is_current(bytes: &[u8], uri: &str, mtime: u64) -> bool {
+    let Ok(reader) = png::Decoder::new(bytes).read_info() else {
+        return false;
+    };
+    let text = |keyword: &str| {
+        reader
+            .info()
+            .uncompressed_latin1_text
+            .iter()
+            .find(|chunk| chunk.keyword == keyword)
+            .map(|chunk| chunk.text.clone())
+    };
+    text("Thumb::URI").as_deref() == Some(uri)
+        && text("Thumb::MTime").as_deref() == Some(mtime.to_string().as_str())
+}
+
+fn encode(image: &DynamicImage, uri: &str, mtime: u64, size: u64) -> Result<Vec<u8>, Error> {
+    let image = image.to_rgba8();
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let text_chunks = [
+        ("Thumb::URI", String::from(uri)),
+        ("Thumb::MTime", mtime.to_string()),
+        ("Thumb::Size", size.to_string()),
+        ("Software", String::from(SOFTWARE)),
+    ];
+    for (keyword, text) in text_chunks {
+        encoder
+            .add_text_chunk(String::from(keyword), text)
+            .map_err(Error::other)?;
+    }
+    let mut writer = encoder.write_header().map_err(Error::other)?;
+    writer.write_image_data(&image).map_err(Error::other)?;
+    writer.finish().map_err(Error::other)?;
+    Ok(bytes)
+}
+
+// Written to a temporary file and renamed, so other programs reading the
+// cache never see a partial thumbnail. Thumbnails of private images are only
+// readable by their owner, as the specification asks.
+fn write_thumbnail(
+    cache_directory: &Path,
+    thumbnail_path: &Path,
+    bytes: &[u8],
+) -> Result<(), Error> {
+    create_private_directory(cache_directory)?;
+    let temporary_path = thumbnail_path.with_extension(format!("{}.tmp", std::process::id()));
+    let written = create_private_file(&temporary_path)
+        .and_then(|mut file| file.write_all(bytes))
+        .and_then(|_| fs::rename(&temporary_path, thumbnail_path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary_path);
+    }
+    written
+}
+
+// The size directory and the thumbnail directory above it
+#[cfg(unix)]
+fn create_private_directory(directory: &Path) -> Result<(), Error> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(directory)?;
+    for directory in directory.ancestors().take(2) {
+        fs::set_permissions(directory, fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn create_private_directory(directory: &Path) -> Result<(), Error> {
+    fs::create_dir_all(directory)
+}
+
+#[cfg(unix)]
+fn create_private_file(path: &Path) -> Result<fs::File, Error> {
+    use std::os::unix::fs::OpenOptionsExt;
+    fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private_file(path: &Path) -> Result<fs::File, Error> {
+    fs::File::create(path)
+}
+
+fn get_cache_directory() -> Option<PathBuf> {
+    dirs::cache_dir().map(|directory| directory.join(THUMBNAIL_DIRECTORY))
+}
+
+// An absolute `file://` URI with everything but unreserved characters and
+// the path separators percent-encoded
+fn get_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.as_os_str().as_encoded_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                uri.push(*byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uri_escapes_all_but_unreserved_characters() {
+        assert_eq!(
+            get_uri(Path::new("/home/me/My Photos/a-b_c.~1.jpg")),
+            "file:///home/me/My%20Photos/a-b_c.~1.jpg"
+        );
+        assert_eq!(
+            get_uri(Path::new("/tmp/caf\u{e9}#1%.png")),
+            "file:///tmp/caf%C3%A9%231%25.png"
+        );
+    }
+
+    #[test]
+    fn thumbnail_is_current_for_the_same_uri_and_time() {
+        let image = DynamicImage::new_rgba8(2, 2);
+        let bytes = encode(&image, "file:///tmp/a.png", 1000, 10).unwrap();
+        assert!(is_current(&bytes, "file:///tmp/a.png", 1000));
+        assert!(!is_current(&bytes, "file:///tmp/a.png", 1001));
+        assert!(!is_current(&bytes, "file:///tmp/b.png", 1000));
+        assert!(!is_current(b"not a png", "file:///tmp/a.png", 1000));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn thumbnails_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-thumbnail-{}", std::process::id()));
+        let cache_directory = directory.join(THUMBNAIL_DIRECTORY).join("normal");
+        let thumbnail_path = cache_directory.join("a.png");
+        write_thumbnail(&cache_directory, &thumbnail_path, b"png").unwrap();
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&thumbnail_path), 0o600);
+        assert_eq!(mode(&cache_directory), 0o700);
+        assert_eq!(mode(&directory.join(THUMBNAIL_DIRECTORY)), 0o700);
+        assert_eq!(fs::read_dir(&cache_directory).unwrap().count(), 1);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
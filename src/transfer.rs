@@ -0,0 +1,408 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File, Metadata, OpenOptions},
+    io::{Error, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use filetime::FileTime;
+
+use crate::index;
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+// Matches the partial copies named by get_partial_path
+pub const PARTIAL_PATTERN: &str = ".*.part";
+
+// How far a run of copies got, with the bytes written of the current file
+#[derive(Debug, Clone)]
+pub struct TransferProgress {
+    done: usize,
+    count: usize,
+    path: PathBuf,
+    copied: u64,
+    size: u64,
+}
+
+impl TransferProgress {
+    pub fn build(done: usize, count: usize, path: &Path, copied: u64, size: u64) -> Self {
+        Self {
+            done,
+            count,
+            path: PathBuf::from(path),
+            copied,
+            size,
+        }
+    }
+
+    pub fn get_done(&self) -> usize {
+        self.done
+    }
+
+    pub fn get_count(&self) -> usize {
+        self.count
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_copied(&self) -> u64 {
+        self.copied
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+}
+
+// Progress is sent on a few times a second, not for every chunk
+#[derive(Debug, Default)]
+pub struct ProgressThrottle {
+    last_sent: Option<Instant>,
+}
+
+impl ProgressThrottle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_due(&mut self) -> bool {
+        if self
+            .last_sent
+            .is_some_and(|sent| sent.elapsed() < PROGRESS_INTERVAL)
+        {
+            return false;
+        }
+        self.last_sent = Some(Instant::now());
+        true
+    }
+}
+
+// Renames when source and destination are on the same filesystem, and copies
+// then removes the original otherwise. An existing destination is never
+// replaced.
+pub fn move_file(
+    source: &Path,
+    destination: &Path,
+    verify: bool,
+    progress: &mut impl FnMut(u64, u64),
+) -> Result<(), Error> {
+    match rename_new(source, destination) {
+        Ok(()) => return Ok(()),
+        Err(error) if error.kind() == ErrorKind::CrossesDevices => {}
+        Err(error) => return Err(error),
+    }
+    copy_file(source, destination, verify, progress)?;
+    fs::remove_file(source)
+}
+
+// The copy is written next to the destination under a hidden name and only
+// renamed once it is on disk, so a destination never holds half a file. An
+// interrupted copy of the same unchanged source carries on where it stopped.
+pub fn copy_file(
+    source: &Path,
+    destination: &Path,
+    verify: bool,
+    progress: &mut impl FnMut(u64, u64),
+) -> Result<(), Error> {
+    copy_to(source, destination, verify, false, progress)
+}
+
+// Like copy_file, but an existing destination is swapped for the new copy in
+// one step once the copy is complete
+pub fn replace_file(
+    source: &Path,
+    destination: &Path,
+    verify: bool,
+    progress: &mut impl FnMut(u64, u64),
+) -> Result<(), Error> {
+    copy_to(source, destination, verify, true, progress)
+}
+
+fn copy_to(
+    source: &Path,
+    destination: &Path,
+    verify: bool,
+    replace: bool,
+    progress: &mut impl FnMut(u64, u64),
+) -> Result<(), Error> {
+    let metadata = fs::metadata(source)?;
+    if !replace && fs::symlink_metadata(destination).is_ok() {
+        return Err(already_exists());
+    }
+    let total = metadata.len();
+    let partial_path = get_partial_path(destination, &metadata)?;
+
+    let mut reader = File::open(source)?;
+    let mut writer = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&partial_path)?;
+    let mut copied = writer.metadata()?.len();
+    if copied > total {
+        copied = 0;
+    }
+    writer.set_len(copied)?;
+    writer.seek(SeekFrom::Start(copied))?;
+    reader.seek(SeekFrom::Start(copied))?;
+    progress(copied, total);
+
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..read])?;
+        copied += read as u64;
+        progress(copied, total);
+    }
+    writer.sync_all()?;
+    drop(writer);
+
+    // The copy is complete, so it is removed instead of resumed when it
+    // cannot be put in place
+    let finished = finish_copy(
+        source,
+        &partial_path,
+        destination,
+        &metadata,
+        verify,
+        replace,
+    );
+    if finished.is_err() {
+        let _ = fs::remove_file(&partial_path);
+    }
+    finished
+}
+
+fn finish_copy(
+    source: &Path,
+    partial_path: &Path,
+    destination: &Path,
+    metadata: &Metadata,
+    verify: bool,
+    replace: bool,
+) -> Result<(), Error> {
+    if verify && index::hash_file(source)? != index::hash_file(partial_path)? {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Copy does not match the original",
+        ));
+    }
+    preserve_metadata(source, partial_path, metadata)?;
+    if replace {
+        fs::rename(partial_path, destination)?;
+    } else {
+        rename_new(partial_path, destination)?;
+    }
+    if let Some(parent) = destination.parent() {
+        sync_directory(parent);
+    }
+    Ok(())
+}
+
+// Renames without replacing a destination that exists, even one created just
+// before. Filesystems that cannot refuse to replace are checked first instead.
+fn rename_new(source: &Path, destination: &Path) -> Result<(), Error> {
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    {
+        use nix::{
+            errno::Errno,
+            fcntl::{renameat2, RenameFlags},
+        };
+        match renameat2(
+            None,
+            source,
+            None,
+            destination,
+            RenameFlags::RENAME_NOREPLACE,
+        ) {
+            Ok(()) => return Ok(()),
+            Err(Errno::EINVAL | Errno::ENOSYS) => {}
+            Err(errno) => return Err(Error::from(errno)),
+        }
+    }
+    if fs::symlink_metadata(destination).is_ok() {
+        return Err(already_exists());
+    }
+    fs::rename(source, destination)
+}
+
+fn already_exists() -> Error {
+    Error::new(ErrorKind::AlreadyExists, "Destination already exists")
+}
+
+// Named after the size and modification time of the source, so a changed
+// source starts over instead of resuming
+fn get_partial_path(destination: &Path, metadata: &Metadata) -> Result<PathBuf, Error> {
+    let (Some(parent), Some(name)) = (destination.parent(), destination.file_name()) else {
+        return Err(Error::new(ErrorKind::InvalidInput, "Invalid destination"));
+    };
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|modified| modified.as_secs())
+        .unwrap_or_default();
+    let mut partial_name = OsString::from(".");
+    partial_name.push(name);
+    partial_name.push(format!(".{:x}-{:x}.part", metadata.len(), modified));
+    Ok(parent.join(partial_name))
+}
+
+// Filesystems such as FAT on USB drives have no permissions or extended
+// attributes, so only the times have to be kept
+fn preserve_metadata(source: &Path, copy: &Path, metadata: &Metadata) -> Result<(), Error> {
+    copy_extended_attributes(source, copy);
+    filetime::set_file_times(
+        copy,
+        FileTime::from_last_access_time(metadata),
+        FileTime::from_last_modification_time(metadata),
+    )?;
+    let _ = fs::set_permissions(copy, metadata.permissions());
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_extended_attributes(source: &Path, copy: &Path) {
+    let Ok(names) = xattr::list(source) else {
+        return;
+    };
+    for name in names {
+        if let Ok(Some(value)) = xattr::get(source, &name) {
+            let _ = xattr::set(copy, &name, &value);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn copy_extended_attributes(_: &Path, _: &Path) {}
+
+// The new name is only durable once the directory itself is written out
+#[cfg(unix)]
+fn sync_directory(directory: &Path) {
+    if let Ok(directory) = File::open(directory) {
+        let _ = directory.sync_all();
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_directory(_: &Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "fileorder-transfer-{}-{}",
+            name,
+            std::process::id()
+        ));
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    // Copies and returns where the copy started
+    fn copy(source: &Path, destination: &Path, verify: bool) -> (Result<(), Error>, Option<u64>) {
+        let mut started = None;
+        let result = copy_file(source, destination, verify, &mut |copied, _| {
+            started.get_or_insert(copied);
+        });
+        (result, started)
+    }
+
+    #[test]
+    fn copy_resumes_from_partial() {
+        let directory = create_directory("resume");
+        let (source, destination) = (directory.join("source"), directory.join("destination"));
+        fs::write(&source, "hello world").unwrap();
+        let partial_path = get_partial_path(&destination, &fs::metadata(&source).unwrap()).unwrap();
+        fs::write(&partial_path, "hello").unwrap();
+
+        let (result, started) = copy(&source, &destination, true);
+        result.unwrap();
+        assert_eq!(started, Some(5));
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "hello world");
+        assert!(!partial_path.exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn copy_starts_over_when_source_changed() {
+        let directory = create_directory("changed");
+        let (source, destination) = (directory.join("source"), directory.join("destination"));
+        fs::write(&source, "old").unwrap();
+        let old_partial_path =
+            get_partial_path(&destination, &fs::metadata(&source).unwrap()).unwrap();
+        fs::write(&old_partial_path, "old").unwrap();
+        fs::write(&source, "new contents").unwrap();
+        let partial_path = get_partial_path(&destination, &fs::metadata(&source).unwrap()).unwrap();
+        assert_ne!(partial_path, old_partial_path);
+
+        let (result, started) = copy(&source, &destination, false);
+        result.unwrap();
+        assert_eq!(started, Some(0));
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "new contents");
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn failed_verify_removes_partial() {
+        let directory = create_directory("verify");
+        let (source, destination) = (directory.join("source"), directory.join("destination"));
+        fs::write(&source, "hello world").unwrap();
+        let partial_path = get_partial_path(&destination, &fs::metadata(&source).unwrap()).unwrap();
+        fs::write(&partial_path, "HELLO").unwrap();
+
+        let (result, _) = copy(&source, &destination, true);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+        assert!(!partial_path.exists());
+        assert!(!destination.exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn existing_destination_is_never_replaced() {
+        let directory = create_directory("existing");
+        let (source, destination) = (directory.join("source"), directory.join("destination"));
+        fs::write(&source, "new").unwrap();
+        fs::write(&destination, "old").unwrap();
+        let partial_path = get_partial_path(&destination, &fs::metadata(&source).unwrap()).unwrap();
+
+        let (result, _) = copy(&source, &destination, false);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::AlreadyExists);
+        assert!(!partial_path.exists());
+        let moved = move_file(&source, &destination, false, &mut |_, _| {});
+        assert_eq!(moved.unwrap_err().kind(), ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&source).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "old");
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn replace_swaps_in_complete_copy() {
+        let directory = create_directory("replace");
+        let (source, destination) = (directory.join("source"), directory.join("destination"));
+        fs::write(&source, "new contents").unwrap();
+        fs::write(&destination, "old").unwrap();
+
+        replace_file(&source, &destination, true, &mut |_, _| {}).unwrap();
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "new contents");
+        let names: Vec<OsString> = fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names.len(), 2);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
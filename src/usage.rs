@@ -0,0 +1,217 @@
+use std::{
+    cmp::Reverse,
+    ffi::{OsStr, OsString},
+    fmt,
+    fs::{self, Metadata},
+    path::{Path, PathBuf},
+};
+
+use crate::{filter::EntryFilter, index};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UsageSort {
+    Name,
+    Size,
+    Files,
+}
+
+impl fmt::Display for UsageSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            UsageSort::Name => "Name",
+            UsageSort::Size => "Size",
+            UsageSort::Files => "Files",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+// A directory with the total size and number of files below it. Files are
+// not kept, only what they add up to in each directory.
+#[derive(Debug, Clone)]
+pub struct UsageNode {
+    name: OsString,
+    path: PathBuf,
+    size: u64,
+    files: usize,
+    file_size: u64,
+    file_count: usize,
+    directories: Vec<UsageNode>,
+}
+
+impl UsageNode {
+    fn new(path: &Path) -> Self {
+        Self {
+            name: path.file_name().map(OsString::from).unwrap_or_default(),
+            path: PathBuf::from(path),
+            size: 0,
+            files: 0,
+            file_size: 0,
+            file_count: 0,
+            directories: Vec::new(),
+        }
+    }
+
+    pub fn get_name(&self) -> &OsStr {
+        &self.name
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn get_files(&self) -> usize {
+        self.files
+    }
+
+    // The files directly inside this directory
+    pub fn get_file_size(&self) -> u64 {
+        self.file_size
+    }
+
+    pub fn get_file_count(&self) -> usize {
+        self.file_count
+    }
+
+    pub fn get_directories(&self) -> &Vec<UsageNode> {
+        &self.directories
+    }
+
+    pub fn get_sorted_directories(&self, sort: UsageSort, descending: bool) -> Vec<&UsageNode> {
+        let mut directories: Vec<&UsageNode> = self.directories.iter().collect();
+        match sort {
+            UsageSort::Name => directories.sort_by(|a, b| a.name.cmp(&b.name)),
+            UsageSort::Size => directories.sort_by_key(|directory| directory.size),
+            UsageSort::Files => directories.sort_by_key(|directory| directory.files),
+        }
+        if descending {
+            directories.reverse();
+        }
+        directories
+    }
+
+    pub fn find(&self, path: &Path) -> Option<&UsageNode> {
+        let relative = path.strip_prefix(&self.path).ok()?;
+        let mut node = self;
+        for component in relative.components() {
+            node = node
+                .directories
+                .iter()
+                .find(|directory| directory.name == component.as_os_str())?;
+        }
+        Some(node)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UsageReport {
+    root: UsageNode,
+    errors: Vec<String>,
+}
+
+impl UsageReport {
+    pub fn get_root(&self) -> &UsageNode {
+        &self.root
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+}
+
+// Sizes are the space the files take up on the drive, so sparse files count
+// only what is written. Symlinks are not followed, other drives mounted below
+// root are left out and so are ignored entries, like in the browser.
+pub fn measure(root: &Path, entry_filter: &EntryFilter) -> UsageReport {
+    let mut errors = Vec::new();
+    let device = fs::metadata(root)
+        .ok()
+        .and_then(|metadata| index::get_file_id(&metadata).0);
+    let root = measure_directory(root, device, entry_filter, &mut errors);
+    UsageReport { root, errors }
+}
+
+fn measure_directory(
+    directory: &Path,
+    device: Option<u64>,
+    entry_filter: &EntryFilter,
+    errors: &mut Vec<String>,
+) -> UsageNode {
+    let mut node = UsageNode::new(directory);
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(error) => {
+            errors.push(format!("{}: {}", directory.display(), error));
+            return node;
+        }
+    };
+    let directory_filter = entry_filter.for_directory(directory);
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let path = entry.path();
+        if directory_filter.is_ignored(&path, metadata.is_dir()) {
+            continue;
+        }
+        if metadata.is_dir() {
+            if index::get_file_id(&metadata).0 != device {
+                continue;
+            }
+            node.directories
+                .push(measure_directory(&path, device, entry_filter, errors));
+        } else if metadata.is_file() {
+            node.file_size += get_allocated_size(&metadata);
+            node.file_count += 1;
+        }
+    }
+    node.directories
+        .sort_by_key(|directory| Reverse(directory.size));
+    node.size = node.file_size + node.directories.iter().map(|d| d.size).sum::<u64>();
+    node.files = node.file_count + node.directories.iter().map(|d| d.files).sum::<usize>();
+    node
+}
+
+#[cfg(unix)]
+fn get_allocated_size(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn get_allocated_size(metadata: &Metadata) -> u64 {
+    metadata.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measure_adds_up_allocated_sizes() {
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-usage-{}", std::process::id()));
+        fs::create_dir_all(directory.join("a/b")).unwrap();
+        fs::write(directory.join("one.txt"), "one").unwrap();
+        fs::write(directory.join("a/b/two.txt"), vec![0; 10000]).unwrap();
+        fs::File::create(directory.join("a/sparse"))
+            .and_then(|file| file.set_len(1 << 30))
+            .unwrap();
+
+        let report = measure(&directory, &EntryFilter::new());
+        assert!(report.get_errors().is_empty());
+        let root = report.get_root();
+        assert_eq!(root.get_files(), 3);
+        assert_eq!(root.get_file_count(), 1);
+        let a = root.find(&directory.join("a")).unwrap();
+        assert_eq!(a.get_files(), 2);
+        assert_eq!(root.get_size(), root.get_file_size() + a.get_size());
+        assert!(a.get_size() < 1 << 30);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -1,6 +1,8 @@
 use std::{
     ffi::{OsStr, OsString},
-    fs,
+    fs::{self, File},
+    io::{Error, Write},
+    path::{Component, Path, PathBuf, Prefix},
 };
 
 use crate::{directory::Directory, fileorder::OperatingSystem};
@@ -13,7 +15,7 @@ pub fn remove_directory_from_path(path: &OsStr, operating_system: &OperatingSyst
             if index == splitted.len() - 1 {
                 break;
             }
-            if is_drive_indentifier(&dir_name) || dir_name.is_empty() {
+            if is_drive_indentifier(dir_name) || dir_name.is_empty() {
                 if let OperatingSystem::Windows = operating_system {
                     filtered_path.push(dir_name);
                 }
@@ -28,6 +30,132 @@ pub fn remove_directory_from_path(path: &OsStr, operating_system: &OperatingSyst
     OsString::from(path)
 }
 
+// For labels only, as names that are not valid UTF-8 are replaced
+pub fn display_path(path: &OsStr) -> String {
+    if path.is_empty() {
+        return String::from("/");
+    }
+    path.to_string_lossy().to_string()
+}
+
+// The path to read and write, which is the root while it is empty
+pub fn current_path_buf(path: &OsStr) -> PathBuf {
+    if path.is_empty() {
+        return PathBuf::from("/");
+    }
+    PathBuf::from(path)
+}
+
+// Writes a sibling file and renames it over path once it is on disk, so an
+// interrupted write never leaves path truncated
+pub fn write_file_atomically(path: &Path, content: &[u8]) -> Result<(), Error> {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(Error::other("Invalid path"));
+    };
+    let mut temporary_name = OsString::from(".");
+    temporary_name.push(name);
+    temporary_name.push(format!(".{}.tmp", std::process::id()));
+    let temporary_path = parent.join(temporary_name);
+    let written = File::create(&temporary_path)
+        .and_then(|mut file| file.write_all(content).and_then(|_| file.sync_all()))
+        .and_then(|_| fs::rename(&temporary_path, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary_path);
+    }
+    written
+}
+
+pub fn get_path_breadcrumbs(path: &OsStr) -> Vec<(String, OsString)> {
+    let mut breadcrumbs: Vec<(String, OsString)> = Vec::new();
+    let mut ancestor = OsString::new();
+    for component in current_path_buf(path).components() {
+        match component {
+            Component::Prefix(prefix) => {
+                ancestor.push(prefix.as_os_str());
+                breadcrumbs.push((
+                    prefix.as_os_str().to_string_lossy().to_string(),
+                    ancestor.clone(),
+                ));
+            }
+            Component::RootDir => {
+                ancestor.push("/");
+                if let Some(last) = breadcrumbs.last_mut() {
+                    last.1 = ancestor.clone();
+                } else {
+                    breadcrumbs.push((String::from("/"), ancestor.clone()));
+                }
+            }
+            Component::Normal(name) => {
+                if !ancestor.to_string_lossy().ends_with('/') {
+                    ancestor.push("/");
+                }
+                ancestor.push(name);
+                breadcrumbs.push((name.to_string_lossy().to_string(), ancestor.clone()));
+            }
+            _ => {}
+        }
+    }
+    breadcrumbs
+}
+
+// Splits an absolute path into the path the directory tree is read from and
+// the directory names leading from there to the target directory
+pub fn split_path(path: &Path) -> (OsString, OsString, Vec<OsString>) {
+    let mut root_path = OsString::from("/");
+    let mut current_path = OsString::new();
+    let mut components: Vec<OsString> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Prefix(prefix) => {
+                let drive = match prefix.kind() {
+                    Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => {
+                        format!("{}:", letter as char)
+                    }
+                    _ => prefix.as_os_str().to_string_lossy().to_string(),
+                };
+                current_path = OsString::from(&drive);
+                root_path = OsString::from(drive + "/");
+            }
+            Component::Normal(name) => components.push(OsString::from(name)),
+            _ => {}
+        }
+    }
+    (root_path, current_path, components)
+}
+
+pub fn complete_path(input: &str) -> Option<String> {
+    let separator_index = input.rfind(['/', '\\'])?;
+    let (parent, prefix) = input.split_at(separator_index + 1);
+    let entries = fs::read_dir(if parent.is_empty() { "/" } else { parent }).ok()?;
+
+    let mut matches: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false))
+        .filter_map(|entry| entry.file_name().to_str().map(String::from))
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    matches.sort();
+
+    let first = matches.first()?;
+    let mut common_length = first.len();
+    for name in &matches {
+        common_length = common_length.min(
+            first
+                .chars()
+                .zip(name.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a.len_utf8())
+                .sum(),
+        );
+    }
+
+    let mut completed = format!("{}{}", parent, &first[..common_length]);
+    if matches.len() == 1 {
+        completed.push('/');
+    }
+    Some(completed)
+}
+
 pub fn find_directory_index_by_id(
     current_dir: &mut Directory,
     current_position: usize,
@@ -82,7 +210,7 @@ fn get_external_storage_devices_on_macos(storage_paths: &mut Vec<OsString>) -> s
 fn get_external_storage_devices_on_windows(
     storage_paths: &mut Vec<OsString>,
 ) -> std::io::Result<()> {
-    for drive_letter in 'A'..'Z' {
+    for drive_letter in 'A'..='Z' {
         let read_dir_path = format!("{}:", drive_letter);
         match fs::read_dir(read_dir_path) {
             Ok(_) => {
@@ -119,8 +247,128 @@ fn is_drive_indentifier(directory_name: &str) -> bool {
 }
 
 fn is_drive_letter(character: &char) -> bool {
-    match character {
-        'A'..'Z' => true,
-        _ => false,
+    character.is_ascii_uppercase()
+}
+
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        return format!("{} B", size);
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+// Sizes such as `500MB`, `1.5 GB` or `2G`, counted in powers of 1024
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (amount, unit) = size.split_at(split);
+    let amount: f64 = amount.parse().ok()?;
+    let exponent = match unit.trim().to_uppercase().trim_end_matches('B') {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => return None,
+    };
+    Some((amount * 1024f64.powi(exponent)) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_are_counted_in_powers_of_1024() {
+        assert_eq!(parse_size("100"), Some(100));
+        assert_eq!(parse_size("10kb"), Some(10 * 1024));
+        assert_eq!(parse_size("500MB"), Some(500 * 1024 * 1024));
+        assert_eq!(parse_size(" 1.5 GB "), Some(1536 * 1024 * 1024));
+        assert_eq!(parse_size("2G"), Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("1T"), Some(1024u64.pow(4)));
+    }
+
+    #[test]
+    fn invalid_sizes_are_rejected() {
+        assert_eq!(parse_size(""), None);
+        assert_eq!(parse_size("MB"), None);
+        assert_eq!(parse_size("-5MB"), None);
+        assert_eq!(parse_size("5XB"), None);
+        assert_eq!(parse_size("1.2.3K"), None);
+    }
+
+    #[test]
+    fn atomic_write_replaces_file_without_leftovers() {
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-util-write-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("settings.toml");
+        fs::write(&path, "old").unwrap();
+
+        write_file_atomically(&path, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+        assert!(write_file_atomically(&directory.join("missing/file"), b"").is_err());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn current_path_keeps_names_that_are_not_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        assert_eq!(current_path_buf(OsStr::new("")), PathBuf::from("/"));
+        let path = OsStr::from_bytes(b"/home/caf\xe9");
+        assert_eq!(current_path_buf(path).as_os_str(), path);
+        let breadcrumbs = get_path_breadcrumbs(path);
+        assert_eq!(breadcrumbs.last().unwrap().1, path);
+    }
+
+    #[test]
+    fn split_path_lists_directories_below_root() {
+        let (root_path, current_path, components) = split_path(Path::new("/home/user/Documents"));
+        assert_eq!(root_path, OsString::from("/"));
+        assert_eq!(current_path, OsString::new());
+        assert_eq!(components, vec!["home", "user", "Documents"]);
+    }
+
+    #[test]
+    fn split_path_of_root_has_no_components() {
+        let (root_path, _, components) = split_path(Path::new("/"));
+        assert_eq!(root_path, OsString::from("/"));
+        assert!(components.is_empty());
+    }
+
+    #[test]
+    fn complete_path_extends_to_common_prefix() {
+        let directory =
+            std::env::temp_dir().join(format!("fileorder-complete-{}", std::process::id()));
+        fs::create_dir_all(directory.join("alpha")).unwrap();
+        fs::create_dir_all(directory.join("alpine")).unwrap();
+        fs::write(directory.join("alps.txt"), "").unwrap();
+        let parent = directory.display().to_string();
+
+        assert_eq!(
+            complete_path(&format!("{}/al", parent)),
+            Some(format!("{}/alp", parent))
+        );
+        assert_eq!(
+            complete_path(&format!("{}/alph", parent)),
+            Some(format!("{}/alpha/", parent))
+        );
+        assert_eq!(complete_path(&format!("{}/beta", parent)), None);
+        assert_eq!(complete_path("no separator"), None);
+
+        fs::remove_dir_all(&directory).unwrap();
     }
 }